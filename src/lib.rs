@@ -1,3 +1,4 @@
 pub mod traffic_logic;
 pub mod simulator;
-pub mod visualizer;
\ No newline at end of file
+pub mod visualizer;
+pub mod scenario;
\ No newline at end of file
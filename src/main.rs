@@ -1,4 +1,5 @@
-use traffic_sim::simulator::{Simulator, Position, Current};
+use traffic_sim::simulator::{Simulator, demand::{Demand, OdPair}};
+use traffic_sim::traffic_logic::intersection::IntersectionId;
 
 fn main() {
 
@@ -6,12 +7,14 @@ fn main() {
     simulator.add_intersections(4);
 
 
-    simulator.add_road(1, 1, 2, 5);//East
-    simulator.add_road(1, 2, 3, 3);//South
-    simulator.add_road(1, 3, 4, 6);//West
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);//East
+    simulator.add_road(IntersectionId(1), 2, IntersectionId(3), 3);//South
+    simulator.add_road(IntersectionId(1), 3, IntersectionId(4), 6);//West
 
-    simulator.add_car(Position::new(Some(Current{int_id:1, direction:1}),None));
-    simulator.add_car(Position::new(Some(Current{int_id:1, direction:1}),None));
+    simulator.set_demand(Demand::new(vec![
+        OdPair{origin: IntersectionId(2), destination: IntersectionId(4), trips_per_hour: 360.0},
+        OdPair{origin: IntersectionId(3), destination: IntersectionId(2), trips_per_hour: 180.0}
+    ]));
     use std::time::Instant;
     let now = Instant::now();
     simulator.run(10000);
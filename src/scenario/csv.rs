@@ -0,0 +1,216 @@
+use crate::scenario::ScenarioError;
+use crate::simulator::{demand::OdPair, estimation::{CountLocation, ObservedCount}};
+use crate::traffic_logic::{intersection::IntersectionId, car::{Direction, TurningProportions}};
+use std::path::Path;
+
+///Splits CSV text into its rows, with the line number of each. Blank lines and lines starting with `#` are skipped.
+/// Quoting is not supported, no field in the files read here has a comma in it.
+pub(crate) fn rows(text: &str) -> Vec<(usize, Vec<&str>)>
+{
+    text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(number, line)| (number + 1, line.split(',').map(str::trim).collect()))
+        .collect()
+}
+
+pub(crate) fn parse_field<T: std::str::FromStr>(line: usize, field: &str, what: &str) -> Result<T, ScenarioError>
+{
+    field.parse().map_err(|_| ScenarioError::Csv{line, message: format!("'{}' is not a valid {}", field, what)})
+}
+
+///Reads a side of an intersection written as `N`, `North`, `0` and so on
+pub(crate) fn parse_side(line: usize, field: &str) -> Result<u8, ScenarioError>
+{
+    match field.to_lowercase().as_str(){
+        "n" | "north" | "0" => Ok(0),
+        "e" | "east" | "1" => Ok(1),
+        "s" | "south" | "2" => Ok(2),
+        "w" | "west" | "3" => Ok(3),
+        _ => Err(ScenarioError::Csv{line, message: format!("'{}' is not a direction", field)})
+    }
+}
+
+///A header row is one whose fields are not all numbers
+fn is_header(fields: &[&str]) -> bool
+{
+    fields.iter().any(|field| !field.is_empty() && field.parse::<f64>().is_err())
+}
+
+///Reads an origin–destination matrix from CSV text, in one of two layouts.
+///
+/// A list has one `origin,destination,trips_per_hour` row per pair, with an optional header row.
+/// A matrix has a header row that starts with an empty field followed by the destination IDs, and
+/// a row for every origin that starts with its ID followed by the trips per hour to each destination.
+/// Intersections are given by their ID and pairs with no trips are left out.
+/// # Examples
+///
+/// ```rust
+/// use traffic_sim::scenario::csv::parse_od_csv;
+/// let list = parse_od_csv("origin,destination,trips_per_hour\n1,3,120\n2,3,60\n").unwrap();
+/// let matrix = parse_od_csv(",3\n1,120\n2,60\n").unwrap();
+/// assert_eq!(list, matrix);
+/// ```
+pub fn parse_od_csv(text: &str) -> Result<Vec<OdPair>, ScenarioError>
+{
+    let rows = rows(text);
+    let Some((first_line, first)) = rows.first() else {
+        return Ok(Vec::new());
+    };
+    let mut pairs = Vec::new();
+    if first.first().is_some_and(|field| field.is_empty()){
+        let destinations = first[1..].iter()
+            .map(|field| parse_field(*first_line, field, "intersection ID").map(IntersectionId))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (line, fields) in rows.iter().skip(1){
+            if fields.len() != destinations.len() + 1{
+                return Err(ScenarioError::Csv{line: *line, message: format!("expected {} fields, found {}", destinations.len() + 1, fields.len())});
+            }
+            let origin = IntersectionId(parse_field(*line, fields[0], "intersection ID")?);
+            for (destination, field) in destinations.iter().zip(&fields[1..]){
+                let trips_per_hour: f64 = parse_field(*line, field, "number of trips")?;
+                if trips_per_hour > 0.0{
+                    pairs.push(OdPair{origin, destination: *destination, trips_per_hour});
+                }
+            }
+        }
+        return Ok(pairs);
+    }
+    let skip = usize::from(is_header(first));
+    for (line, fields) in rows.iter().skip(skip){
+        if fields.len() != 3{
+            return Err(ScenarioError::Csv{line: *line, message: format!("expected 3 fields, found {}", fields.len())});
+        }
+        let origin = IntersectionId(parse_field(*line, fields[0], "intersection ID")?);
+        let destination = IntersectionId(parse_field(*line, fields[1], "intersection ID")?);
+        let trips_per_hour: f64 = parse_field(*line, fields[2], "number of trips")?;
+        if trips_per_hour > 0.0{
+            pairs.push(OdPair{origin, destination, trips_per_hour});
+        }
+    }
+    Ok(pairs)
+}
+
+///Reads an origin–destination matrix from a CSV file, see `parse_od_csv`
+pub fn load_od_csv(path: impl AsRef<Path>) -> Result<Vec<OdPair>, ScenarioError>
+{
+    parse_od_csv(&std::fs::read_to_string(path)?)
+}
+
+///The cars counted making each turn from one side of an intersection
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TurningCount
+{
+    pub intersection: IntersectionId,
+    ///The side the cars arrive at and wait on
+    pub approach: u8,
+    pub left: f64,
+    pub straight: f64,
+    pub right: f64,
+    pub u_turn: f64
+}
+
+impl TurningCount
+{
+    pub fn proportions(&self) -> TurningProportions
+    {
+        TurningProportions::new(self.left, self.straight, self.right, self.u_turn)
+    }
+
+    pub fn total(&self) -> f64
+    {
+        self.left + self.straight + self.right + self.u_turn
+    }
+
+    ///Splits the count into one observed count for every turn
+    pub fn observations(&self) -> Vec<ObservedCount>
+    {
+        [(Direction::Left, self.left), (Direction::Straight, self.straight), (Direction::Right, self.right), (Direction::UTurn, self.u_turn)]
+            .into_iter()
+            .map(|(turn, vehicles_per_hour)| ObservedCount{
+                location: CountLocation::Turn{int_id: self.intersection, approach: self.approach, exit: Direction::get_next_direction(self.approach, turn)},
+                vehicles_per_hour
+            })
+            .collect()
+    }
+}
+
+fn turning_row(line: usize, fields: &[&str]) -> Result<TurningCount, ScenarioError>
+{
+    let count = |index: usize| fields.get(index).map_or(Ok(0.0), |field| parse_field(line, field, "count"));
+    Ok(TurningCount{
+        intersection: IntersectionId(parse_field(line, fields[0], "intersection ID")?),
+        approach: parse_side(line, fields[1])?,
+        left: count(2)?,
+        straight: count(3)?,
+        right: count(4)?,
+        u_turn: count(5)?
+    })
+}
+
+///Reads turning movement counts from CSV text, one `intersection,approach,left,straight,right,u_turn`
+/// row per approach with an optional header row. The `u_turn` field can be left out and counts as 0.
+/// Approaches are written as `N`, `North` or `0` and so on.
+/// # Examples
+///
+/// ```rust
+/// use traffic_sim::scenario::csv::parse_turning_csv;
+/// let counts = parse_turning_csv("intersection,approach,left,straight,right\n1,N,20,150,30\n").unwrap();
+/// assert_eq!(counts[0].approach, 0);
+/// assert_eq!(counts[0].total(), 200.0);
+/// ```
+pub fn parse_turning_csv(text: &str) -> Result<Vec<TurningCount>, ScenarioError>
+{
+    let rows = rows(text);
+    let skip = rows.first().map_or(0, |(_, fields)| usize::from(is_header(&fields[..1])));
+    let mut counts = Vec::new();
+    for (line, fields) in rows.iter().skip(skip){
+        if fields.len() != 5 && fields.len() != 6{
+            return Err(ScenarioError::Csv{line: *line, message: format!("expected 5 or 6 fields, found {}", fields.len())});
+        }
+        counts.push(turning_row(*line, fields)?);
+    }
+    Ok(counts)
+}
+
+///Reads turning movement counts from a CSV file, see `parse_turning_csv`
+pub fn load_turning_csv(path: impl AsRef<Path>) -> Result<Vec<TurningCount>, ScenarioError>
+{
+    parse_turning_csv(&std::fs::read_to_string(path)?)
+}
+
+///Reads link and turning movement counts from CSV text, in cars an hour, for `estimate_od`.
+///
+/// A row with three fields, `intersection,arm,vehicles_per_hour`, counts the cars driving onto the
+/// road leaving the intersection on that arm. A row with five or six fields is a turning movement
+/// count, see `parse_turning_csv`, and gives one count for every turn. Both kinds can be mixed
+/// and a header row is skipped.
+/// # Examples
+///
+/// ```rust
+/// use traffic_sim::scenario::csv::parse_counts_csv;
+/// let counts = parse_counts_csv("intersection,arm,count\n1,E,300\n2,W,0,250,40,0\n").unwrap();
+/// assert_eq!(counts.len(), 5);
+/// ```
+pub fn parse_counts_csv(text: &str) -> Result<Vec<ObservedCount>, ScenarioError>
+{
+    let rows = rows(text);
+    let skip = rows.first().map_or(0, |(_, fields)| usize::from(is_header(&fields[..1])));
+    let mut counts = Vec::new();
+    for (line, fields) in rows.iter().skip(skip){
+        match fields.len(){
+            3 => counts.push(ObservedCount{
+                location: CountLocation::Link{int_id: IntersectionId(parse_field(*line, fields[0], "intersection ID")?), arm: parse_side(*line, fields[1])?},
+                vehicles_per_hour: parse_field(*line, fields[2], "count")?
+            }),
+            5 | 6 => counts.extend(turning_row(*line, fields)?.observations()),
+            found => return Err(ScenarioError::Csv{line: *line, message: format!("expected 3, 5 or 6 fields, found {}", found)})
+        }
+    }
+    Ok(counts)
+}
+
+///Reads link and turning movement counts from a CSV file, see `parse_counts_csv`
+pub fn load_counts_csv(path: impl AsRef<Path>) -> Result<Vec<ObservedCount>, ScenarioError>
+{
+    parse_counts_csv(&std::fs::read_to_string(path)?)
+}
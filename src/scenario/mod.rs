@@ -0,0 +1,795 @@
+pub mod osm;
+pub mod sumo;
+pub mod csv;
+
+use crate::simulator::{Simulator, Position, Current, Between, builder::{RoadNetworkBuilder, BuildError}, incidents::Closure, transit::{Schedule, Stop, TransitLine},
+    demand::{Demand, OdPair, TimeProfile}, microscopic::Idm, lanes::Mobil, saturation::SaturationFlow, violations::NonCompliance};
+use crate::traffic_logic::{road::CardinalDirection, intersection::{SignalPlan, IntersectionId}, geometry::Point, car::{Direction, TurningProportions}, route::{Route, Trip}, vehicle::VehicleClass, driver::DriverProfile};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fmt::Display, path::Path};
+
+///Everything that can go wrong while reading, writing or building a scenario
+#[derive(Debug)]
+pub enum ScenarioError
+{
+    Io(std::io::Error),
+    Toml(String),
+    Json(serde_json::Error),
+    ///The file extension was not `.toml` or `.json`
+    UnknownFormat(String),
+    ///A network file that is not valid XML
+    Xml(String),
+    ///A CSV file with a row that cannot be read, `line` starts at 1
+    Csv{line: usize, message: String},
+    Build(BuildError),
+    ///A car, route, closure or line refers to an intersection the scenario does not have
+    UnknownIntersection(String),
+    ///A car or route uses a road between two intersections that are not connected that way
+    NoRoad{from: String, to: String},
+    ///A bus refers to a line the scenario does not have, or a line cannot be driven
+    InvalidLine(String)
+}
+
+impl Display for ScenarioError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::Io(err) => write!(f, "Could not access scenario file: {}", err),
+            Self::Toml(err) => write!(f, "Invalid TOML scenario: {}", err),
+            Self::Json(err) => write!(f, "Invalid JSON scenario: {}", err),
+            Self::UnknownFormat(ext) => write!(f, "Unknown scenario format '{}', expected toml or json", ext),
+            Self::Xml(err) => write!(f, "Invalid XML network: {}", err),
+            Self::Csv{line, message} => write!(f, "Invalid CSV on line {}: {}", line, message),
+            Self::Build(err) => write!(f, "{}", err),
+            Self::UnknownIntersection(name) => write!(f, "Scenario has no intersection named '{}'", name),
+            Self::NoRoad{from, to} => write!(f, "Scenario has no road from '{}' to '{}'", from, to),
+            Self::InvalidLine(line) => write!(f, "Transit line '{}' does not exist or cannot be driven", line)
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<std::io::Error> for ScenarioError{
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for ScenarioError{
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(err)
+    }
+}
+
+impl From<BuildError> for ScenarioError{
+    fn from(err: BuildError) -> Self {
+        Self::Build(err)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IntersectionSpec
+{
+    pub name: String,
+    ///The intersection shows random lights if it has no plan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal_plan: Option<SignalPlan>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Point>
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RoadSpec
+{
+    pub from: String,
+    ///The arm of `from` the road leaves from
+    pub direction: CardinalDirection,
+    pub to: String,
+    ///The arm of `to` the road arrives at, the arm opposite `direction` if it is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_direction: Option<CardinalDirection>,
+    ///The number of ticks it takes to drive the road
+    pub distance: u32,
+    ///The number of lanes in each direction
+    #[serde(default = "one_lane")]
+    pub lanes: u8,
+    ///The road can only be driven from `from` to `to`
+    #[serde(default)]
+    pub one_way: bool,
+    ///The points the road bends through, in order from `from` to `to`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shape: Vec<Point>
+}
+
+fn one_lane() -> u8
+{
+    1
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CarSpec
+{
+    ///The intersection the car waits at, or drives to if it is on a road
+    pub intersection: String,
+    ///The side of the intersection the car waits at, or arrives at if it is on a road
+    pub direction: CardinalDirection,
+    ///The road the car is driving on, the car waits at the intersection if it is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_road: Option<OnRoadSpec>,
+    ///Where the car is going, cars without a trip turn at random
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trip: Option<TripSpec>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub class: VehicleClass,
+    ///How the driver behaves at signals, drivers without a profile only go on green
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub driver: Option<DriverProfile>,
+    ///The ticks the car has spent waiting so far
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub wait_time: usize,
+    ///The line the car is a bus of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bus: Option<BusSpec>
+}
+
+impl CarSpec
+{
+    ///A passenger car without a trip waiting at an intersection
+    pub fn waiting(intersection: &str, direction: CardinalDirection) -> CarSpec
+    {
+        CarSpec{intersection: intersection.to_string(), direction, on_road: None, trip: None, class: VehicleClass::default(), driver: None, wait_time: 0, bus: None}
+    }
+}
+
+///The road a car is driving on, it ends at the intersection and side of the car
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OnRoadSpec
+{
+    ///The intersection the road leaves from
+    pub from: String,
+    ///The ticks the car still has to drive
+    pub distance_to_target: u32
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TripSpec
+{
+    ///The number of the trip, a new one is given to trips without it
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub serial: u64,
+    pub origin: String,
+    pub destination: String,
+    ///The tick the car was placed at its origin
+    pub depart_tick: usize,
+    ///The cost of the route the car set off on
+    pub free_flow_time: u64,
+    ///Every intersection the car still drives through, from the one it leaves next to its destination
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route: Vec<String>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub reroutes: bool,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub route_changes: usize,
+    ///Every road the car has driven onto
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub path: Vec<PathStep>
+}
+
+///A road a car drove onto, as the intersection and arm it left from and the tick it left
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathStep
+{
+    pub intersection: String,
+    pub arm: CardinalDirection,
+    pub tick: usize
+}
+
+///A bus driving its line
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BusSpec
+{
+    ///The name of the line in `transit_lines`, the first line with that name if several share it
+    pub line: String,
+    ///Which bus of the line it is, in the order of the schedule
+    pub run: usize,
+    ///The index of the next stop of the line the bus serves
+    pub next_stop: usize
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DepartureSpec
+{
+    pub tick: usize,
+    pub intersection: String,
+    pub direction: CardinalDirection,
+    ///The intersection the car drives to by the shortest route, cars without one turn at random
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+    ///Every intersection the car drives through from `intersection` to `destination`, the shortest route is taken if it is empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub route: Vec<String>
+}
+
+///How often cars without a route make each turn from one side of an intersection
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TurningSpec
+{
+    pub intersection: String,
+    ///The side cars wait at
+    pub side: CardinalDirection,
+    pub proportions: TurningProportions
+}
+
+///The road leaving an intersection on an arm, closed from `from_tick` until `to_tick`
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClosureSpec
+{
+    pub intersection: String,
+    pub arm: CardinalDirection,
+    pub from_tick: usize,
+    pub to_tick: usize
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransitLineSpec
+{
+    pub name: String,
+    ///Every intersection the line passes in order
+    pub intersections: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stops: Vec<Stop>,
+    pub schedule: Schedule,
+    ///The ticks a bus stands at every stop to open and close its doors
+    pub dead_time: usize,
+    ///The ticks each passenger takes to board
+    pub boarding_ticks: f64,
+    ///The number of buses of the schedule that have already set off
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub dispatched: usize
+}
+
+///Trips started over time from an origin–destination matrix, as in `Demand`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OdDemandSpec
+{
+    pub pairs: Vec<OdPairSpec>,
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub profile: TimeProfile,
+    ///The number of seconds a tick lasts
+    pub tick_seconds: f64,
+    ///The passenger car spaces that can be taken up at the side of an origin before entries there are blocked
+    pub max_queue: usize,
+    ///The share of trips made by each kind of vehicle, every trip is by passenger car if it is empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<ClassShareSpec>
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OdPairSpec
+{
+    pub origin: String,
+    pub destination: String,
+    pub trips_per_hour: f64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClassShareSpec
+{
+    pub class: VehicleClass,
+    pub share: f64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DriverShareSpec
+{
+    pub driver: DriverProfile,
+    pub share: f64
+}
+
+///The road from one intersection to another letting fewer cars out from `from_tick` until `to_tick`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IncidentSpec
+{
+    pub from: String,
+    pub to: String,
+    pub from_tick: usize,
+    pub to_tick: usize,
+    ///The share of a car that can leave the road each tick
+    pub capacity: f64
+}
+
+///How often drivers enter against their light, as in `NonCompliance`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NonComplianceSpec
+{
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub red: Vec<ChanceSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub late_yellow: Vec<ChanceSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub approach_factors: Vec<ApproachFactorSpec>
+}
+
+///The chance a driver of a profile enters against the light, drivers without a profile if it is not given
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChanceSpec
+{
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub driver: Option<DriverProfile>,
+    pub chance: f64
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApproachFactorSpec
+{
+    pub intersection: String,
+    pub side: CardinalDirection,
+    pub factor: f64
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool
+{
+    *value == T::default()
+}
+
+///A network and the traffic on it, as stored in a scenario file.
+///
+/// Intersections get IDs in the order they are listed, starting at 1.
+/// # Examples
+///
+/// ```rust
+/// use traffic_sim::scenario::Scenario;
+/// let scenario = Scenario::from_toml_str(r#"
+/// [[intersections]]
+/// name = "A"
+/// signal_plan = { phases = [{ config = "NorthSouth", duration = 10 }, { config = "EastWest", duration = 10 }] }
+///
+/// [[intersections]]
+/// name = "B"
+///
+/// [[roads]]
+/// from = "A"
+/// direction = "East"
+/// to = "B"
+/// distance = 5
+///
+/// [[cars]]
+/// intersection = "A"
+/// direction = "East"
+///
+/// [[demand]]
+/// tick = 20
+/// intersection = "B"
+/// direction = "West"
+/// "#).unwrap();
+/// let mut simulator = scenario.to_simulator().unwrap();
+/// simulator.run(30);
+/// assert_eq!(simulator.cars().len(), 2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scenario
+{
+    pub intersections: Vec<IntersectionSpec>,
+    #[serde(default)]
+    pub roads: Vec<RoadSpec>,
+    ///Cars waiting at an intersection when the run starts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cars: Vec<CarSpec>,
+    ///Cars that appear at an intersection later in the run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub demand: Vec<DepartureSpec>,
+    ///The tick the run starts on
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub tick: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub turning: Vec<TurningSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub closures: Vec<ClosureSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub transit_lines: Vec<TransitLineSpec>,
+    ///Trips started over time from an origin–destination matrix
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub od_demand: Option<OdDemandSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub incidents: Vec<IncidentSpec>,
+    ///The share of drivers of each profile new cars are given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub driver_mix: Vec<DriverShareSpec>,
+    ///The share of cars starting a trip that reroute at every intersection
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub reroute_share: f64,
+    ///The ticks each passenger car space on a road adds to its live cost, the simulator's default if it is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queue_delay: Option<f64>,
+    ///The microscopic mode is on if it is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub microscopic: Option<Idm>,
+    ///The lane changing model, the simulator's default if it is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lane_changing: Option<Mobil>,
+    ///Queues discharge at a saturation flow rate if it is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub saturation_flow: Option<SaturationFlow>,
+    ///No one runs the lights if it is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub non_compliance: Option<NonComplianceSpec>
+}
+
+impl Scenario
+{
+    pub fn from_toml_str(text: &str) -> Result<Scenario, ScenarioError>
+    {
+        toml::from_str(text).map_err(|err| ScenarioError::Toml(err.to_string()))
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, ScenarioError>
+    {
+        toml::to_string_pretty(self).map_err(|err| ScenarioError::Toml(err.to_string()))
+    }
+
+    pub fn from_json_str(text: &str) -> Result<Scenario, ScenarioError>
+    {
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn to_json_string(&self) -> Result<String, ScenarioError>
+    {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    ///Reads a scenario file, the format is picked from the `.toml` or `.json` extension
+    pub fn load(path: impl AsRef<Path>) -> Result<Scenario, ScenarioError>
+    {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)?;
+        match extension(path)?.as_str(){
+            "toml" => Self::from_toml_str(&text),
+            _ => Self::from_json_str(&text)
+        }
+    }
+
+    ///Writes a scenario file, the format is picked from the `.toml` or `.json` extension
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ScenarioError>
+    {
+        let path = path.as_ref();
+        let text = match extension(path)?.as_str(){
+            "toml" => self.to_toml_string()?,
+            _ => self.to_json_string()?
+        };
+        std::fs::write(path, text)?;
+        Ok(())
+    }
+
+    ///Describes the current state of a simulator as a scenario, which `to_simulator` builds again.
+    ///
+    /// Intersections are named after their IDs and cars are listed in the order of their IDs. The settings of the
+    /// run are kept with the traffic. What has been recorded so far is not part of a scenario, nor are the trips the
+    /// demand has started that still wait in an entry queue. A bus standing at a stop starts its dwell again, and in
+    /// the microscopic mode cars on a road keep the ticks they have left to drive rather than their position and speed.
+    pub fn from_simulator(simulator: &Simulator) -> Scenario
+    {
+        let name = |id: IntersectionId| id.to_string();
+        let side = |side: u8| CardinalDirection::try_from(side).ok();
+        let intersections = simulator.intersections().iter()
+            .map(|intersection| IntersectionSpec{name: name(intersection.id), signal_plan: intersection.signal_plan.clone(), position: intersection.position})
+            .collect();
+
+        let road = &simulator.road().road;
+        let mut roads = Vec::new();
+        let mut ids: Vec<&IntersectionId> = road.keys().collect();
+        ids.sort();
+        for from in ids{
+            for (dir, node) in road[from].iter().enumerate(){
+                let Some(node) = node else { continue };
+                let dir = dir as u8;
+                let back = road.get(&node.dest_int_id)
+                    .and_then(|arms| arms[usize::from(node.direction)])
+                    .filter(|back| back.dest_int_id == *from && back.direction == dir);
+                //two way roads are stored once in each direction, only keep one of them
+                if back.is_some() && (node.dest_int_id, node.direction) < (*from, dir){
+                    continue;
+                }
+                let direction = CardinalDirection::try_from(dir).unwrap();
+                let to_direction = CardinalDirection::try_from(node.direction).unwrap();
+                roads.push(RoadSpec{
+                    from: name(*from),
+                    direction,
+                    to: name(node.dest_int_id),
+                    to_direction: if to_direction == direction.opposite() {None} else {Some(to_direction)},
+                    distance: node.dist_from_source,
+                    lanes: node.lanes,
+                    one_way: back.is_none(),
+                    shape: simulator.road().get_shape(*from, dir).to_vec()
+                });
+            }
+        }
+
+        let route_names = |route: &Route| -> Vec<String> {
+            let Some((first, _)) = route.links.front() else { return Vec::new() };
+            let mut names = vec![name(*first)];
+            names.extend(route.links.iter().filter_map(|(int_id, arm)| road.get(int_id)?[usize::from(*arm)]).map(|node| name(node.dest_int_id)));
+            names
+        };
+
+        let lines = simulator.transit_lines();
+        let mut cars: Vec<_> = simulator.cars().iter().collect();
+        cars.sort_by_key(|car| car.id);
+        let cars = cars.into_iter().filter_map(|car| {
+            let pos = simulator.get_position(car.id)?;
+            let (int_id, direction, on_road) = match (pos.current_intersection, pos.in_between){
+                (Some(current), _) => (current.int_id, current.direction, None),
+                (None, Some(between)) => (between.int_2_id, between.from, Some(OnRoadSpec{from: name(between.int_1_id), distance_to_target: between.distance_to_target})),
+                (None, None) => return None
+            };
+            let trip = car.trip.as_ref().map(|trip| TripSpec{
+                serial: trip.serial,
+                origin: name(trip.origin),
+                destination: name(trip.destination),
+                depart_tick: trip.depart_tick,
+                free_flow_time: trip.free_flow_time,
+                route: route_names(&trip.route),
+                reroutes: trip.reroutes,
+                route_changes: trip.route_changes,
+                path: trip.path.iter().filter_map(|(int_id, arm, tick)| Some(PathStep{intersection: name(*int_id), arm: side(*arm)?, tick: *tick})).collect()
+            });
+            let bus = simulator.bus_run(car.id).and_then(|(line, run, next_stop)| Some(BusSpec{line: lines.get(line)?.name.clone(), run, next_stop}));
+            Some(CarSpec{intersection: name(int_id), direction: side(direction)?, on_road, trip, class: car.class, driver: car.driver, wait_time: car.wait_time, bus})
+        }).collect();
+
+        let demand = simulator.departures().iter().filter_map(|departure| Some(DepartureSpec{
+            tick: departure.tick,
+            intersection: name(departure.at.int_id),
+            direction: side(departure.at.direction)?,
+            destination: departure.destination.map(name),
+            route: departure.route.as_ref().map(route_names).unwrap_or_default()
+        })).collect();
+
+        let turning = simulator.intersections().iter().flat_map(|intersection| {
+            intersection.turning.iter().enumerate().filter_map(move |(dir, turning)| Some(TurningSpec{
+                intersection: name(intersection.id),
+                side: side(dir as u8)?,
+                proportions: (*turning)?
+            }))
+        }).collect();
+
+        let closures = simulator.closures().iter().filter_map(|closure| Some(ClosureSpec{
+            intersection: name(closure.int_id),
+            arm: side(closure.arm)?,
+            from_tick: closure.from_tick,
+            to_tick: closure.to_tick
+        })).collect();
+
+        let transit_lines = simulator.transit_lines().into_iter().zip(simulator.line_metrics()).map(|(line, metrics)| TransitLineSpec{
+            name: line.name.clone(),
+            intersections: line.intersections.iter().map(|id| name(*id)).collect(),
+            stops: line.stops.clone(),
+            schedule: line.schedule.clone(),
+            dead_time: line.dead_time,
+            boarding_ticks: line.boarding_ticks,
+            dispatched: metrics.dispatched
+        }).collect();
+
+        let od_demand = simulator.demand().map(|demand| OdDemandSpec{
+            pairs: demand.pairs.iter().map(|pair| OdPairSpec{origin: name(pair.origin), destination: name(pair.destination), trips_per_hour: pair.trips_per_hour}).collect(),
+            profile: demand.profile.clone(),
+            tick_seconds: demand.tick_seconds,
+            max_queue: demand.max_queue,
+            classes: demand.classes.iter().map(|(class, share)| ClassShareSpec{class: *class, share: *share}).collect()
+        });
+
+        let incidents = simulator.incidents().iter().map(|incident| IncidentSpec{
+            from: name(incident.int_1_id),
+            to: name(incident.int_2_id),
+            from_tick: incident.from_tick,
+            to_tick: incident.to_tick,
+            capacity: incident.capacity
+        }).collect();
+
+        let defaults = Simulator::new();
+        let non_compliance = simulator.non_compliance().map(|non_compliance| {
+            let chances = |chances: &HashMap<Option<DriverProfile>, f64>| {
+                let mut chances: Vec<ChanceSpec> = chances.iter().map(|(driver, chance)| ChanceSpec{driver: *driver, chance: *chance}).collect();
+                chances.sort_by_key(|chance| chance.driver.map(|driver| driver as u8));
+                chances
+            };
+            let mut factors: Vec<(&(IntersectionId, u8), &f64)> = non_compliance.approach_factors.iter().collect();
+            factors.sort_by_key(|(approach, _)| **approach);
+            NonComplianceSpec{
+                red: chances(&non_compliance.red),
+                late_yellow: chances(&non_compliance.late_yellow),
+                approach_factors: factors.into_iter().filter_map(|((int_id, dir), factor)| Some(ApproachFactorSpec{intersection: name(*int_id), side: side(*dir)?, factor: *factor})).collect()
+            }
+        });
+
+        Scenario{intersections, roads, cars, demand, tick: simulator.timestep(), turning, closures, transit_lines, od_demand, incidents,
+            driver_mix: simulator.driver_mix().iter().map(|(driver, share)| DriverShareSpec{driver: *driver, share: *share}).collect(),
+            reroute_share: simulator.reroute_share(),
+            queue_delay: Some(simulator.queue_delay()).filter(|delay| *delay != defaults.queue_delay()),
+            microscopic: simulator.microscopic().copied(),
+            lane_changing: Some(*simulator.lane_changing()).filter(|mobil| mobil != defaults.lane_changing()),
+            saturation_flow: simulator.saturation_flow().copied(),
+            non_compliance}
+    }
+
+    ///Finds the arm of an intersection that the road to a neighbouring intersection leaves from
+    /// # Returns
+    /// `Option<CardinalDirection>` : Returns `None` if there is no road that can be driven from `from` to `to`
+    pub fn arm_towards(&self, from: &str, to: &str) -> Option<CardinalDirection>
+    {
+        self.roads.iter().find_map(|road| {
+            if road.from == from && road.to == to{
+                Some(road.direction)
+            }
+            else if road.to == from && road.from == to && !road.one_way{
+                Some(road.to_direction.unwrap_or(road.direction.opposite()))
+            }
+            else {
+                None
+            }
+        })
+    }
+
+    ///Builds a simulator from the scenario, checking the network with a `RoadNetworkBuilder`
+    /// # Returns
+    /// `Result<Simulator, ScenarioError>` : Returns `ScenarioError::Build` if the network is not valid, and an error
+    /// if the traffic refers to intersections, roads or lines the network does not have
+    pub fn to_simulator(&self) -> Result<Simulator, ScenarioError>
+    {
+        let mut builder = RoadNetworkBuilder::new();
+        for intersection in self.intersections.iter(){
+            builder = match intersection.position{
+                Some(pos) => builder.intersection_at(&intersection.name, pos.x, pos.y),
+                None => builder.intersection(&intersection.name)
+            };
+            if let Some(plan) = &intersection.signal_plan{
+                builder = builder.signal_plan(&intersection.name, plan.clone());
+            }
+        }
+        for road in self.roads.iter(){
+            let to_direction = road.to_direction.unwrap_or(road.direction.opposite());
+            builder = builder.road_to_arm(&road.from, road.direction, &road.to, to_direction, road.distance).lanes(road.lanes).shape(road.shape.clone());
+            if road.one_way{
+                builder = builder.one_way();
+            }
+        }
+        let mut simulator = builder.build()?;
+        simulator.set_timestep(self.tick);
+
+        //the builder has given the intersections IDs in the order they are listed
+        let ids: HashMap<&str, IntersectionId> = self.intersections.iter().enumerate()
+            .map(|(i, intersection)| (intersection.name.as_str(), IntersectionId(i as u32 + 1)))
+            .collect();
+        let id = |name: &str| ids.get(name).copied().ok_or_else(|| ScenarioError::UnknownIntersection(name.to_string()));
+        let route = |simulator: &Simulator, names: &[String]| -> Result<Route, ScenarioError> {
+            let mut route = Route::default();
+            for pair in names.windows(2){
+                let (from, to) = (id(&pair[0])?, id(&pair[1])?);
+                let (arm, node) = simulator.road().road.get(&from)
+                    .and_then(|arms| arms.iter().enumerate().find_map(|(arm, node)| node.filter(|node| node.dest_int_id == to).map(|node| (arm as u8, node))))
+                    .ok_or_else(|| ScenarioError::NoRoad{from: pair[0].clone(), to: pair[1].clone()})?;
+                route.links.push_back((from, arm));
+                route.cost += u64::from(node.dist_from_source);
+            }
+            Ok(route)
+        };
+
+        for turning in self.turning.iter(){
+            simulator.set_turning_proportions(id(&turning.intersection)?, turning.side.into(), Some(turning.proportions));
+        }
+        for closure in self.closures.iter(){
+            simulator.add_closure(Closure{int_id: id(&closure.intersection)?, arm: closure.arm.into(), from_tick: closure.from_tick, to_tick: closure.to_tick});
+        }
+        for spec in self.transit_lines.iter(){
+            let intersections = spec.intersections.iter().map(|name| id(name)).collect::<Result<Vec<IntersectionId>, ScenarioError>>()?;
+            let line = TransitLine{name: spec.name.clone(), intersections, stops: spec.stops.clone(), schedule: spec.schedule.clone(), dead_time: spec.dead_time, boarding_ticks: spec.boarding_ticks};
+            let index = simulator.add_transit_line(line).ok_or_else(|| ScenarioError::InvalidLine(spec.name.clone()))?;
+            simulator.set_dispatched(index, spec.dispatched);
+        }
+
+        //trips without a serial are numbered after every trip that has one
+        if let Some(serial) = self.cars.iter().filter_map(|spec| spec.trip.as_ref()).map(|trip| trip.serial).max(){
+            simulator.reserve_trip_serial(serial);
+        }
+        for spec in self.cars.iter(){
+            let at = Current{int_id: id(&spec.intersection)?, direction: spec.direction.into()};
+            let pos = match &spec.on_road{
+                None => Position::new(Some(at), None),
+                Some(on_road) => {
+                    let from = id(&on_road.from)?;
+                    let connected = simulator.road().road.get(&from)
+                        .is_some_and(|arms| arms.iter().flatten().any(|node| node.dest_int_id == at.int_id && node.direction == at.direction));
+                    if !connected{
+                        return Err(ScenarioError::NoRoad{from: on_road.from.clone(), to: spec.intersection.clone()});
+                    }
+                    Position::new(None, Some(Between{int_1_id: from, int_2_id: at.int_id, distance_to_target: on_road.distance_to_target.max(1), from: at.direction}))
+                }
+            };
+            let trip = match &spec.trip{
+                Some(trip) => Some(Trip{
+                    serial: match trip.serial{
+                        0 => simulator.next_trip_serial(),
+                        serial => serial
+                    },
+                    origin: id(&trip.origin)?,
+                    destination: id(&trip.destination)?,
+                    depart_tick: trip.depart_tick,
+                    free_flow_time: trip.free_flow_time,
+                    route: route(&simulator, &trip.route)?,
+                    reroutes: trip.reroutes,
+                    route_changes: trip.route_changes,
+                    path: trip.path.iter().map(|step| Ok((id(&step.intersection)?, step.arm.into(), step.tick))).collect::<Result<Vec<_>, ScenarioError>>()?
+                }),
+                None => None
+            };
+            let turning = simulator.intersections()[at.int_id.index()].turning[usize::from(at.direction)];
+            let car_id = simulator.add_car(pos);
+            let car = simulator.get_car_mut(car_id).expect("Car was just added");
+            car.class = spec.class;
+            car.driver = spec.driver;
+            car.wait_time = spec.wait_time;
+            match trip.as_ref().and_then(|trip| trip.route.links.front()){
+                Some((int_id, arm)) if *int_id == at.int_id => car.intention = Direction::toward(at.direction, *arm),
+                _ => car.randomize_intent(turning.as_ref())
+            }
+            car.trip = trip;
+            if let Some(bus) = &spec.bus{
+                let line = self.transit_lines.iter().position(|line| line.name == bus.line);
+                if !line.is_some_and(|line| simulator.resume_bus(car_id, line, bus.run, bus.next_stop)){
+                    return Err(ScenarioError::InvalidLine(bus.line.clone()));
+                }
+            }
+        }
+
+        for departure in self.demand.iter(){
+            let at = Current{int_id: id(&departure.intersection)?, direction: departure.direction.into()};
+            match &departure.destination{
+                Some(destination) if !departure.route.is_empty() => simulator.schedule_trip_on_route(departure.tick, at, id(destination)?, route(&simulator, &departure.route)?),
+                Some(destination) => simulator.schedule_trip(departure.tick, at, id(destination)?),
+                None => simulator.schedule_car(departure.tick, at)
+            }
+        }
+
+        if let Some(spec) = &self.od_demand{
+            let pairs = spec.pairs.iter().map(|pair| Ok(OdPair{origin: id(&pair.origin)?, destination: id(&pair.destination)?, trips_per_hour: pair.trips_per_hour}))
+                .collect::<Result<Vec<OdPair>, ScenarioError>>()?;
+            let mut demand = Demand::new(pairs).with_profile(spec.profile.clone())
+                .with_classes(spec.classes.iter().map(|share| (share.class, share.share)).collect());
+            demand.tick_seconds = spec.tick_seconds;
+            demand.max_queue = spec.max_queue;
+            simulator.set_demand(demand);
+        }
+        for incident in self.incidents.iter(){
+            let (from, to) = (id(&incident.from)?, id(&incident.to)?);
+            if simulator.road().road.get(&from).is_none_or(|arms| !arms.iter().flatten().any(|node| node.dest_int_id == to)){
+                return Err(ScenarioError::NoRoad{from: incident.from.clone(), to: incident.to.clone()});
+            }
+            simulator.add_incident(from, to, incident.from_tick, incident.to_tick, incident.capacity);
+        }
+        simulator.set_driver_mix(self.driver_mix.iter().map(|share| (share.driver, share.share)).collect());
+        simulator.set_reroute_share(self.reroute_share);
+        if let Some(delay) = self.queue_delay{
+            simulator.set_queue_delay(delay);
+        }
+        simulator.set_microscopic(self.microscopic);
+        if let Some(mobil) = self.lane_changing{
+            simulator.set_lane_changing(mobil);
+        }
+        simulator.set_saturation_flow(self.saturation_flow);
+        if let Some(spec) = &self.non_compliance{
+            let chances = |chances: &[ChanceSpec]| chances.iter().map(|chance| (chance.driver, chance.chance)).collect();
+            let approach_factors = spec.approach_factors.iter().map(|factor| Ok(((id(&factor.intersection)?, factor.side.into()), factor.factor)))
+                .collect::<Result<HashMap<(IntersectionId, u8), f64>, ScenarioError>>()?;
+            simulator.set_non_compliance(NonCompliance{red: chances(&spec.red), late_yellow: chances(&spec.late_yellow), approach_factors});
+        }
+        Ok(simulator)
+    }
+}
+
+fn extension(path: &Path) -> Result<String, ScenarioError>
+{
+    let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("").to_lowercase();
+    match ext.as_str(){
+        "toml" | "json" => Ok(ext),
+        _ => Err(ScenarioError::UnknownFormat(ext))
+    }
+}
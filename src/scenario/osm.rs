@@ -0,0 +1,307 @@
+use crate::scenario::{Scenario, ScenarioError, IntersectionSpec, RoadSpec};
+use crate::traffic_logic::{road::CardinalDirection, intersection::SignalPlan, geometry::{Point, pick_arm}};
+use std::{collections::{HashMap, HashSet}, path::Path};
+
+///Settings used to turn an OpenStreetMap extract into a scenario
+#[derive(Clone, Copy, Debug)]
+pub struct OsmOptions
+{
+    ///The number of real seconds a tick represents
+    pub tick_seconds: f64,
+    ///The speed limit used for roads without a `maxspeed` tag and an unknown `highway` type
+    pub default_speed_kmh: f64,
+    ///The number of ticks each through phase of an imported signal is shown for
+    pub green_ticks: usize,
+    ///The number of ticks each left turn phase of an imported signal is shown for
+    pub turn_ticks: usize,
+    ///Signal nodes that are not at a junction are moved to a junction this many meters away or closer
+    pub signal_snap_m: f64
+}
+
+impl Default for OsmOptions
+{
+    fn default() -> Self {
+        OsmOptions{tick_seconds: 1.0, default_speed_kmh: 50.0, green_ticks: 20, turn_ticks: 5, signal_snap_m: 30.0}
+    }
+}
+
+///The result of importing an OpenStreetMap extract
+pub struct OsmImport
+{
+    ///The imported network, intersections are named after their OSM node IDs
+    pub scenario: Scenario,
+    ///Parts of the extract that could not be represented and were changed or left out
+    pub warnings: Vec<String>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum OneWay
+{
+    No,
+    Forward,
+    Backward
+}
+
+struct Way
+{
+    id: i64,
+    nodes: Vec<i64>,
+    speed_kmh: f64,
+    one_way: OneWay,
+    lanes_forward: u8,
+    lanes_backward: u8
+}
+
+///Reads a local `.osm` XML file, see `parse_osm`
+pub fn import_osm(path: impl AsRef<Path>, options: &OsmOptions) -> Result<OsmImport, ScenarioError>
+{
+    let text = std::fs::read_to_string(path)?;
+    parse_osm(&text, options)
+}
+
+///Converts OpenStreetMap XML into a scenario.
+///
+/// Drivable `highway` ways are split into roads at every node shared by two or more ways and at
+/// way ends. Each road is attached to the arm of its intersections closest to its bearing, and its
+/// distance is the time it takes to drive it at the speed limit. `oneway`, `lanes` and
+/// `highway=traffic_signals` tags are kept, junctions without signals are uncontrolled. The two one way
+/// carriageways of a divided road become a single road that can be driven both ways, with the distance,
+/// lanes and shape of the first carriageway. Positions and road shapes use longitude as x and latitude as y.
+pub fn parse_osm(text: &str, options: &OsmOptions) -> Result<OsmImport, ScenarioError>
+{
+    let doc = roxmltree::Document::parse(text).map_err(|err| ScenarioError::Xml(err.to_string()))?;
+    let mut warnings = Vec::new();
+
+    let mut coords: HashMap<i64, (f64, f64)> = HashMap::new();
+    let mut signals: HashSet<i64> = HashSet::new();
+    let mut ways = Vec::new();
+    for element in doc.root_element().children().filter(|n| n.is_element()){
+        let Some(id) = element.attribute("id").and_then(|id| id.parse::<i64>().ok()) else { continue };
+        let tags: HashMap<&str, &str> = element.children()
+            .filter(|n| n.has_tag_name("tag"))
+            .filter_map(|n| Some((n.attribute("k")?, n.attribute("v")?)))
+            .collect();
+        match element.tag_name().name(){
+            "node" => {
+                let lat = element.attribute("lat").and_then(|v| v.parse::<f64>().ok());
+                let lon = element.attribute("lon").and_then(|v| v.parse::<f64>().ok());
+                if let (Some(lat), Some(lon)) = (lat, lon){
+                    coords.insert(id, (lat, lon));
+                }
+                if tags.get("highway") == Some(&"traffic_signals"){
+                    signals.insert(id);
+                }
+            },
+            "way" => {
+                let Some(speed) = tags.get("highway").and_then(|highway| highway_speed(highway)) else { continue };
+                let nodes = element.children()
+                    .filter(|n| n.has_tag_name("nd"))
+                    .filter_map(|n| n.attribute("ref")?.parse::<i64>().ok())
+                    .collect();
+                ways.push(parse_way(id, nodes, &tags, speed, options));
+            },
+            _ => ()
+        }
+    }
+
+    //junctions are nodes shared by several ways, plus the ends of every way
+    let mut uses: HashMap<i64, usize> = HashMap::new();
+    for way in ways.iter_mut(){
+        way.nodes.retain(|node| {
+            let known = coords.contains_key(node);
+            if !known{
+                warnings.push(format!("Way {} references node {} which has no coordinates, skipping the node", way.id, node));
+            }
+            known
+        });
+        for node in way.nodes.iter(){
+            *uses.entry(*node).or_insert(0) += 1;
+        }
+    }
+    let is_junction = |way: &Way, i: usize| i == 0 || i == way.nodes.len()-1 || uses[&way.nodes[i]] > 1;
+
+    let mut signalized: HashSet<i64> = HashSet::new();
+    let mut order: Vec<i64> = Vec::new();
+    let mut seen: HashSet<i64> = HashSet::new();
+    let mut arms: HashMap<i64, [bool;4]> = HashMap::new();
+    //every pair of nodes connected so far in the direction it can be driven, with the road connecting them
+    let mut pairs: HashMap<(i64, i64), usize> = HashMap::new();
+    let mut roads: Vec<RoadSpec> = Vec::new();
+    for way in ways.iter(){
+        if way.nodes.len() < 2{
+            continue;
+        }
+        let mut start = 0;
+        for end in 1..way.nodes.len(){
+            if !is_junction(way, end){
+                continue;
+            }
+            let shape: Vec<(f64, f64)> = way.nodes[start..=end].iter().map(|node| coords[node]).collect();
+            let (from, to) = (way.nodes[start], way.nodes[end]);
+            snap_signals(&way.nodes[start..=end], &shape, &signals, options.signal_snap_m, &mut signalized);
+            start = end;
+
+            if from == to{
+                warnings.push(format!("Way {} loops back to node {}, skipping the loop", way.id, from));
+                continue;
+            }
+            let driven = match way.one_way{
+                OneWay::No => vec![(from, to), (to, from)],
+                OneWay::Forward => vec![(from, to)],
+                OneWay::Backward => vec![(to, from)]
+            };
+            if driven.iter().any(|pair| pairs.contains_key(pair)){
+                warnings.push(format!("Nodes {} and {} are already connected, skipping the part of way {} between them", from, to, way.id));
+                continue;
+            }
+            //the other carriageway of a divided road
+            if let Some(&road) = pairs.get(&(driven[0].1, driven[0].0)){
+                let lanes = if way.one_way == OneWay::Backward {way.lanes_backward} else {way.lanes_forward};
+                if roads[road].lanes != lanes{
+                    warnings.push(format!("The part of way {} between nodes {} and {} has {} lane(s) but the other direction has {}, using {}", way.id, from, to, lanes, roads[road].lanes, roads[road].lanes));
+                }
+                roads[road].one_way = false;
+                pairs.insert(driven[0], road);
+                continue;
+            }
+
+            let from_arm = pick_arm(&arms, from, bearing(shape[0], shape[1]));
+            let to_arm = pick_arm(&arms, to, bearing(shape[shape.len()-1], shape[shape.len()-2]));
+            let (Some(from_arm), Some(to_arm)) = (from_arm, to_arm) else {
+                warnings.push(format!("Nodes {} and {} have more than 4 roads, skipping the part of way {} between them", from, to, way.id));
+                continue;
+            };
+            arms.entry(from).or_insert([false;4])[usize::from(from_arm)] = true;
+            arms.entry(to).or_insert([false;4])[usize::from(to_arm)] = true;
+            for node in [from, to]{
+                if seen.insert(node){
+                    order.push(node);
+                }
+            }
+
+            let length: f64 = shape.windows(2).map(|pair| haversine(pair[0], pair[1])).sum();
+            let ticks = (length / (way.speed_kmh / 3.6 * options.tick_seconds)).round();
+            if ticks > f64::from(u32::MAX){
+                warnings.push(format!("The part of way {} between nodes {} and {} takes {} ticks, shortening it to {}", way.id, from, to, ticks, u32::MAX));
+            }
+            let distance = ticks.clamp(2.0, f64::from(u32::MAX)) as u32;
+
+            let mut shape: Vec<Point> = shape[1..shape.len()-1].iter().map(|(lat, lon)| Point::new(*lon, *lat)).collect();
+            let (from, from_arm, to, to_arm, lanes) = match way.one_way{
+                OneWay::Backward => {
+                    shape.reverse();
+                    (to, to_arm, from, from_arm, way.lanes_backward)
+                },
+                _ => (from, from_arm, to, to_arm, way.lanes_forward)
+            };
+            for pair in driven{
+                pairs.insert(pair, roads.len());
+            }
+            let direction = CardinalDirection::try_from(from_arm).unwrap();
+            let to_direction = CardinalDirection::try_from(to_arm).unwrap();
+            roads.push(RoadSpec{
+                from: from.to_string(),
+                direction,
+                to: to.to_string(),
+                to_direction: if to_direction == direction.opposite() {None} else {Some(to_direction)},
+                distance,
+                lanes,
+                one_way: way.one_way != OneWay::No,
+                shape
+            });
+        }
+    }
+
+    let intersections = order.iter().map(|node| IntersectionSpec{
+        name: node.to_string(),
+        signal_plan: Some(if signalized.contains(node) || signals.contains(node){
+            SignalPlan::four_phase(options.green_ticks, options.turn_ticks)
+        }
+        else {
+            SignalPlan::uncontrolled()
+        }),
+        position: Some(Point::new(coords[node].1, coords[node].0))
+    }).collect();
+
+    Ok(OsmImport{scenario: Scenario{intersections, roads, ..Default::default()}, warnings})
+}
+
+fn parse_way(id: i64, nodes: Vec<i64>, tags: &HashMap<&str, &str>, speed: f64, options: &OsmOptions) -> Way
+{
+    let speed_kmh = tags.get("maxspeed").and_then(|v| parse_speed(v)).or(if speed > 0.0 {Some(speed)} else {None}).unwrap_or(options.default_speed_kmh);
+    let one_way = match tags.get("oneway").copied(){
+        Some("yes") | Some("true") | Some("1") => OneWay::Forward,
+        Some("-1") | Some("reverse") => OneWay::Backward,
+        Some("no") | Some("false") | Some("0") => OneWay::No,
+        _ if tags.get("junction") == Some(&"roundabout") || tags.get("highway") == Some(&"motorway") => OneWay::Forward,
+        _ => OneWay::No
+    };
+    let lanes = |key: &str| tags.get(key).and_then(|v| v.parse::<u8>().ok()).filter(|lanes| *lanes > 0);
+    let total = lanes("lanes");
+    let (lanes_forward, lanes_backward) = match one_way{
+        OneWay::No => {
+            let half = total.map(|total| total.div_ceil(2).max(1)).unwrap_or(1);
+            (lanes("lanes:forward").unwrap_or(half), lanes("lanes:backward").unwrap_or(half))
+        },
+        _ => (total.unwrap_or(1), total.unwrap_or(1))
+    };
+    Way{id, nodes, speed_kmh, one_way, lanes_forward, lanes_backward}
+}
+
+///Gets the typical speed limit of a drivable highway type in km/h, 0 if it is drivable but unknown
+fn highway_speed(highway: &str) -> Option<f64>
+{
+    let base = highway.strip_suffix("_link").unwrap_or(highway);
+    match base{
+        "motorway" => Some(110.0),
+        "trunk" => Some(90.0),
+        "primary" => Some(60.0),
+        "secondary" | "tertiary" => Some(50.0),
+        "unclassified" => Some(40.0),
+        "residential" => Some(30.0),
+        "service" => Some(20.0),
+        "living_street" => Some(10.0),
+        "road" => Some(0.0),
+        _ => None
+    }
+}
+
+fn parse_speed(value: &str) -> Option<f64>
+{
+    let value = value.trim();
+    if let Some(mph) = value.strip_suffix("mph"){
+        return mph.trim().parse::<f64>().ok().map(|mph| mph * 1.609_344);
+    }
+    value.trim_end_matches("km/h").trim().parse::<f64>().ok().filter(|speed| *speed > 0.0)
+}
+
+///Marks the closest end of a road as signalized for every signal node along it that is not a junction
+fn snap_signals(nodes: &[i64], shape: &[(f64, f64)], signals: &HashSet<i64>, snap_m: f64, signalized: &mut HashSet<i64>)
+{
+    let mut along = vec![0.0];
+    for pair in shape.windows(2){
+        along.push(along[along.len()-1] + haversine(pair[0], pair[1]));
+    }
+    let length = along[along.len()-1];
+    for i in 1..nodes.len()-1{
+        if !signals.contains(&nodes[i]){
+            continue;
+        }
+        let (end, dist) = if along[i] <= length - along[i] {(nodes[0], along[i])} else {(nodes[nodes.len()-1], length - along[i])};
+        if dist <= snap_m{
+            signalized.insert(end);
+        }
+    }
+}
+
+///The compass bearing in degrees from one (lat, lon) point to another, 0 is north and 90 is east
+fn bearing(from: (f64, f64), to: (f64, f64)) -> f64
+{
+    Point::new(from.1, from.0).geo_bearing_to(&Point::new(to.1, to.0))
+}
+
+///The distance in meters between two (lat, lon) points
+fn haversine(from: (f64, f64), to: (f64, f64)) -> f64
+{
+    Point::new(from.1, from.0).geo_distance(&Point::new(to.1, to.0))
+}
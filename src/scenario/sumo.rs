@@ -0,0 +1,458 @@
+use crate::scenario::{Scenario, ScenarioError, IntersectionSpec, RoadSpec, DepartureSpec};
+use crate::traffic_logic::{road::CardinalDirection, intersection::SignalPlan, geometry::{self, Point, pick_arm, polyline_length}};
+use std::{collections::{HashMap, HashSet}, path::Path};
+
+///Settings used to convert between SUMO files and scenarios
+#[derive(Clone, Copy, Debug)]
+pub struct SumoOptions
+{
+    ///The number of real seconds a tick represents
+    pub tick_seconds: f64,
+    ///The speed in m/s used for edges without a speed, and for every edge written by the exporters
+    pub default_speed: f64,
+    ///The number of ticks each through phase of an imported traffic light is shown for
+    pub green_ticks: usize,
+    ///The number of ticks each left turn phase of an imported traffic light is shown for
+    pub turn_ticks: usize
+}
+
+impl Default for SumoOptions
+{
+    fn default() -> Self {
+        SumoOptions{tick_seconds: 1.0, default_speed: 13.89, green_ticks: 20, turn_ticks: 5}
+    }
+}
+
+///The result of importing a SUMO network
+pub struct SumoImport
+{
+    ///The imported network, intersections are named after their SUMO node IDs
+    pub scenario: Scenario,
+    ///Maps every imported SUMO edge ID to the names of the intersections it goes from and to
+    pub edges: HashMap<String, (String, String)>,
+    ///Parts of the network that could not be represented and were changed or left out
+    pub warnings: Vec<String>
+}
+
+///A vehicle from a SUMO route file
+#[derive(Clone, Debug, PartialEq)]
+pub struct SumoVehicle
+{
+    pub id: String,
+    ///The tick the vehicle departs on
+    pub depart: usize,
+    ///The names of the intersections the vehicle drives through, in order
+    pub route: Vec<String>
+}
+
+impl SumoVehicle
+{
+    ///Gets the departure that places the vehicle at the start of its route facing its first road,
+    /// driving its route to the end
+    /// # Returns
+    /// `Option<DepartureSpec>` : Returns `None` if the route is shorter than one road or its first road is not in the scenario
+    pub fn departure(&self, scenario: &Scenario) -> Option<DepartureSpec>
+    {
+        let arm = scenario.arm_towards(self.route.first()?, self.route.get(1)?)?;
+        Some(DepartureSpec{tick: self.depart, intersection: self.route[0].clone(), direction: arm.opposite(), destination: self.route.last().cloned(), route: self.route.clone()})
+    }
+}
+
+///The result of importing a SUMO route file
+pub struct SumoRoutes
+{
+    pub vehicles: Vec<SumoVehicle>,
+    ///Vehicles that could not be imported
+    pub warnings: Vec<String>
+}
+
+struct SumoNode
+{
+    id: String,
+    pos: Point,
+    signalized: bool
+}
+
+struct SumoEdge
+{
+    id: String,
+    from: String,
+    to: String,
+    ///Length in meters
+    length: Option<f64>,
+    ///Speed in m/s
+    speed: Option<f64>,
+    lanes: u8,
+    shape: Vec<Point>
+}
+
+///Reads a compiled SUMO `.net.xml` file, see `parse_sumo_net`
+pub fn import_sumo_net(path: impl AsRef<Path>, options: &SumoOptions) -> Result<SumoImport, ScenarioError>
+{
+    let text = std::fs::read_to_string(path)?;
+    parse_sumo_net(&text, options)
+}
+
+///Converts a compiled SUMO network into a scenario.
+///
+/// Internal junctions and edges are left out. Every pair of opposite edges becomes one two way road with the
+/// lanes and length of the first edge, with a warning if the second differs, and other edges become one way roads. Roads are attached to the arm of their intersections closest to
+/// their bearing, `traffic_light` junctions get a fixed time plan and the rest are uncontrolled.
+pub fn parse_sumo_net(text: &str, options: &SumoOptions) -> Result<SumoImport, ScenarioError>
+{
+    let doc = roxmltree::Document::parse(text).map_err(|err| ScenarioError::Xml(err.to_string()))?;
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    for element in doc.root_element().children().filter(|n| n.is_element()){
+        match element.tag_name().name(){
+            "junction" => {
+                if element.attribute("type") == Some("internal"){
+                    continue;
+                }
+                if let Some(node) = parse_node(&element){
+                    nodes.push(node);
+                }
+            },
+            "edge" => {
+                if element.attribute("function").is_some_and(|function| function != "normal"){
+                    continue;
+                }
+                let lanes: Vec<roxmltree::Node> = element.children().filter(|n| n.has_tag_name("lane")).collect();
+                if let Some(mut edge) = parse_edge(&element){
+                    edge.lanes = u8::try_from(lanes.len()).unwrap_or(u8::MAX).max(1);
+                    if let Some(lane) = lanes.first(){
+                        edge.length = edge.length.or(number(lane, "length"));
+                        edge.speed = edge.speed.or(number(lane, "speed"));
+                        if edge.shape.is_empty(){
+                            edge.shape = lane.attribute("shape").map(parse_shape).unwrap_or_default();
+                        }
+                    }
+                    edges.push(edge);
+                }
+            },
+            _ => ()
+        }
+    }
+    Ok(to_scenario(nodes, edges, options))
+}
+
+///Reads SUMO plain `.nod.xml` and `.edg.xml` files, see `parse_sumo_plain`
+pub fn import_sumo_plain(nodes_path: impl AsRef<Path>, edges_path: impl AsRef<Path>, options: &SumoOptions) -> Result<SumoImport, ScenarioError>
+{
+    let nodes = std::fs::read_to_string(nodes_path)?;
+    let edges = std::fs::read_to_string(edges_path)?;
+    parse_sumo_plain(&nodes, &edges, options)
+}
+
+///Converts a SUMO plain network into a scenario, in the same way as `parse_sumo_net`
+pub fn parse_sumo_plain(nodes_text: &str, edges_text: &str, options: &SumoOptions) -> Result<SumoImport, ScenarioError>
+{
+    let nodes_doc = roxmltree::Document::parse(nodes_text).map_err(|err| ScenarioError::Xml(err.to_string()))?;
+    let edges_doc = roxmltree::Document::parse(edges_text).map_err(|err| ScenarioError::Xml(err.to_string()))?;
+    let nodes = nodes_doc.root_element().children()
+        .filter(|n| n.has_tag_name("node"))
+        .filter_map(|n| parse_node(&n))
+        .collect();
+    let edges = edges_doc.root_element().children()
+        .filter(|n| n.has_tag_name("edge"))
+        .filter_map(|n| {
+            let mut edge = parse_edge(&n)?;
+            edge.lanes = n.attribute("numLanes").and_then(|v| v.parse::<u8>().ok()).unwrap_or(1).max(1);
+            Some(edge)
+        })
+        .collect();
+    Ok(to_scenario(nodes, edges, options))
+}
+
+///Writes a scenario as SUMO plain `.nod.xml` and `.edg.xml` files, see `sumo_plain_strings`
+pub fn export_sumo_plain(scenario: &Scenario, nodes_path: impl AsRef<Path>, edges_path: impl AsRef<Path>, options: &SumoOptions) -> Result<(), ScenarioError>
+{
+    let (nodes, edges) = sumo_plain_strings(scenario, options)?;
+    std::fs::write(nodes_path, nodes)?;
+    std::fs::write(edges_path, edges)?;
+    Ok(())
+}
+
+///Converts a scenario into SUMO plain node and edge XML, which `netconvert` can turn into a `.net.xml` file.
+///
+/// Every road becomes an edge named `<from>_<to>`, two way roads also get a `<to>_<from>` edge. Edges
+/// are as long as a vehicle at `default_speed` drives in the road's distance. Intersections are placed
+/// at their positions, or if any are missing laid out by walking the roads from the first intersection
+/// along their arms.
+/// # Returns
+/// `Result<(String, String), ScenarioError>` : The node XML and the edge XML, or `ScenarioError::UnknownIntersection`
+/// if a road goes from or to an intersection the scenario does not have
+pub fn sumo_plain_strings(scenario: &Scenario, options: &SumoOptions) -> Result<(String, String), ScenarioError>
+{
+    let names: HashSet<&str> = scenario.intersections.iter().map(|intersection| intersection.name.as_str()).collect();
+    if let Some(unknown) = scenario.roads.iter().flat_map(|road| [&road.from, &road.to]).find(|name| !names.contains(name.as_str())){
+        return Err(ScenarioError::UnknownIntersection(unknown.clone()));
+    }
+    let meters_per_tick = options.default_speed * options.tick_seconds;
+    let positions = layout(scenario, meters_per_tick);
+    let mut nodes = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<nodes>\n");
+    for intersection in scenario.intersections.iter(){
+        let Point{x, y} = positions[&intersection.name];
+        let kind = match &intersection.signal_plan{
+            Some(plan) if plan.is_uncontrolled() => "priority",
+            _ => "traffic_light"
+        };
+        nodes.push_str(&format!("    <node id=\"{}\" x=\"{:.2}\" y=\"{:.2}\" type=\"{}\"/>\n", escape(&intersection.name), x, y, kind));
+    }
+    nodes.push_str("</nodes>\n");
+
+    let mut edges = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<edges>\n");
+    for road in scenario.roads.iter(){
+        let mut directions = vec![(&road.from, &road.to)];
+        if !road.one_way{
+            directions.push((&road.to, &road.from));
+        }
+        for (from, to) in directions{
+            let mut points = road.shape.clone();
+            if from != &road.from{
+                points.reverse();
+            }
+            let shape = if points.is_empty() {String::new()} else {
+                let line: Vec<String> = std::iter::once(positions[from]).chain(points).chain(std::iter::once(positions[to]))
+                    .map(|point| format!("{:.2},{:.2}", point.x, point.y)).collect();
+                format!(" shape=\"{}\"", line.join(" "))
+            };
+            edges.push_str(&format!("    <edge id=\"{}\" from=\"{}\" to=\"{}\" numLanes=\"{}\" speed=\"{:.2}\" length=\"{:.2}\"{}/>\n",
+                escape(&edge_id(from, to)), escape(from), escape(to), road.lanes, options.default_speed, f64::from(road.distance) * meters_per_tick, shape));
+        }
+    }
+    edges.push_str("</edges>\n");
+    Ok((nodes, edges))
+}
+
+///Reads a SUMO route file, see `parse_sumo_routes`
+pub fn import_sumo_routes(path: impl AsRef<Path>, edges: &HashMap<String, (String, String)>, options: &SumoOptions) -> Result<SumoRoutes, ScenarioError>
+{
+    let text = std::fs::read_to_string(path)?;
+    parse_sumo_routes(&text, edges, options)
+}
+
+///Converts the vehicles of a SUMO route file into routes through the intersections of a scenario.
+///
+/// Vehicles can give their edges in a nested `<route>` or refer to a top level `<route>` by ID.
+/// `<trip>` and `<flow>` elements have no route to follow and are left out.
+/// # Parameters
+/// * `edges` : `&HashMap<String, (String, String)>` - The edges of the network, as returned by an import
+pub fn parse_sumo_routes(text: &str, edges: &HashMap<String, (String, String)>, options: &SumoOptions) -> Result<SumoRoutes, ScenarioError>
+{
+    let doc = roxmltree::Document::parse(text).map_err(|err| ScenarioError::Xml(err.to_string()))?;
+    let named_routes: HashMap<&str, &str> = doc.root_element().children()
+        .filter(|n| n.has_tag_name("route"))
+        .filter_map(|n| Some((n.attribute("id")?, n.attribute("edges")?)))
+        .collect();
+
+    let mut vehicles = Vec::new();
+    let mut warnings = Vec::new();
+    for element in doc.root_element().children().filter(|n| n.is_element()){
+        let id = element.attribute("id").unwrap_or("").to_string();
+        match element.tag_name().name(){
+            "vehicle" => (),
+            "trip" | "flow" => {
+                warnings.push(format!("{} {} has no route, skipping it", element.tag_name().name(), id));
+                continue;
+            },
+            _ => continue
+        }
+        let route_edges = element.children()
+            .find(|n| n.has_tag_name("route"))
+            .and_then(|n| n.attribute("edges"))
+            .or_else(|| named_routes.get(element.attribute("route")?).copied());
+        let Some(route_edges) = route_edges else {
+            warnings.push(format!("Vehicle {} has no route, skipping it", id));
+            continue;
+        };
+        let depart = number(&element, "depart").unwrap_or(0.0);
+
+        let mut route: Vec<String> = Vec::new();
+        for edge in route_edges.split_whitespace(){
+            let Some((from, to)) = edges.get(edge) else {
+                warnings.push(format!("Vehicle {} uses edge {} which is not in the network, skipping it", id, edge));
+                route.clear();
+                break;
+            };
+            if route.is_empty(){
+                route.push(from.clone());
+            }
+            else if route[route.len()-1] != *from{
+                warnings.push(format!("Vehicle {} has a route that is not connected at edge {}, skipping it", id, edge));
+                route.clear();
+                break;
+            }
+            route.push(to.clone());
+        }
+        if !route.is_empty(){
+            vehicles.push(SumoVehicle{id, depart: (depart / options.tick_seconds).round() as usize, route});
+        }
+    }
+    Ok(SumoRoutes{vehicles, warnings})
+}
+
+///Writes vehicles to a SUMO route file, see `sumo_routes_string`
+pub fn export_sumo_routes(path: impl AsRef<Path>, vehicles: &[SumoVehicle], options: &SumoOptions) -> Result<(), ScenarioError>
+{
+    std::fs::write(path, sumo_routes_string(vehicles, options))?;
+    Ok(())
+}
+
+///Converts vehicles into SUMO route XML, using the edge names written by `sumo_plain_strings`
+pub fn sumo_routes_string(vehicles: &[SumoVehicle], options: &SumoOptions) -> String
+{
+    let mut sorted: Vec<&SumoVehicle> = vehicles.iter().collect();
+    sorted.sort_by_key(|vehicle| vehicle.depart);
+    let mut routes = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<routes>\n");
+    for vehicle in sorted{
+        let edges: Vec<String> = vehicle.route.windows(2).map(|pair| escape(&edge_id(&pair[0], &pair[1]))).collect();
+        routes.push_str(&format!("    <vehicle id=\"{}\" depart=\"{:.2}\">\n        <route edges=\"{}\"/>\n    </vehicle>\n",
+            escape(&vehicle.id), vehicle.depart as f64 * options.tick_seconds, edges.join(" ")));
+    }
+    routes.push_str("</routes>\n");
+    routes
+}
+
+fn edge_id(from: &str, to: &str) -> String
+{
+    format!("{}_{}", from, to)
+}
+
+fn parse_node(element: &roxmltree::Node) -> Option<SumoNode>
+{
+    Some(SumoNode{
+        id: element.attribute("id")?.to_string(),
+        pos: Point::new(number(element, "x")?, number(element, "y")?),
+        signalized: element.attribute("type").is_some_and(|kind| kind.starts_with("traffic_light"))
+    })
+}
+
+fn parse_edge(element: &roxmltree::Node) -> Option<SumoEdge>
+{
+    Some(SumoEdge{
+        id: element.attribute("id")?.to_string(),
+        from: element.attribute("from")?.to_string(),
+        to: element.attribute("to")?.to_string(),
+        length: number(element, "length"),
+        speed: number(element, "speed"),
+        lanes: 1,
+        shape: element.attribute("shape").map(parse_shape).unwrap_or_default()
+    })
+}
+
+fn number(element: &roxmltree::Node, attribute: &str) -> Option<f64>
+{
+    element.attribute(attribute)?.parse::<f64>().ok()
+}
+
+fn parse_shape(shape: &str) -> Vec<Point>
+{
+    shape.split_whitespace().filter_map(|point| {
+        let mut coords = point.split(',').map(|v| v.parse::<f64>().ok());
+        Some(Point::new(coords.next()??, coords.next()??))
+    }).collect()
+}
+
+fn escape(text: &str) -> String
+{
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn to_scenario(nodes: Vec<SumoNode>, edges: Vec<SumoEdge>, options: &SumoOptions) -> SumoImport
+{
+    let mut warnings = Vec::new();
+    let positions: HashMap<&str, Point> = nodes.iter().map(|node| (node.id.as_str(), node.pos)).collect();
+    let mut edge_map = HashMap::new();
+    let mut used_nodes: HashSet<&str> = HashSet::new();
+    let mut arms: HashMap<&str, [bool;4]> = HashMap::new();
+    //(from, to) -> index of the road in `roads`
+    let mut pairs: HashMap<(&str, &str), usize> = HashMap::new();
+    let mut roads: Vec<RoadSpec> = Vec::new();
+
+    for edge in edges.iter(){
+        let (Some(from_pos), Some(to_pos)) = (positions.get(edge.from.as_str()), positions.get(edge.to.as_str())) else {
+            warnings.push(format!("Edge {} connects nodes that are not in the network, skipping it", edge.id));
+            continue;
+        };
+        if edge.from == edge.to{
+            warnings.push(format!("Edge {} loops back to node {}, skipping it", edge.id, edge.from));
+            continue;
+        }
+        if pairs.contains_key(&(edge.from.as_str(), edge.to.as_str())){
+            warnings.push(format!("Nodes {} and {} are already connected, skipping edge {}", edge.from, edge.to, edge.id));
+            continue;
+        }
+        let mut shape = edge.shape.clone();
+        if shape.len() < 2{
+            shape = vec![*from_pos, *to_pos];
+        }
+        let length = edge.length.unwrap_or_else(|| polyline_length(&shape));
+        let speed = edge.speed.filter(|speed| *speed > 0.0).unwrap_or(options.default_speed);
+        let ticks = (length / (speed * options.tick_seconds)).round();
+        let distance = ticks.clamp(2.0, f64::from(u32::MAX)) as u32;
+
+        //the opposite edge of a one way road makes it two way, a road has the same lanes and distance both ways
+        if let Some(index) = pairs.get(&(edge.to.as_str(), edge.from.as_str())).copied(){
+            if roads[index].one_way{
+                if (roads[index].lanes, roads[index].distance) != (edge.lanes, distance){
+                    warnings.push(format!("Edge {} has {} lane(s) and takes {} ticks but the opposite edge has {} and takes {}, using those of the opposite edge",
+                        edge.id, edge.lanes, distance, roads[index].lanes, roads[index].distance));
+                }
+                roads[index].one_way = false;
+                pairs.insert((edge.from.as_str(), edge.to.as_str()), index);
+                edge_map.insert(edge.id.clone(), (edge.from.clone(), edge.to.clone()));
+                continue;
+            }
+        }
+        let from_arm = pick_arm(&arms, edge.from.as_str(), shape[0].bearing_to(&shape[1]));
+        let to_arm = pick_arm(&arms, edge.to.as_str(), shape[shape.len()-1].bearing_to(&shape[shape.len()-2]));
+        let (Some(from_arm), Some(to_arm)) = (from_arm, to_arm) else {
+            warnings.push(format!("Nodes {} and {} have more than 4 roads, skipping edge {}", edge.from, edge.to, edge.id));
+            continue;
+        };
+        arms.entry(edge.from.as_str()).or_insert([false;4])[usize::from(from_arm)] = true;
+        arms.entry(edge.to.as_str()).or_insert([false;4])[usize::from(to_arm)] = true;
+        used_nodes.insert(edge.from.as_str());
+        used_nodes.insert(edge.to.as_str());
+
+        if ticks > f64::from(u32::MAX){
+            warnings.push(format!("Edge {} takes {} ticks, shortening it to {}", edge.id, ticks, u32::MAX));
+        }
+
+        let direction = CardinalDirection::try_from(from_arm).unwrap();
+        let to_direction = CardinalDirection::try_from(to_arm).unwrap();
+        pairs.insert((edge.from.as_str(), edge.to.as_str()), roads.len());
+        edge_map.insert(edge.id.clone(), (edge.from.clone(), edge.to.clone()));
+        roads.push(RoadSpec{
+            from: edge.from.clone(),
+            direction,
+            to: edge.to.clone(),
+            to_direction: if to_direction == direction.opposite() {None} else {Some(to_direction)},
+            distance,
+            lanes: edge.lanes,
+            one_way: true,
+            shape: if edge.shape.len() > 2 {edge.shape[1..edge.shape.len()-1].to_vec()} else {Vec::new()}
+        });
+    }
+
+    let intersections = nodes.iter().filter(|node| used_nodes.contains(node.id.as_str())).map(|node| IntersectionSpec{
+        name: node.id.clone(),
+        signal_plan: Some(if node.signalized {SignalPlan::four_phase(options.green_ticks, options.turn_ticks)} else {SignalPlan::uncontrolled()}),
+        position: Some(node.pos)
+    }).collect();
+
+    SumoImport{scenario: Scenario{intersections, roads, ..Default::default()}, edges: edge_map, warnings}
+}
+
+///Places every intersection at its position, or if any are missing by walking the roads along
+/// their arms with each road as long as its distance
+fn layout(scenario: &Scenario, meters_per_tick: f64) -> HashMap<String, Point>
+{
+    let nodes: Vec<(String, Option<Point>)> = scenario.intersections.iter().map(|intersection| (intersection.name.clone(), intersection.position)).collect();
+    let links: Vec<(String, u8, String, u8, f64)> = scenario.roads.iter().map(|road| {
+        let to_direction = road.to_direction.unwrap_or(road.direction.opposite());
+        (road.from.clone(), road.direction.into(), road.to.clone(), to_direction.into(), f64::from(road.distance) * meters_per_tick)
+    }).collect();
+    geometry::layout(&nodes, &links, 1000.0)
+}
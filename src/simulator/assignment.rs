@@ -0,0 +1,363 @@
+use crate::simulator::Simulator;
+use crate::traffic_logic::{intersection::IntersectionId, road::Road, route::Route};
+use std::{cmp::Reverse, collections::{BTreeMap, BinaryHeap, HashMap, VecDeque}, fmt::Display};
+
+///Settings for `assign`
+#[derive(Clone, Copy, Debug)]
+pub struct AssignmentOptions
+{
+    ///The most times the simulation is run
+    pub max_iterations: usize,
+    ///The relative gap at which routes are considered to be in equilibrium
+    pub gap_tolerance: f64,
+    ///The number of ticks link travel times are averaged over, cars entering a road in the same interval share its travel time
+    pub interval: usize,
+    ///The most ticks each run of the simulation can take before the trips still driving are left unfinished
+    pub max_ticks: usize
+}
+
+impl Default for AssignmentOptions
+{
+    fn default() -> Self {
+        AssignmentOptions{max_iterations: 20, gap_tolerance: 0.01, interval: 10, max_ticks: 10000}
+    }
+}
+
+///How one run of the simulation went
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IterationStats
+{
+    pub iteration: usize,
+    ///The total time every trip would save on its quickest route, divided by the total time of every trip on its route
+    pub relative_gap: f64,
+    ///The mean travel time of the trips that reached their destination
+    pub mean_travel_time: f64,
+    ///The number of trips that reached their destination
+    pub completed: usize,
+    ///The number of trips moved to a quicker route after the run
+    pub switched: usize
+}
+
+///The number of trips that take a route
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RouteFlow
+{
+    pub origin: IntersectionId,
+    pub destination: IntersectionId,
+    ///The roads of the route, as the intersection and arm they leave from
+    pub links: Vec<(IntersectionId, u8)>,
+    pub trips: usize
+}
+
+///The result of `assign`
+#[derive(Clone, Debug)]
+pub struct Assignment
+{
+    ///The route of every scheduled trip, in the order of `Simulator::departures`, `None` if its destination cannot be reached
+    pub routes: Vec<Option<Route>>,
+    ///The number of trips on every route used, sorted by origin, destination and route
+    pub route_flows: Vec<RouteFlow>,
+    ///The number of trips that drive every road, keyed by the intersection and arm the road leaves from
+    pub link_volumes: HashMap<(IntersectionId, u8), usize>,
+    pub iterations: Vec<IterationStats>,
+    ///The relative gap fell below `gap_tolerance` before `max_iterations` runs
+    pub converged: bool
+}
+
+///Why `assign` could not finish
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AssignmentError
+{
+    ///The simulator built for an iteration does not schedule the same trips, in the same order, as the first one.
+    /// `expected` and `found` are the numbers of trips the first and this simulator schedule, and `first_difference`
+    /// the place of the first trip that differs, counting from 0
+    DemandChanged{iteration: usize, expected: usize, found: usize, first_difference: usize}
+}
+
+impl Display for AssignmentError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::DemandChanged{iteration, expected, found, first_difference} => write!(f,
+                "The simulator built for iteration {} schedules {} trips where the first scheduled {}, trip {} is the first that differs",
+                iteration, found, expected, first_difference + 1)
+        }
+    }
+}
+
+impl std::error::Error for AssignmentError {}
+
+///Link travel times measured in a run, averaged over intervals of the tick cars entered the road on
+struct LinkTimes
+{
+    interval: usize,
+    //(intersection, arm) -> interval -> (total ticks, cars)
+    times: HashMap<(IntersectionId, u8), BTreeMap<usize, (u64, u64)>>
+}
+
+impl LinkTimes
+{
+    fn measure(simulator: &Simulator, interval: usize) -> LinkTimes
+    {
+        let mut link_times = LinkTimes{interval, times: HashMap::new()};
+        for record in simulator.completed_trips(){
+            link_times.add_path(&record.path, Some(record.arrive_tick));
+        }
+        for trip in simulator.cars().iter().filter_map(|car| car.trip.as_ref()){
+            link_times.add_path(&trip.path, None);
+        }
+        link_times
+    }
+
+    ///Adds the time between entering each road and the next, and from the last road to `end` if the trip finished
+    fn add_path(&mut self, path: &[(IntersectionId, u8, usize)], end: Option<usize>)
+    {
+        let ends = path.iter().skip(1).map(|(_, _, tick)| Some(*tick)).chain(std::iter::once(end));
+        for ((int_id, arm, entered), left) in path.iter().zip(ends){
+            let Some(left) = left else { continue };
+            let entry = self.times.entry((*int_id, *arm)).or_default().entry(entered / self.interval).or_insert((0, 0));
+            entry.0 += (left - entered) as u64;
+            entry.1 += 1;
+        }
+    }
+
+    ///The ticks it takes to drive a road entered on `tick`, the distance of the road if nobody entered it in that interval
+    fn time(&self, road: &Road, int_id: IntersectionId, arm: u8, tick: u64) -> u64
+    {
+        let measured = self.times.get(&(int_id, arm))
+            .and_then(|intervals| intervals.get(&(tick as usize / self.interval)))
+            .map(|(total, count)| (*total as f64 / *count as f64).round() as u64);
+        measured.or_else(|| road.road.get(&int_id)?[usize::from(arm)].map(|node| u64::from(node.dist_from_source)))
+            .unwrap_or(u64::MAX / 4)
+            .max(1)
+    }
+
+    ///The ticks it takes to drive a route setting off on `tick`
+    fn route_time(&self, road: &Road, links: &VecDeque<(IntersectionId, u8)>, tick: usize) -> u64
+    {
+        let start = tick as u64;
+        links.iter().fold(start, |now, (int_id, arm)| now + self.time(road, *int_id, *arm, now)) - start
+    }
+
+    ///Finds the quickest route setting off on `tick` with time dependent Dijkstra
+    fn quickest_route(&self, road: &Road, from: IntersectionId, to: IntersectionId, tick: usize) -> Option<Route>
+    {
+        let start = tick as u64;
+        let mut best: HashMap<IntersectionId, u64> = HashMap::from([(from, start)]);
+        let mut previous: HashMap<IntersectionId, (IntersectionId, u8)> = HashMap::new();
+        let mut queue = BinaryHeap::from([Reverse((start, from))]);
+        while let Some(Reverse((now, current))) = queue.pop(){
+            if current == to{
+                break;
+            }
+            if best.get(&current).is_some_and(|known| *known < now){
+                continue;
+            }
+            let Some(arms) = road.road.get(&current) else { continue };
+            for (arm, node) in arms.iter().enumerate(){
+                let Some(node) = node else { continue };
+                let arrival = now + self.time(road, current, arm as u8, now);
+                if best.get(&node.dest_int_id).is_none_or(|known| arrival < *known){
+                    best.insert(node.dest_int_id, arrival);
+                    previous.insert(node.dest_int_id, (current, arm as u8));
+                    queue.push(Reverse((arrival, node.dest_int_id)));
+                }
+            }
+        }
+        if from == to || !previous.contains_key(&to){
+            return None;
+        }
+        let mut links = VecDeque::new();
+        let mut at = to;
+        while at != from{
+            let link = previous[&at];
+            links.push_front(link);
+            at = link.0;
+        }
+        Some(Route{cost: free_flow_cost(road, &links), links})
+    }
+}
+
+///The ticks it takes to drive a route without waiting
+fn free_flow_cost(road: &Road, links: &VecDeque<(IntersectionId, u8)>) -> u64
+{
+    links.iter()
+        .filter_map(|(int_id, arm)| road.road.get(int_id)?[usize::from(*arm)])
+        .map(|node| u64::from(node.dist_from_source))
+        .sum()
+}
+
+///The trips between an origin and a destination that set off in the same interval, and how their flow is
+/// split over routes
+struct Pair
+{
+    ///The place of every trip of the pair in the demand
+    trips: Vec<usize>,
+    ///Every route the pair has used and the share of its trips on it
+    flows: Vec<(Route, f64)>
+}
+
+impl Pair
+{
+    ///Moves `step` of the pair's flow from every route onto the routes in `quickest`, the quickest route of every trip
+    fn average(&mut self, quickest: &[Route], step: f64)
+    {
+        for (_, flow) in self.flows.iter_mut(){
+            *flow *= 1.0 - step;
+        }
+        for route in quickest{
+            match self.flows.iter_mut().find(|(known, _)| known.links == route.links){
+                Some((_, flow)) => *flow += step,
+                None => self.flows.push((route.clone(), step))
+            }
+        }
+    }
+
+    ///The number of trips on every route, rounding the flows so they add up to the trips of the pair
+    fn trips_per_route(&self) -> Vec<usize>
+    {
+        let mut counts: Vec<usize> = self.flows.iter().map(|(_, flow)| (flow + 1e-9).floor() as usize).collect();
+        let mut remainders: Vec<(usize, f64)> = self.flows.iter().enumerate().map(|(route, (_, flow))| (route, flow - flow.floor())).collect();
+        remainders.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        let missing = self.trips.len().saturating_sub(counts.iter().sum());
+        for (route, _) in remainders.into_iter().take(missing){
+            counts[route] += 1;
+        }
+        counts
+    }
+}
+
+///Finds routes for every scheduled trip that drivers can hardly improve on by switching, a dynamic user
+/// equilibrium found by the method of successive averages.
+///
+/// Trips from the same origin to the same destination setting off in the same `interval` form a pair.
+/// Every iteration builds a new simulator, gives every trip scheduled on it its route and runs it until
+/// every trip has arrived. The travel time of every road is measured for each interval of the tick cars
+/// entered it, and each trip's quickest route is found from those times. The relative gap is the time
+/// trips would save by switching to their quickest route over the time of every trip on its route. If it
+/// is above `gap_tolerance`, a 1/(iteration+1) share of the flow of every pair moves onto the quickest
+/// routes of its trips, and the flows are rounded to whole trips. Trips keep their route if it still has
+/// room for them.
+///
+/// Only departures with a destination are assigned. Cars without a destination and trips already
+/// placed when the simulator is built still drive, and add to the congestion.
+/// # Parameters
+/// * `build` : `FnMut() -> Simulator` - Creates the network with its scheduled trips, it must schedule
+///   the same trips, in the same order, every time as routes are matched to trips by their place
+/// * `options` : `&AssignmentOptions` - When to stop
+/// # Returns
+/// `Result<Assignment, AssignmentError>` : Returns `DemandChanged` if a simulator built after the first
+/// schedules different trips, checked by the tick, origin and destination of each
+pub fn assign(mut build: impl FnMut() -> Simulator, options: &AssignmentOptions) -> Result<Assignment, AssignmentError>
+{
+    let interval = options.interval.max(1);
+    let first = build();
+    //(tick, origin, destination) of every trip to assign
+    let demand: Vec<(usize, IntersectionId, IntersectionId)> = first.departures.iter()
+        .filter_map(|departure| Some((departure.tick, departure.at.int_id, departure.destination?)))
+        .collect();
+    let mut routes: Vec<Option<Route>> = demand.iter()
+        .map(|(_, origin, destination)| first.road.shortest_path(*origin, *destination, &Default::default()))
+        .collect();
+    //every pair starts with all its trips on the shortest route
+    let mut pairs: BTreeMap<(IntersectionId, IntersectionId, usize), Pair> = BTreeMap::new();
+    for (index, (tick, origin, destination)) in demand.iter().enumerate(){
+        let Some(route) = &routes[index] else { continue };
+        let pair = pairs.entry((*origin, *destination, tick / interval)).or_insert_with(|| Pair{trips: Vec::new(), flows: vec![(route.clone(), 0.0)]});
+        pair.trips.push(index);
+        pair.flows[0].1 += 1.0;
+    }
+    let mut iterations = Vec::new();
+    let mut converged = false;
+
+    for iteration in 1..=options.max_iterations.max(1){
+        let mut simulator = build();
+        let scheduled: Vec<(usize, IntersectionId, IntersectionId)> = simulator.departures.iter()
+            .filter_map(|departure| Some((departure.tick, departure.at.int_id, departure.destination?)))
+            .collect();
+        if scheduled != demand{
+            let first_difference = scheduled.iter().zip(demand.iter()).take_while(|(found, expected)| found == expected).count();
+            return Err(AssignmentError::DemandChanged{iteration, expected: demand.len(), found: scheduled.len(), first_difference});
+        }
+        for (trip, departure) in simulator.departures.iter_mut().filter(|departure| departure.destination.is_some()).enumerate(){
+            departure.route = routes.get(trip).cloned().flatten();
+        }
+        while simulator.timestep < options.max_ticks
+            && (simulator.departures.iter().any(|departure| departure.destination.is_some()) || simulator.cars.iter().any(|car| car.trip.is_some())){
+            simulator.run(1);
+        }
+
+        let times = LinkTimes::measure(&simulator, interval);
+        //the quickest route of every trip, its own route if nothing is quicker
+        let mut quickest: Vec<Option<Route>> = vec![None; demand.len()];
+        let (mut total, mut saving) = (0u64, 0u64);
+        for (index, (tick, origin, destination)) in demand.iter().enumerate(){
+            let Some(route) = &routes[index] else { continue };
+            let current = times.route_time(&simulator.road, &route.links, *tick);
+            let best = times.quickest_route(&simulator.road, *origin, *destination, *tick)
+                .map(|best| (times.route_time(&simulator.road, &best.links, *tick), best))
+                .filter(|(time, _)| *time < current);
+            total += current;
+            quickest[index] = Some(match best{
+                Some((time, best)) => {
+                    saving += current - time;
+                    best
+                },
+                None => route.clone()
+            });
+        }
+        let relative_gap = if total == 0 {0.0} else {saving as f64 / total as f64};
+        let completed = simulator.completed_trips();
+        let mean_travel_time = if completed.is_empty() {0.0} else {
+            completed.iter().map(|record| record.travel_time()).sum::<usize>() as f64 / completed.len() as f64
+        };
+        let mut stats = IterationStats{iteration, relative_gap, mean_travel_time, completed: completed.len(), switched: 0};
+
+        if relative_gap <= options.gap_tolerance{
+            converged = true;
+            iterations.push(stats);
+            break;
+        }
+        if iteration < options.max_iterations{
+            let step = 1.0 / (iteration as f64 + 1.0);
+            for pair in pairs.values_mut(){
+                let targets: Vec<Route> = pair.trips.iter().filter_map(|trip| quickest[*trip].clone()).collect();
+                pair.average(&targets, step);
+                let mut room = pair.trips_per_route();
+                //trips stay on their route while it has room, the others move to their quickest route or
+                // else the first route with room
+                let mut moving = Vec::new();
+                for trip in pair.trips.iter().copied(){
+                    let on = pair.flows.iter().position(|(route, _)| routes[trip].as_ref().is_some_and(|current| current.links == route.links));
+                    match on{
+                        Some(route) if room[route] > 0 => room[route] -= 1,
+                        _ => moving.push(trip)
+                    }
+                }
+                for trip in moving{
+                    let preferred = pair.flows.iter().position(|(route, _)| quickest[trip].as_ref().is_some_and(|best| best.links == route.links));
+                    let Some(route) = preferred.filter(|route| room[*route] > 0).or_else(|| room.iter().position(|left| *left > 0)) else { continue };
+                    room[route] -= 1;
+                    routes[trip] = Some(pair.flows[route].0.clone());
+                    stats.switched += 1;
+                }
+            }
+        }
+        iterations.push(stats);
+    }
+
+    //(origin, destination, route) -> trips
+    let mut flows: BTreeMap<_, usize> = BTreeMap::new();
+    let mut link_volumes = HashMap::new();
+    for ((_, origin, destination), route) in demand.iter().zip(routes.iter()){
+        let Some(route) = route else { continue };
+        for link in route.links.iter(){
+            *link_volumes.entry(*link).or_insert(0) += 1;
+        }
+        *flows.entry((*origin, *destination, route.links.iter().copied().collect())).or_insert(0) += 1;
+    }
+    let route_flows = flows.into_iter()
+        .map(|((origin, destination, links), trips)| RouteFlow{origin, destination, links, trips})
+        .collect();
+
+    Ok(Assignment{routes, route_flows, link_volumes, iterations, converged})
+}
@@ -0,0 +1,509 @@
+use crate::simulator::{Simulator, Position, Current};
+use crate::traffic_logic::{road::CardinalDirection, intersection::{SignalPlan, IntersectionId}, geometry::{Point, pick_arm, polyline_length, geo_polyline_length}};
+use std::{collections::{HashMap, HashSet}, fmt::Display};
+
+///A single problem found while assembling a network with a `RoadNetworkBuilder`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NetworkError
+{
+    ///The same intersection name was declared more than once
+    DuplicateIntersection(String),
+    ///A road or car referenced an intersection that was never declared
+    MissingIntersection(String),
+    ///The two intersections are already connected by another road
+    DuplicateArm{from: String, to: String},
+    ///The arm of `intersection` facing `direction` is already used by a road to `existing`
+    DirectionConflict{intersection: String, direction: u8, existing: String, new: String},
+    ///Roads must take at least 2 ticks to traverse
+    InvalidDistance{from: String, to: String, distance: u32},
+    ///Roads must have at least one lane in each direction
+    InvalidLanes{from: String, to: String},
+    ///More intersections were declared than IDs are available
+    TooManyIntersections(usize),
+    ///A road without a direction or distance connects an intersection that has no position
+    MissingPosition(String),
+    ///A road without a direction connects an intersection whose arms are all used
+    NoFreeArm(String)
+}
+
+impl Display for NetworkError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::DuplicateIntersection(name) => write!(f, "Intersection {} is declared more than once", name),
+            Self::MissingIntersection(name) => write!(f, "Intersection {} does not exist", name),
+            Self::DuplicateArm{from, to} => write!(f, "Intersection {} is already connected to Intersection {}", from, to),
+            Self::DirectionConflict{intersection, direction, existing, new} => write!(f,
+                "Intersection {} is already connected to Intersection {} in direction {}, cannot connect it to Intersection {}",
+                intersection, existing, direction, new),
+            Self::InvalidDistance{from, to, distance} => write!(f,
+                "Road from Intersection {} to Intersection {} has distance {}, roads must be at least 2 ticks long", from, to, distance),
+            Self::InvalidLanes{from, to} => write!(f,
+                "Road from Intersection {} to Intersection {} has no lanes, roads must have at least 1", from, to),
+            Self::TooManyIntersections(count) => write!(f, "{} intersections were declared, at most {} are supported", count, u32::MAX),
+            Self::MissingPosition(name) => write!(f, "Intersection {} has no position to work out its roads from", name),
+            Self::NoFreeArm(name) => write!(f, "Intersection {} has no free arm left for another road", name)
+        }
+    }
+}
+
+///Every problem found by `RoadNetworkBuilder::build`. The problems with intersections come first, then those with
+/// roads: the roads given an arm are checked before the roads that pick one, and each group in the order it was added.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BuildError
+{
+    pub errors: Vec<NetworkError>
+}
+
+impl Display for BuildError{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to build road network with {} error(s)", self.errors.len())?;
+        for error in self.errors.iter(){
+            write!(f, "\n  * {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+///Refers to an intersection in a `RoadNetworkBuilder`, either by its name or by its position
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntersectionRef
+{
+    Name(String),
+    At(Point)
+}
+
+impl Display for IntersectionRef{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::Name(name) => write!(f, "{}", name),
+            Self::At(point) => write!(f, "({}, {})", point.x, point.y)
+        }
+    }
+}
+
+impl From<&str> for IntersectionRef{
+    fn from(name: &str) -> Self {
+        Self::Name(name.to_string())
+    }
+}
+
+impl From<String> for IntersectionRef{
+    fn from(name: String) -> Self {
+        Self::Name(name)
+    }
+}
+
+impl From<&String> for IntersectionRef{
+    fn from(name: &String) -> Self {
+        Self::Name(name.clone())
+    }
+}
+
+impl From<Point> for IntersectionRef{
+    fn from(point: Point) -> Self {
+        Self::At(point)
+    }
+}
+
+impl From<(f64, f64)> for IntersectionRef{
+    fn from((x, y): (f64, f64)) -> Self {
+        Self::At(Point::new(x, y))
+    }
+}
+
+struct RoadSpec
+{
+    from: IntersectionRef,
+    direction: Option<CardinalDirection>,
+    to: IntersectionRef,
+    to_direction: Option<CardinalDirection>,
+    distance: Option<u32>,
+    lanes: u8,
+    one_way: bool,
+    shape: Vec<Point>
+}
+
+struct CarSpec
+{
+    at: IntersectionRef,
+    direction: CardinalDirection,
+    tick: Option<usize>,
+    destination: Option<IntersectionRef>
+}
+
+///Assembles intersections, roads and cars by name or position, then checks the whole network at once.
+///
+/// Intersections are given IDs in the order they are declared, starting at 1.
+/// # Examples
+///
+/// ```rust
+/// use traffic_sim::simulator::builder::RoadNetworkBuilder;
+/// use traffic_sim::traffic_logic::road::CardinalDirection::{North, East, South, West};
+/// let mut simulator = RoadNetworkBuilder::new()
+///     .intersection("A")
+///     .intersection("B")
+///     .intersection("C")
+///     .road("A", East, "B", 5)
+///     .road("A", South, "C", 3)
+///     .car("A", East)
+///     .build()
+///     .unwrap();
+/// simulator.run(10);
+///
+/// //arms and distances can also come from where the intersections are
+/// let simulator = RoadNetworkBuilder::new()
+///     .units_per_tick(10.0)
+///     .intersection_at("A", 0.0, 0.0)
+///     .intersection_at("B", 50.0, 0.0)
+///     .intersection_at("C", 0.0, -40.0)
+///     .connect("A", "B")
+///     .connect((0.0, 0.0), (0.0, -40.0))
+///     .build()
+///     .unwrap();
+/// assert_eq!(simulator.road().get_distance(1.into(), 2.into()), Some(5));
+///
+/// let errors = RoadNetworkBuilder::new()
+///     .intersection("A")
+///     .intersection("B")
+///     .intersection("C")
+///     .road("A", East, "B", 5)
+///     .road("A", East, "C", 3) //A's east arm is taken
+///     .road("B", West, "A", 4) //A and B are already connected
+///     .road("A", North, "D", 1) //D does not exist and the road is too short
+///     .build()
+///     .err()
+///     .unwrap();
+/// assert_eq!(errors.errors.len(), 4);
+/// ```
+pub struct RoadNetworkBuilder
+{
+    intersections: Vec<(String, Option<Point>)>,
+    roads: Vec<RoadSpec>,
+    cars: Vec<CarSpec>,
+    signal_plans: Vec<(IntersectionRef, SignalPlan)>,
+    units_per_tick: f64,
+    ///Positions are longitude and latitude rather than planar coordinates
+    geographic: bool
+}
+
+impl Default for RoadNetworkBuilder
+{
+    fn default() -> Self {
+        RoadNetworkBuilder{intersections: Vec::new(), roads: Vec::new(), cars: Vec::new(), signal_plans: Vec::new(), units_per_tick: 1.0, geographic: false}
+    }
+}
+
+impl RoadNetworkBuilder
+{
+    pub fn new() -> RoadNetworkBuilder
+    {
+        RoadNetworkBuilder::default()
+    }
+
+    ///Declares a new intersection
+    /// # Parameters
+    /// * `name` : `&str` - The name used to refer to the intersection in `road` and `car`
+    pub fn intersection(mut self, name : &str) -> RoadNetworkBuilder
+    {
+        self.intersections.push((name.to_string(), None));
+        self
+    }
+
+    ///Declares a new intersection at a position on the map
+    /// # Parameters
+    /// * `name` : `&str` - The name used to refer to the intersection in `road` and `car`
+    /// * `x` : `f64` - The x coordinate, or the longitude if the builder is `geographic`
+    /// * `y` : `f64` - The y coordinate, or the latitude if the builder is `geographic`
+    pub fn intersection_at(mut self, name : &str, x : f64, y : f64) -> RoadNetworkBuilder
+    {
+        self.intersections.push((name.to_string(), Some(Point::new(x, y))));
+        self
+    }
+
+    ///Sets how far on the map a car drives each tick, used to work out the distance of roads added with `connect`.
+    /// The distance is in metres if the builder is `geographic`.
+    pub fn units_per_tick(mut self, units : f64) -> RoadNetworkBuilder
+    {
+        self.units_per_tick = units;
+        self
+    }
+
+    ///Treats the positions of intersections and the points of road shapes as longitude (x) and latitude (y)
+    /// in degrees instead of planar coordinates. Roads added with `connect` are then measured in metres along
+    /// the surface of the Earth and take their arms from compass bearings, which planar coordinates get wrong
+    /// away from the equator.
+    pub fn geographic(mut self) -> RoadNetworkBuilder
+    {
+        self.geographic = true;
+        self
+    }
+
+    ///Connects two intersections in both directions
+    /// # Parameters
+    /// * `from` : `impl Into<IntersectionRef>` - The name or position of the first intersection
+    /// * `direction` : `CardinalDirection` - The arm of `from` the road leaves from, `to` is joined on the opposite arm
+    /// * `to` : `impl Into<IntersectionRef>` - The name or position of the second intersection
+    /// * `distance` : `u32` - The number of ticks it takes to drive the road
+    pub fn road(self, from : impl Into<IntersectionRef>, direction : CardinalDirection, to : impl Into<IntersectionRef>, distance : u32) -> RoadNetworkBuilder
+    {
+        self.road_to_arm(from, direction, to, direction.opposite(), distance)
+    }
+
+    ///Connects two intersections in both directions, joining `to` on any of its arms
+    /// # Parameters
+    /// * `from` : `impl Into<IntersectionRef>` - The name or position of the first intersection
+    /// * `direction` : `CardinalDirection` - The arm of `from` the road leaves from
+    /// * `to` : `impl Into<IntersectionRef>` - The name or position of the second intersection
+    /// * `to_direction` : `CardinalDirection` - The arm of `to` the road arrives at
+    /// * `distance` : `u32` - The number of ticks it takes to drive the road
+    pub fn road_to_arm(mut self, from : impl Into<IntersectionRef>, direction : CardinalDirection, to : impl Into<IntersectionRef>, to_direction : CardinalDirection, distance : u32) -> RoadNetworkBuilder
+    {
+        self.roads.push(RoadSpec{from: from.into(), direction: Some(direction), to: to.into(), to_direction: Some(to_direction), distance: Some(distance), lanes: 1, one_way: false, shape: Vec::new()});
+        self
+    }
+
+    ///Connects two intersections with positions in both directions. The road uses the free arms
+    /// closest to the way it leaves each intersection, and its distance is its length divided by `units_per_tick`.
+    /// The way and length follow the shape of the road if it is given one.
+    /// # Parameters
+    /// * `from` : `impl Into<IntersectionRef>` - The name or position of the first intersection
+    /// * `to` : `impl Into<IntersectionRef>` - The name or position of the second intersection
+    pub fn connect(mut self, from : impl Into<IntersectionRef>, to : impl Into<IntersectionRef>) -> RoadNetworkBuilder
+    {
+        self.roads.push(RoadSpec{from: from.into(), direction: None, to: to.into(), to_direction: None, distance: None, lanes: 1, one_way: false, shape: Vec::new()});
+        self
+    }
+
+    ///Sets the number of lanes in each direction of the last road added
+    pub fn lanes(mut self, lanes : u8) -> RoadNetworkBuilder
+    {
+        if let Some(road) = self.roads.last_mut(){
+            road.lanes = lanes;
+        }
+        self
+    }
+
+    ///Makes the last road added only drivable from `from` to `to`
+    pub fn one_way(mut self) -> RoadNetworkBuilder
+    {
+        if let Some(road) = self.roads.last_mut(){
+            road.one_way = true;
+        }
+        self
+    }
+
+    ///Bends the last road added through some points, in order from `from` to `to`
+    pub fn shape(mut self, points : Vec<Point>) -> RoadNetworkBuilder
+    {
+        if let Some(road) = self.roads.last_mut(){
+            road.shape = points;
+        }
+        self
+    }
+
+    ///Places a car waiting at an intersection
+    /// # Parameters
+    /// * `at` : `impl Into<IntersectionRef>` - The name or position of the intersection
+    /// * `direction` : `CardinalDirection` - The side of the intersection the car is waiting at
+    pub fn car(mut self, at : impl Into<IntersectionRef>, direction : CardinalDirection) -> RoadNetworkBuilder
+    {
+        self.cars.push(CarSpec{at: at.into(), direction, tick: None, destination: None});
+        self
+    }
+
+    ///Schedules a car to appear at an intersection on a later tick
+    /// # Parameters
+    /// * `tick` : `usize` - The timestep the car appears on
+    /// * `at` : `impl Into<IntersectionRef>` - The name or position of the intersection
+    /// * `direction` : `CardinalDirection` - The side of the intersection the car will wait at
+    pub fn departure(mut self, tick : usize, at : impl Into<IntersectionRef>, direction : CardinalDirection) -> RoadNetworkBuilder
+    {
+        self.cars.push(CarSpec{at: at.into(), direction, tick: Some(tick), destination: None});
+        self
+    }
+
+    ///Schedules a car to appear at an intersection on a later tick and drive the shortest route to another
+    /// # Parameters
+    /// * `tick` : `usize` - The timestep the car appears on
+    /// * `at` : `impl Into<IntersectionRef>` - The name or position of the intersection the car starts at
+    /// * `direction` : `CardinalDirection` - The side of the intersection the car will wait at
+    /// * `destination` : `impl Into<IntersectionRef>` - The name or position of the intersection the car drives to
+    pub fn trip(mut self, tick : usize, at : impl Into<IntersectionRef>, direction : CardinalDirection, destination : impl Into<IntersectionRef>) -> RoadNetworkBuilder
+    {
+        self.cars.push(CarSpec{at: at.into(), direction, tick: Some(tick), destination: Some(destination.into())});
+        self
+    }
+
+    ///Controls an intersection with a fixed time signal plan instead of random lights
+    /// # Parameters
+    /// * `at` : `impl Into<IntersectionRef>` - The name or position of the intersection
+    /// * `plan` : `SignalPlan` - The plan to run
+    pub fn signal_plan(mut self, at : impl Into<IntersectionRef>, plan : SignalPlan) -> RoadNetworkBuilder
+    {
+        self.signal_plans.push((at.into(), plan));
+        self
+    }
+
+    ///Validates the network and creates a `Simulator` from it
+    /// # Returns
+    /// `Result<Simulator, BuildError>` : Returns every problem found if the network is invalid
+    pub fn build(self) -> Result<Simulator, BuildError>
+    {
+        let mut errors = Vec::new();
+        let mut ids: HashMap<&str, IntersectionId> = HashMap::new();
+
+        if u32::try_from(self.intersections.len()).is_err(){
+            errors.push(NetworkError::TooManyIntersections(self.intersections.len()));
+        }
+        for (i, (name, _)) in self.intersections.iter().enumerate(){
+            if ids.contains_key(name.as_str()){
+                errors.push(NetworkError::DuplicateIntersection(name.clone()));
+            }
+            else if let Ok(id) = u32::try_from(i+1){
+                ids.insert(name, IntersectionId(id));
+            }
+        }
+        let position = |id: IntersectionId| self.intersections[id.index()].1;
+        let name = |id: IntersectionId| self.intersections[id.index()].0.clone();
+
+        let lookup = |at : &IntersectionRef, errors : &mut Vec<NetworkError>| {
+            let id = match at{
+                IntersectionRef::Name(name) => ids.get(name.as_str()).copied(),
+                IntersectionRef::At(point) => self.intersections.iter()
+                    .position(|(_, pos)| pos.is_some_and(|pos| pos.distance(point) < 1e-9))
+                    .and_then(|i| u32::try_from(i+1).ok().map(IntersectionId))
+            };
+            let missing = NetworkError::MissingIntersection(at.to_string());
+            if id.is_none() && !errors.contains(&missing){
+                errors.push(missing);
+            }
+            id
+        };
+
+        //(intersection, arm) -> name of the intersection on the other end
+        let mut arms: HashMap<(IntersectionId, u8), String> = HashMap::new();
+        //every pair of intersections connected so far, lowest ID first
+        let mut connected: HashSet<(IntersectionId, IntersectionId)> = HashSet::new();
+        let mut valid_roads = Vec::new();
+        //roads with explicit arms claim them first, then the rest pick from what is left
+        let mut ordered: Vec<&RoadSpec> = self.roads.iter().filter(|spec| spec.direction.is_some()).collect();
+        ordered.extend(self.roads.iter().filter(|spec| spec.direction.is_none()));
+        for spec in ordered{
+            let from = lookup(&spec.from, &mut errors);
+            let to = lookup(&spec.to, &mut errors);
+            let mut valid = true;
+            if let Some(distance) = spec.distance.filter(|distance| *distance < 2){
+                errors.push(NetworkError::InvalidDistance{from: spec.from.to_string(), to: spec.to.to_string(), distance});
+                valid = false;
+            }
+            if spec.lanes == 0{
+                errors.push(NetworkError::InvalidLanes{from: spec.from.to_string(), to: spec.to.to_string()});
+                valid = false;
+            }
+            let (Some(from), Some(to)) = (from, to) else { continue };
+            let (from_name, to_name) = (name(from), name(to));
+
+            if connected.contains(&(from.min(to), from.max(to))){
+                errors.push(NetworkError::DuplicateArm{from: from_name, to: to_name});
+                continue;
+            }
+
+            //work out anything that was not given from the positions of the intersections
+            let mut line = Vec::new();
+            if spec.direction.is_none() || spec.distance.is_none(){
+                let (Some(from_pos), Some(to_pos)) = (position(from), position(to)) else {
+                    for id in [from, to].into_iter().filter(|id| position(*id).is_none()){
+                        errors.push(NetworkError::MissingPosition(name(id)));
+                    }
+                    continue;
+                };
+                line.push(from_pos);
+                line.extend(spec.shape.iter().copied());
+                line.push(to_pos);
+            }
+            let mut used: HashMap<IntersectionId, [bool;4]> = [from, to].into_iter().map(|id| {
+                (id, [0, 1, 2, 3].map(|arm| arms.contains_key(&(id, arm))))
+            }).collect();
+            let bearing = |from: &Point, to: &Point| if self.geographic {from.geo_bearing_to(to)} else {from.bearing_to(to)};
+            let direction = spec.direction.map(u8::from).or_else(|| pick_arm(&used, from, bearing(&line[0], &line[1])));
+            if let (Some(dir), true) = (direction, from == to){
+                used.entry(from).or_insert([false;4])[usize::from(dir)] = true;
+            }
+            let to_direction = spec.to_direction.map(u8::from).or_else(|| pick_arm(&used, to, bearing(&line[line.len()-1], &line[line.len()-2])));
+            let (Some(direction), Some(to_direction)) = (direction, to_direction) else {
+                errors.push(NetworkError::NoFreeArm(if direction.is_none() {from_name} else {to_name}));
+                continue;
+            };
+            let length = if self.geographic {geo_polyline_length(&line)} else {polyline_length(&line)};
+            let distance = spec.distance.unwrap_or_else(|| (length / self.units_per_tick).round().clamp(2.0, f64::from(u32::MAX)) as u32);
+
+            for (int, int_name, dir, other) in [(from, &from_name, direction, &to_name), (to, &to_name, to_direction, &from_name)]{
+                if let Some(existing) = arms.get(&(int, dir)){
+                    errors.push(NetworkError::DirectionConflict{intersection: int_name.clone(), direction: dir, existing: existing.clone(), new: other.clone()});
+                    valid = false;
+                }
+            }
+            if from == to && direction == to_direction{
+                errors.push(NetworkError::DirectionConflict{intersection: from_name.clone(), direction, existing: to_name.clone(), new: to_name.clone()});
+                valid = false;
+            }
+            if valid{
+                arms.insert((from, direction), to_name);
+                arms.insert((to, to_direction), from_name);
+                connected.insert((from.min(to), from.max(to)));
+                valid_roads.push((from, direction, to, to_direction, distance, spec));
+            }
+        }
+
+        let mut valid_cars = Vec::new();
+        for spec in self.cars.iter(){
+            let id = lookup(&spec.at, &mut errors);
+            let destination = spec.destination.as_ref().map(|destination| lookup(destination, &mut errors));
+            if let (Some(id), None | Some(Some(_))) = (id, destination){
+                valid_cars.push((spec.tick, Current{int_id: id, direction: spec.direction.into()}, destination.flatten()));
+            }
+        }
+
+        let mut valid_plans = Vec::new();
+        for (at, plan) in self.signal_plans.iter(){
+            if let Some(id) = lookup(at, &mut errors){
+                valid_plans.push((id, plan.clone()));
+            }
+        }
+
+        if !errors.is_empty(){
+            return Err(BuildError{errors});
+        }
+
+        let mut simulator = Simulator::new();
+        simulator.add_intersections(self.intersections.len() as u32);
+        for (i, (_, pos)) in self.intersections.iter().enumerate(){
+            if let Some(pos) = pos{
+                simulator.set_position(IntersectionId(i as u32 + 1), *pos);
+            }
+        }
+        for (from, direction, to, to_direction, distance, spec) in valid_roads{
+            simulator.add_link(from, direction, to, to_direction, distance, spec.lanes);
+            simulator.set_shape(from, direction, spec.shape.clone());
+            if !spec.one_way{
+                simulator.add_link(to, to_direction, from, direction, distance, spec.lanes);
+                simulator.set_shape(to, to_direction, spec.shape.iter().rev().copied().collect());
+            }
+        }
+        for (id, plan) in valid_plans{
+            simulator.set_signal_plan(id, plan);
+        }
+        for (tick, current, destination) in valid_cars{
+            match (tick, destination){
+                (Some(tick), Some(destination)) => simulator.schedule_trip(tick, current, destination),
+                (Some(tick), None) => simulator.schedule_car(tick, current),
+                (None, _) => {
+                    simulator.add_car(Position::new(Some(current), None));
+                }
+            }
+        }
+        Ok(simulator)
+    }
+}
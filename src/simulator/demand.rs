@@ -0,0 +1,244 @@
+use crate::simulator::{Simulator, Current};
+use crate::traffic_logic::{car::CarId, intersection::IntersectionId, vehicle::VehicleClass};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+///The means above this are sampled as a sum of smaller ones, `exp(-mean)` gets too small to compare against
+const MAX_POISSON_MEAN : f64 = 30.0;
+
+///The number of trips an hour from one intersection to another
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OdPair
+{
+    pub origin : IntersectionId,
+    pub destination : IntersectionId,
+    pub trips_per_hour : f64
+}
+
+///Scales demand by the time of day. The factors repeat once the last interval is over, so 24 hourly
+/// factors describe every day of a run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimeProfile
+{
+    ///The number of ticks each factor lasts
+    pub interval : usize,
+    ///The multiplier of every trip rate in each interval, an empty profile leaves the rates as they are
+    pub factors : Vec<f64>
+}
+
+impl TimeProfile
+{
+    pub fn new(interval : usize, factors : Vec<f64>) -> TimeProfile
+    {
+        TimeProfile{interval, factors}
+    }
+
+    ///The multiplier of every trip rate on a tick
+    pub fn factor(&self, tick : usize) -> f64
+    {
+        if self.factors.is_empty() || self.interval == 0{
+            return 1.0;
+        }
+        self.factors[(tick / self.interval) % self.factors.len()]
+    }
+}
+
+///Cars that start trips over time, from an origin–destination matrix.
+///
+/// Every tick, each pair starts a Poisson distributed number of trips, with a mean of its rate
+/// scaled by the profile. New trips join the entry queue of their origin. One car a tick leaves each
+/// entry queue, onto the side of the origin facing the first road of its shortest route, unless
+/// the cars already waiting there take up `max_queue` passenger car spaces. Blocked cars stay in
+/// the entry queue until there is room, so the time they spent waiting to enter is kept in their
+/// `EntryRecord`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Demand
+{
+    pub pairs : Vec<OdPair>,
+    pub profile : TimeProfile,
+    ///The number of seconds a tick lasts, used to turn trips an hour into trips a tick
+    pub tick_seconds : f64,
+    ///The passenger car spaces that can be taken up at the side of an origin before entries there are blocked
+    pub max_queue : usize,
+    ///The share of trips made by each kind of vehicle, every trip is by passenger car if it is empty
+    pub classes : Vec<(VehicleClass, f64)>
+}
+
+impl Demand
+{
+    pub fn new(pairs : Vec<OdPair>) -> Demand
+    {
+        Demand{pairs, profile: TimeProfile::default(), tick_seconds: 1.0, max_queue: 10, classes: Vec::new()}
+    }
+
+    pub fn with_profile(mut self, profile : TimeProfile) -> Demand
+    {
+        self.profile = profile;
+        self
+    }
+
+    pub fn with_classes(mut self, classes : Vec<(VehicleClass, f64)>) -> Demand
+    {
+        self.classes = classes;
+        self
+    }
+
+    ///Picks the kind of vehicle a trip is made by from the class shares
+    pub(crate) fn sample_class(&self) -> VehicleClass
+    {
+        pick_weighted(&self.classes).unwrap_or_default()
+    }
+
+    ///The mean number of trips a pair starts on a tick
+    pub fn rate(&self, pair : &OdPair, tick : usize) -> f64
+    {
+        pair.trips_per_hour * self.tick_seconds / 3600.0 * self.profile.factor(tick)
+    }
+}
+
+///A trip started by the demand, and when it got into the network
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EntryRecord
+{
+    pub origin : IntersectionId,
+    pub destination : IntersectionId,
+    pub class : VehicleClass,
+    ///The tick the trip joined the entry queue
+    pub generated_tick : usize,
+    ///The tick the car was placed at its origin
+    pub entered_tick : usize,
+    ///The car driving the trip, `None` if the destination could not be reached and no car was placed
+    pub car_id : Option<CarId>,
+    ///The trip was at the front of its entry queue on a tick the first road of its route had no room
+    pub(crate) blocked : bool
+}
+
+impl EntryRecord
+{
+    ///The car had to wait in the entry queue because its origin was full, not only for the trips ahead of it
+    pub fn was_blocked(&self) -> bool
+    {
+        self.blocked
+    }
+}
+
+///A trip waiting in an entry queue
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct PendingEntry
+{
+    pub(crate) destination : IntersectionId,
+    pub(crate) class : VehicleClass,
+    generated_tick : usize,
+    blocked : bool
+}
+
+///Picks one of a list of choices with a chance in proportion to its weight
+/// # Returns
+/// `Option<T>` : Returns `None` if no choice has a weight above 0
+pub(crate) fn pick_weighted<T : Copy>(choices : &[(T, f64)]) -> Option<T>
+{
+    let total : f64 = choices.iter().map(|(_, weight)| weight.max(0.0)).sum();
+    if total <= 0.0{
+        return None;
+    }
+    let mut pick = rand::random::<f64>() * total;
+    for (choice, weight) in choices.iter(){
+        if pick < weight.max(0.0){
+            return Some(*choice);
+        }
+        pick -= weight.max(0.0);
+    }
+    choices.iter().rev().find(|(_, weight)| *weight > 0.0).map(|(choice, _)| *choice)
+}
+
+///Samples a Poisson distributed number with Knuth's method
+pub(crate) fn poisson(mean : f64) -> usize
+{
+    if mean <= 0.0{
+        return 0;
+    }
+    if mean > MAX_POISSON_MEAN{
+        return poisson(mean / 2.0) + poisson(mean / 2.0);
+    }
+    let limit = (-mean).exp();
+    let mut product = rand::random::<f64>();
+    let mut count = 0;
+    while product > limit{
+        product *= rand::random::<f64>();
+        count += 1;
+    }
+    count
+}
+
+impl Simulator
+{
+    ///Starts trips from an origin–destination matrix every tick from now on, replacing any demand set before.
+    /// Trips still waiting in entry queues are kept.
+    pub fn set_demand(&mut self, demand : Demand)
+    {
+        self.demand = Some(demand);
+    }
+
+    ///Stops starting trips from the demand, trips still waiting in entry queues keep entering
+    pub fn clear_demand(&mut self)
+    {
+        self.demand = None;
+    }
+
+    pub fn demand(&self) -> Option<&Demand>
+    {
+        self.demand.as_ref()
+    }
+
+    ///Every trip that has left an entry queue, in the order they left
+    pub fn entry_log(&self) -> &[EntryRecord]
+    {
+        &self.entry_log
+    }
+
+    ///The number of trips waiting to enter the network at an intersection
+    pub fn entry_queue_length(&self, origin : IntersectionId) -> usize
+    {
+        self.entry_queues.get(&origin).map_or(0, VecDeque::len)
+    }
+
+    ///Starts this tick's trips and lets one car from every entry queue into the network
+    pub(crate) fn tick_demand(&mut self)
+    {
+        let tick = self.timestep;
+        if let Some(demand) = &self.demand{
+            for pair in demand.pairs.iter(){
+                for _ in 0..poisson(demand.rate(pair, tick)){
+                    self.entry_queues.entry(pair.origin).or_default()
+                        .push_back(PendingEntry{destination: pair.destination, class: demand.sample_class(), generated_tick: tick, blocked: false});
+                }
+            }
+        }
+        if self.entry_queues.is_empty(){
+            return;
+        }
+        let max_queue = self.demand.as_ref().map_or(usize::MAX, |demand| demand.max_queue);
+        let closed = self.closed_links();
+        let mut origins: Vec<IntersectionId> = self.entry_queues.keys().copied().collect();
+        origins.sort();
+        for origin in origins{
+            let Some(pending) = self.entry_queues.get(&origin).and_then(|queue| queue.front().copied()) else { continue };
+            let route = self.road.shortest_path(origin, pending.destination, &closed);
+            let mut car_id = None;
+            if let Some(route) = route{
+                let side = (route.links[0].1 + 2) % 4;
+                if self.queue_occupancy(origin, side) >= max_queue as f64{
+                    self.entry_queues.get_mut(&origin).and_then(VecDeque::front_mut).expect("Queue was just read").blocked = true;
+                    continue;
+                }
+                car_id = self.add_trip_on_route(Current{int_id: origin, direction: side}, pending.destination, route);
+                if let Some(car_id) = car_id{
+                    self.set_vehicle_class(car_id, pending.class);
+                }
+            }
+            self.entry_queues.get_mut(&origin).expect("Queue was just read").pop_front();
+            self.entry_log.push(EntryRecord{origin, destination: pending.destination, class: pending.class, generated_tick: pending.generated_tick, entered_tick: tick, car_id, blocked: pending.blocked});
+        }
+        self.entry_queues.retain(|_, queue| !queue.is_empty());
+    }
+}
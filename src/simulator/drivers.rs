@@ -0,0 +1,94 @@
+use crate::simulator::Simulator;
+use crate::simulator::demand::pick_weighted;
+use crate::simulator::violations::{Entry, NonCompliance};
+use crate::traffic_logic::{car::{Car, CarId, Direction}, driver::DriverProfile, intersection::{Intersection, IntersectionId, LightStatus}};
+use std::collections::HashMap;
+
+impl Simulator
+{
+    ///Sets the share of drivers of each profile, every car added from now on gets a profile picked
+    /// from it. Cars added while the mix is empty have no profile and follow the lights exactly.
+    pub fn set_driver_mix(&mut self, mix : Vec<(DriverProfile, f64)>)
+    {
+        self.driver_mix = mix;
+    }
+
+    pub fn driver_mix(&self) -> &[(DriverProfile, f64)]
+    {
+        &self.driver_mix
+    }
+
+    ///Changes the driver profile of a car
+    /// # Returns
+    /// `bool` : Returns `false` if there is no car with that ID
+    pub fn set_driver_profile(&mut self, car_id : CarId, driver : Option<DriverProfile>) -> bool
+    {
+        match self.get_car_mut(car_id){
+            Some(car) => {
+                car.driver = driver;
+                true
+            },
+            None => false
+        }
+    }
+
+    pub(crate) fn sample_driver(&self) -> Option<DriverProfile>
+    {
+        pick_weighted(&self.driver_mix)
+    }
+
+    ///Finds how soon the next car arrives at every side of every intersection
+    /// # Returns
+    /// `HashMap<(IntersectionId, u8), u32>` : The ticks until the closest car driving towards each side arrives, sides no car is driving towards are left out
+    pub(crate) fn approach_gaps(&self) -> HashMap<(IntersectionId, u8), u32>
+    {
+        let mut gaps: HashMap<(IntersectionId, u8), u32> = HashMap::new();
+        for between in self.car_positions.values().filter_map(|pos| pos.in_between){
+            let gap = gaps.entry((between.int_2_id, between.from)).or_insert(u32::MAX);
+            *gap = (*gap).min(between.distance_to_target);
+        }
+        gaps
+    }
+
+    ///Checks if a car at the stop line of one side of an intersection goes this tick. Besides going
+    /// on its own light, a driver with a profile turns left on a green light without a green arrow,
+    /// and right on red, when the next conflicting car is at least its critical gap away. A driver
+    /// that may not go can still run the light, with the chance set by the non-compliance. A driver facing
+    /// a yellow keeps to the decision it made when the light turned yellow.
+    /// # Parameters
+    /// * `gaps` : `&HashMap<(IntersectionId, u8), u32>` - How soon the next car arrives at every side, from `approach_gaps`
+    /// * `non_compliance` : `Option<&NonCompliance>` - How often drivers run the lights, no one does if `None`
+    pub(crate) fn permitted(car : &mut Car, intersection : &Intersection, side : u8, gaps : &HashMap<(IntersectionId, u8), u32>, non_compliance : Option<&NonCompliance>) -> Entry
+    {
+        let main_light_index = usize::from((side+2)%4);
+        car.see_light(&intersection.lights, main_light_index);
+        if car.can_go(&intersection.lights, main_light_index) || Self::gap_accepted(car, intersection, side, gaps){
+            return Entry::Legal;
+        }
+        Self::runs_light(non_compliance, car, intersection.id, car.light_for(&intersection.lights, main_light_index), side)
+    }
+
+    ///Checks if a driver with a profile turns in front of conflicting traffic without a green light for its turn
+    fn gap_accepted(car : &Car, intersection : &Intersection, side : u8, gaps : &HashMap<(IntersectionId, u8), u32>) -> bool
+    {
+        let main_light_index = usize::from((side+2)%4);
+        let Some(params) = car.driver.map(|driver| driver.params()) else {
+            return false;
+        };
+        //cars already queued at a side that has green are about to drive through
+        let gap = |from : u8| {
+            let moving = intersection.lights[usize::from((from+2)%4)].main_status == LightStatus::Green;
+            if moving && !intersection.light_queues[usize::from(from)].is_empty() {0}
+            else {gaps.get(&(intersection.id, from)).copied().unwrap_or(u32::MAX)}
+        };
+        let light = intersection.lights[main_light_index];
+        match car.intention{
+            Direction::Left | Direction::UTurn => light.main_status == LightStatus::Green
+                && gap((side+2)%4) >= params.critical_gap,
+            Direction::Right => light.main_status == LightStatus::Red
+                && gap((side+1)%4) >= params.critical_gap
+                && rand::random::<f64>() < params.turn_on_red,
+            Direction::Straight => false
+        }
+    }
+}
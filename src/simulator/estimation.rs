@@ -0,0 +1,188 @@
+use crate::simulator::{Simulator, demand::Demand};
+use crate::traffic_logic::intersection::IntersectionId;
+use std::collections::HashMap;
+
+///Where cars were counted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CountLocation
+{
+    ///Cars driving onto the road leaving an intersection on an arm
+    Link{int_id: IntersectionId, arm: u8},
+    ///Cars arriving at an intersection on `approach` and leaving it on `exit`
+    Turn{int_id: IntersectionId, approach: u8, exit: u8}
+}
+
+///The number of cars an hour counted at a location
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ObservedCount
+{
+    pub location: CountLocation,
+    pub vehicles_per_hour: f64
+}
+
+///Settings for `estimate_od`
+#[derive(Clone, Copy, Debug)]
+pub struct EstimationOptions
+{
+    ///The number of times the simulation is run, the matrix is adjusted between runs
+    pub max_iterations: usize,
+    ///The number of times every count is balanced after each run
+    pub balancing_iterations: usize,
+    ///The number of ticks each run lasts
+    pub ticks: usize
+}
+
+impl Default for EstimationOptions
+{
+    fn default() -> Self {
+        EstimationOptions{max_iterations: 10, balancing_iterations: 20, ticks: 3600}
+    }
+}
+
+///How well one run of the simulation matched the counts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EstimationStats
+{
+    pub iteration: usize,
+    ///The root mean square difference between the simulated and observed counts, in cars an hour
+    pub rmse: f64,
+    ///The share of counts with a GEH statistic below 5, the usual target for a calibrated model is 0.85
+    pub geh_below_5: f64,
+    ///The trips an hour of the whole matrix
+    pub total_trips_per_hour: f64
+}
+
+///The result of `estimate_od`
+#[derive(Clone, Debug)]
+pub struct OdEstimate
+{
+    ///The seed demand with the trip rates of the run closest to the counts
+    pub demand: Demand,
+    ///The counts that run produced, in the order of the observed counts
+    pub simulated_counts: Vec<f64>,
+    pub iterations: Vec<EstimationStats>
+}
+
+///The GEH statistic comparing a simulated and an observed hourly count
+pub fn geh(simulated: f64, observed: f64) -> f64
+{
+    if simulated + observed <= 0.0{
+        return 0.0;
+    }
+    (2.0 * (simulated - observed).powi(2) / (simulated + observed)).sqrt()
+}
+
+///Counts how many times the trips of every pair passed every count location in a run
+/// # Returns
+/// `HashMap<(IntersectionId, IntersectionId), HashMap<usize, f64>>` : Keyed by origin and destination, then by the index of the count
+fn count_passes(simulator: &Simulator, locations: &HashMap<CountLocation, usize>) -> HashMap<(IntersectionId, IntersectionId), HashMap<usize, f64>>
+{
+    let trips = simulator.completed_trips().iter().map(|record| (record.origin, record.destination, &record.path))
+        .chain(simulator.cars().iter().filter_map(|car| car.trip.as_ref()).map(|trip| (trip.origin, trip.destination, &trip.path)));
+    let mut passes: HashMap<(IntersectionId, IntersectionId), HashMap<usize, f64>> = HashMap::new();
+    for (origin, destination, path) in trips{
+        let pair = passes.entry((origin, destination)).or_default();
+        let mut approach = None;
+        for (int_id, arm, _) in path.iter(){
+            let mut passed = vec![CountLocation::Link{int_id: *int_id, arm: *arm}];
+            if let Some(approach) = approach{
+                passed.push(CountLocation::Turn{int_id: *int_id, approach, exit: *arm});
+            }
+            for location in passed{
+                if let Some(index) = locations.get(&location){
+                    *pair.entry(*index).or_insert(0.0) += 1.0;
+                }
+            }
+            approach = simulator.road().road.get(int_id).and_then(|arms| arms[usize::from(*arm)]).map(|node| node.direction);
+        }
+    }
+    passes
+}
+
+///Adjusts a seed origin–destination matrix until the simulated counts match observed link and
+/// turning counts.
+///
+/// Every iteration builds a new simulator, runs the demand on it for `ticks` and measures which
+/// share of each pair's trips passes every count location, so routing, rerouting and queues at the
+/// entries all shape the estimate. The rates are then balanced against the counts one at a time
+/// with the maximum entropy method of Van Zuylen and Willumsen: the rate of every pair crossing a
+/// count is scaled by `1 + p (observed / simulated - 1)`, `p` being the share of its trips that
+/// cross it. Pairs that are not in the seed stay empty and pairs that cross no count keep their seed rate.
+///
+/// Arrivals are random, so the counts of a run scatter around those the rates give. The rates of
+/// the run with the lowest RMSE are returned rather than the last ones.
+/// # Parameters
+/// * `build` : `FnMut() -> Simulator` - Creates the network, the demand is set on it
+/// * `seed` : `&Demand` - The matrix to start from, its profile and tick length are kept
+/// * `counts` : `&[ObservedCount]` - The counts to match, in cars an hour averaged over the run
+/// * `options` : `&EstimationOptions` - How long and how often to run
+pub fn estimate_od(mut build: impl FnMut() -> Simulator, seed: &Demand, counts: &[ObservedCount], options: &EstimationOptions) -> OdEstimate
+{
+    let ticks = options.ticks.max(1);
+    let hours = ticks as f64 * seed.tick_seconds / 3600.0;
+    //the trips a pair starts in a run for every trip an hour of its rate
+    let trips_per_rate: f64 = (0..ticks).map(|tick| seed.tick_seconds / 3600.0 * seed.profile.factor(tick)).sum();
+    let locations: HashMap<CountLocation, usize> = counts.iter().enumerate().map(|(index, count)| (count.location, index)).collect();
+    let mut demand = seed.clone();
+    let mut iterations: Vec<EstimationStats> = Vec::new();
+    let mut best = (seed.clone(), vec![0.0; counts.len()]);
+
+    for iteration in 1..=options.max_iterations.max(1){
+        let mut simulator = build();
+        simulator.set_demand(demand.clone());
+        simulator.run(ticks);
+        let passes = count_passes(&simulator, &locations);
+
+        let mut simulated_counts = vec![0.0; counts.len()];
+        for pair_passes in passes.values(){
+            for (index, passed) in pair_passes.iter(){
+                simulated_counts[*index] += passed / hours;
+            }
+        }
+        let squared: f64 = counts.iter().zip(simulated_counts.iter()).map(|(count, simulated)| (simulated - count.vehicles_per_hour).powi(2)).sum();
+        let good = counts.iter().zip(simulated_counts.iter()).filter(|(count, simulated)| geh(**simulated, count.vehicles_per_hour) < 5.0).count();
+        let stats = EstimationStats{
+            iteration,
+            rmse: if counts.is_empty() {0.0} else {(squared / counts.len() as f64).sqrt()},
+            geh_below_5: if counts.is_empty() {1.0} else {good as f64 / counts.len() as f64},
+            total_trips_per_hour: demand.pairs.iter().map(|pair| pair.trips_per_hour).sum()
+        };
+        if iterations.iter().all(|previous| stats.rmse < previous.rmse){
+            best = (demand.clone(), simulated_counts);
+        }
+        iterations.push(stats);
+        if iteration >= options.max_iterations{
+            break;
+        }
+
+        //(pair, count) -> share of the pair's trips that pass the count
+        let shares: Vec<Vec<(usize, f64)>> = demand.pairs.iter().map(|pair| {
+            let expected = pair.trips_per_hour * trips_per_rate;
+            let Some(pair_passes) = passes.get(&(pair.origin, pair.destination)).filter(|_| expected > 0.0) else {
+                return Vec::new();
+            };
+            pair_passes.iter().map(|(index, passed)| (*index, (passed / expected).min(1.0))).collect()
+        }).collect();
+        let mut crossing: Vec<Vec<(usize, f64)>> = vec![Vec::new(); counts.len()];
+        for (pair, pair_shares) in shares.iter().enumerate(){
+            for (index, share) in pair_shares.iter(){
+                crossing[*index].push((pair, *share));
+            }
+        }
+        for _ in 0..options.balancing_iterations{
+            for (count, crossing) in counts.iter().zip(crossing.iter()){
+                let modelled: f64 = crossing.iter().map(|(pair, share)| demand.pairs[*pair].trips_per_hour * trips_per_rate * share / hours).sum();
+                if modelled <= 0.0{
+                    continue;
+                }
+                let ratio = count.vehicles_per_hour.max(0.0) / modelled;
+                for (pair, share) in crossing.iter(){
+                    demand.pairs[*pair].trips_per_hour *= 1.0 + share * (ratio - 1.0);
+                }
+            }
+        }
+    }
+
+    let (demand, simulated_counts) = best;
+    OdEstimate{demand, simulated_counts, iterations}
+}
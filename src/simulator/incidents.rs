@@ -0,0 +1,185 @@
+use crate::simulator::Simulator;
+use crate::traffic_logic::intersection::IntersectionId;
+use std::collections::HashSet;
+
+///A road leaving an intersection on an arm that cannot be entered from `from_tick` until `to_tick`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Closure
+{
+    pub int_id : IntersectionId,
+    pub arm : u8,
+    pub from_tick : usize,
+    pub to_tick : usize
+}
+
+///A road between two intersections that lets fewer cars out while it is active
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Incident
+{
+    pub int_1_id : IntersectionId,
+    pub int_2_id : IntersectionId,
+    pub from_tick : usize,
+    pub to_tick : usize,
+    ///The share of a car that can leave the road each tick, 0.5 lets one car out every other tick
+    pub capacity : f64
+}
+
+impl Closure
+{
+    pub fn is_active(&self, tick : usize) -> bool
+    {
+        self.from_tick <= tick && tick < self.to_tick
+    }
+}
+
+impl Incident
+{
+    pub fn is_active(&self, tick : usize) -> bool
+    {
+        self.from_tick <= tick && tick < self.to_tick
+    }
+}
+
+impl Simulator
+{
+    ///Closes the road between two intersections in both directions.
+    ///
+    /// Cars cannot turn onto the road while it is closed and pick another direction instead. Cars
+    /// that are already on it when it closes keep driving to the end.
+    /// # Parameters
+    /// * `int_1` : `IntersectionId` - The ID of one end of the road
+    /// * `int_2` : `IntersectionId` - The ID of the other end of the road
+    /// * `from_tick` : `usize` - The first tick the road is closed on
+    /// * `to_tick` : `usize` - The tick the road opens again on
+    pub fn close_road(&mut self, int_1 : IntersectionId, int_2 : IntersectionId, from_tick : usize, to_tick : usize)
+    {
+        let mut links = self.links_between(int_1, int_2);
+        links.extend(self.links_between(int_2, int_1));
+        if links.is_empty(){
+            panic!("Intersection {} has no connection to Intersection {}", int_1, int_2);
+        }
+        for (int_id, arm) in links{
+            self.closures.push(Closure{int_id, arm, from_tick, to_tick});
+        }
+    }
+
+    ///Closes one arm of an intersection, both the road leaving from it and every road arriving at it
+    /// # Parameters
+    /// * `int_id` : `IntersectionId` - The ID of the intersection
+    /// * `arm` : `u8` - The 0,1,2,3 direction of the arm
+    /// * `from_tick` : `usize` - The first tick the arm is closed on
+    /// * `to_tick` : `usize` - The tick the arm opens again on
+    pub fn close_arm(&mut self, int_id : IntersectionId, arm : u8, from_tick : usize, to_tick : usize)
+    {
+        self.closures.push(Closure{int_id, arm, from_tick, to_tick});
+        for (from, from_arm) in self.links_arriving(int_id, arm){
+            self.closures.push(Closure{int_id: from, arm: from_arm, from_tick, to_tick});
+        }
+    }
+
+    ///Ends every closure of the road between two intersections that has not ended yet
+    pub fn reopen_road(&mut self, int_1 : IntersectionId, int_2 : IntersectionId)
+    {
+        let mut links = self.links_between(int_1, int_2);
+        links.extend(self.links_between(int_2, int_1));
+        let now = self.timestep;
+        self.closures.iter_mut()
+            .filter(|closure| links.contains(&(closure.int_id, closure.arm)) && closure.to_tick > now)
+            .for_each(|closure| closure.to_tick = now.max(closure.from_tick));
+    }
+
+    ///Ends every closure of an arm of an intersection that has not ended yet
+    pub fn reopen_arm(&mut self, int_id : IntersectionId, arm : u8)
+    {
+        let now = self.timestep;
+        let arriving = self.links_arriving(int_id, arm);
+        self.closures.iter_mut()
+            .filter(|closure| ((closure.int_id, closure.arm) == (int_id, arm) || arriving.contains(&(closure.int_id, closure.arm))) && closure.to_tick > now)
+            .for_each(|closure| closure.to_tick = now.max(closure.from_tick));
+    }
+
+    ///Limits how many cars can leave the road from `int_1` to `int_2` for a while
+    /// # Parameters
+    /// * `capacity` : `f64` - The share of a car that can leave the road each tick, between 0 and 1
+    pub fn add_incident(&mut self, int_1 : IntersectionId, int_2 : IntersectionId, from_tick : usize, to_tick : usize, capacity : f64)
+    {
+        if self.links_between(int_1, int_2).is_empty(){
+            panic!("Intersection {} has no connection to Intersection {}", int_1, int_2);
+        }
+        self.incidents.push(Incident{int_1_id: int_1, int_2_id: int_2, from_tick, to_tick, capacity: capacity.clamp(0.0, 1.0)});
+    }
+
+    ///Closes the road leaving an intersection on an arm without closing the road arriving at it,
+    /// as closures are kept one road at a time
+    pub(crate) fn add_closure(&mut self, closure : Closure)
+    {
+        self.closures.push(closure);
+    }
+
+    pub fn closures(&self) -> &[Closure]
+    {
+        &self.closures
+    }
+
+    pub fn incidents(&self) -> &[Incident]
+    {
+        &self.incidents
+    }
+
+    ///Checks if the road leaving an intersection on an arm is closed on the current tick
+    pub fn is_closed(&self, int_id : IntersectionId, arm : u8) -> bool
+    {
+        self.closures.iter().any(|closure| closure.int_id == int_id && closure.arm == arm && closure.is_active(self.timestep))
+    }
+
+    ///Gets every (intersection, arm) whose road leaving it is closed on the current tick
+    pub(crate) fn closed_links(&self) -> HashSet<(IntersectionId, u8)>
+    {
+        self.closures.iter()
+            .filter(|closure| closure.is_active(self.timestep))
+            .map(|closure| (closure.int_id, closure.arm))
+            .collect()
+    }
+
+    ///Gives every road with an active incident its share of a car for this tick
+    pub(crate) fn tick_incidents(&mut self)
+    {
+        let now = self.timestep;
+        let mut active: Vec<((IntersectionId, IntersectionId), f64)> = Vec::new();
+        for incident in self.incidents.iter().filter(|incident| incident.is_active(now)){
+            let link = (incident.int_1_id, incident.int_2_id);
+            match active.iter_mut().find(|(other, _)| *other == link){
+                Some((_, capacity)) => *capacity = capacity.min(incident.capacity),
+                None => active.push((link, incident.capacity))
+            }
+        }
+        self.incident_credit.retain(|link, _| active.iter().any(|(other, _)| other == link));
+        for (link, capacity) in active{
+            let credit = self.incident_credit.entry(link).or_insert(0.0);
+            *credit = (*credit + capacity).min(1.0);
+        }
+    }
+
+    ///Gets every road arriving at one side of an intersection, by the intersection and arm it leaves from
+    fn links_arriving(&self, int_id : IntersectionId, side : u8) -> Vec<(IntersectionId, u8)>
+    {
+        let mut links: Vec<(IntersectionId, u8)> = self.road.road.iter().flat_map(|(from, arms)| {
+            arms.iter().enumerate()
+                .filter(|(_, node)| node.is_some_and(|node| node.dest_int_id == int_id && node.direction == side))
+                .map(|(from_arm, _)| (*from, from_arm as u8))
+                .collect::<Vec<(IntersectionId, u8)>>()
+        }).collect();
+        links.sort();
+        links
+    }
+
+    fn links_between(&self, from : IntersectionId, to : IntersectionId) -> Vec<(IntersectionId, u8)>
+    {
+        self.road.road.get(&from).map(|arms| {
+            arms.iter().enumerate()
+                .filter(|(_, node)| node.is_some_and(|node| node.dest_int_id == to))
+                .map(|(arm, _)| (from, arm as u8))
+                .collect()
+        }).unwrap_or_default()
+    }
+}
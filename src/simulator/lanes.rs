@@ -0,0 +1,178 @@
+use crate::simulator::{Simulator, Between, microscopic::{Idm, Kinematics}};
+use crate::traffic_logic::{car::{CarId, Direction}, intersection::IntersectionId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+///The parameters of the MOBIL lane changing model. A driver changes lanes if it gains more
+/// acceleration than the threshold, after taking off the acceleration the change costs the cars
+/// behind it weighted by its politeness, and the car it cuts in front of does not have to brake
+/// harder than is safe. A driver near the end of a road in a lane it cannot make its next turn from
+/// changes towards the lane it needs as soon as it is safe, whatever it gains.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Mobil
+{
+    ///How much the acceleration the cars behind lose or gain counts, 0 for a selfish driver
+    pub politeness : f64,
+    ///The acceleration a change has to gain to be worth it, in metres a second squared
+    pub threshold : f64,
+    ///The hardest the car behind in the new lane may have to brake, in metres a second squared
+    pub safe_deceleration : f64,
+    ///The distance before the stop bar from which drivers head for a lane they can make their next turn from, in metres
+    pub turn_distance : f64,
+    ///The ticks a driver stands at the stop bar in a lane it cannot turn from before it forces its way across the other lanes
+    pub patience : usize
+}
+
+impl Default for Mobil
+{
+    fn default() -> Self {
+        Mobil{politeness: 0.3, threshold: 0.2, safe_deceleration: 4.0, turn_distance: 150.0, patience: 10}
+    }
+}
+
+///A car moving into the next lane
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LaneChange
+{
+    pub tick : usize,
+    pub car_id : CarId,
+    pub link : (IntersectionId, IntersectionId),
+    pub from_lane : u8,
+    pub to_lane : u8,
+    ///The distance the car had left to the stop bar, in metres
+    pub remaining : f64,
+    ///The car changed lanes to make its next turn
+    pub mandatory : bool
+}
+
+///Checks if a turn can be made from a lane, left and U turns are made from the lane furthest from the
+/// kerb and right turns from the kerb lane, cars can drive straight on from any lane
+/// # Examples
+///
+/// ```rust
+/// use traffic_sim::simulator::lanes::lane_allows;
+/// use traffic_sim::traffic_logic::car::Direction;
+/// assert!(lane_allows(2, 3, Direction::Left));
+/// assert!(!lane_allows(1, 3, Direction::Right));
+/// assert!(lane_allows(0, 1, Direction::Left));
+/// ```
+pub fn lane_allows(lane : u8, lanes : u8, turn : Direction) -> bool
+{
+    match turn{
+        Direction::Left | Direction::UTurn => lane + 1 >= lanes,
+        Direction::Right => lane == 0,
+        Direction::Straight => true
+    }
+}
+
+impl Simulator
+{
+    ///Sets how drivers change lanes on roads with several lanes in the microscopic mode
+    pub fn set_lane_changing(&mut self, mobil : Mobil)
+    {
+        self.mobil = mobil;
+    }
+
+    pub fn lane_changing(&self) -> &Mobil
+    {
+        &self.mobil
+    }
+
+    ///Every lane change made in the microscopic mode, in the order they were made
+    pub fn lane_changes(&self) -> &[LaneChange]
+    {
+        &self.lane_changes
+    }
+
+    ///Picks the lane a car drives onto a road in, the lane nearest to it for a turn and the one with the most room for straight on
+    pub(crate) fn entry_lane(&self, link : (IntersectionId, IntersectionId), lanes : u8, turn : Direction) -> u8
+    {
+        match turn{
+            Direction::Right => 0,
+            Direction::Left | Direction::UTurn => lanes - 1,
+            Direction::Straight => (0..lanes).max_by(|a, b| self.lane_room(link, *a).total_cmp(&self.lane_room(link, *b)).then(b.cmp(a))).unwrap_or(0)
+        }
+    }
+
+    ///The distance from the start of a road to the back of the last car in a lane, in metres
+    fn lane_room(&self, link : (IntersectionId, IntersectionId), lane : u8) -> f64
+    {
+        self.kinematics.iter()
+            .filter(|(_, state)| state.link == link && state.lane == lane)
+            .filter_map(|(car_id, state)| Some(state.position - self.get_car(*car_id)?.class.params().length))
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    ///Finds the cars just ahead of and just behind a position in a lane
+    /// # Parameters
+    /// * `cars` : `&[CarId]` - The cars on the road, front first
+    fn neighbours(&self, cars : &[CarId], car_id : CarId, lane : u8, position : f64) -> (Option<CarId>, Option<CarId>)
+    {
+        let in_lane = || cars.iter().copied().filter(|other| *other != car_id && self.kinematics[other].lane == lane);
+        (in_lane().rfind(|other| self.kinematics[other].position > position),
+            in_lane().find(|other| self.kinematics[other].position <= position))
+    }
+
+    ///Lets every car on a road with several lanes change into a neighbouring lane by the MOBIL model,
+    /// front first. Each change is made before the next car decides, so two cars never take the same gap.
+    /// # Parameters
+    /// * `cars` : `&[CarId]` - The cars on the road, front first
+    pub(crate) fn change_lanes(&mut self, idm : &Idm, cars : &[CarId], between : &Between, full : &HashSet<(IntersectionId, IntersectionId)>)
+    {
+        let mobil = self.mobil;
+        for car_id in cars.iter().copied(){
+            let (Some(car), Some(state)) = (self.get_car(car_id), self.kinematics.get(&car_id).copied()) else { continue };
+            let length = car.class.params().length;
+            let turn = Direction::toward(between.from, car.next_exit(between.int_2_id, between.from));
+            let mandatory = !state.allows(turn) && state.remaining() < mobil.turn_distance;
+            //the lane a car that cannot turn from its lane heads for
+            let needed = if matches!(turn, Direction::Right) {0} else {state.lanes - 1};
+            let leading = |id : Option<CarId>| id.and_then(|id| Some((self.kinematics[&id], self.get_car(id)?.class)));
+            let (old_leader, old_follower) = self.neighbours(cars, car_id, state.lane, state.position);
+            let (old_leader, old_follower) = (leading(old_leader), leading(old_follower));
+            let current = idm.follow(car.class, &state, old_leader.as_ref().map(|(ahead, class)| (ahead, class.params().length)), old_leader.is_none() && self.must_stop(idm, car, &state, between, full));
+            let mut best = None;
+            for target in [state.lane.checked_sub(1), Some(state.lane + 1).filter(|lane| *lane < state.lanes)].into_iter().flatten(){
+                let moved = Kinematics{lane: target, ..state};
+                if mandatory && target.abs_diff(needed) >= state.lane.abs_diff(needed){
+                    continue;
+                }
+                //drivers do not leave a lane they can make their next turn from
+                if state.allows(turn) && !moved.allows(turn){
+                    continue;
+                }
+                let (leader, follower) = self.neighbours(cars, car_id, target, state.position);
+                let (leader, follower) = (leading(leader), leading(follower));
+                //there has to be room for the car in the new lane
+                if leader.as_ref().is_some_and(|(ahead, class)| ahead.position - class.params().length <= state.position)
+                    || follower.as_ref().is_some_and(|(behind, _)| state.position - length - behind.position < idm.min_gap){
+                    continue;
+                }
+                let follower_after = follower.as_ref().map(|(behind, class)| idm.follow(*class, behind, Some((&moved, length)), false));
+                if follower_after.is_some_and(|after| after < -mobil.safe_deceleration){
+                    continue;
+                }
+                if mandatory{
+                    best = Some((target, f64::INFINITY));
+                    break;
+                }
+                let gain = idm.follow(car.class, &moved, leader.as_ref().map(|(ahead, class)| (ahead, class.params().length)), leader.is_none() && self.must_stop(idm, car, &moved, between, full)) - current;
+                let new_follower = follower.as_ref().zip(follower_after).map_or(0.0, |((behind, class), after)| {
+                    after - idm.follow(*class, behind, leader.as_ref().map(|(ahead, class)| (ahead, class.params().length)), false)
+                });
+                let old_follower = old_follower.as_ref().map_or(0.0, |(behind, class)| {
+                    idm.follow(*class, behind, old_leader.as_ref().map(|(ahead, class)| (ahead, class.params().length)), false)
+                        - idm.follow(*class, behind, Some((&state, length)), false)
+                });
+                let incentive = gain + mobil.politeness * (new_follower + old_follower);
+                if incentive > mobil.threshold && best.is_none_or(|(_, best)| incentive > best){
+                    best = Some((target, incentive));
+                }
+            }
+            if let Some((target, _)) = best{
+                self.kinematics.insert(car_id, Kinematics{lane: target, ..state});
+                self.lane_changes.push(LaneChange{tick: self.timestep, car_id, link: state.link, from_lane: state.lane, to_lane: target, remaining: state.remaining(), mandatory});
+            }
+        }
+    }
+}
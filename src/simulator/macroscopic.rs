@@ -0,0 +1,382 @@
+use crate::simulator::{Simulator, demand::TimeProfile};
+use crate::traffic_logic::{car::Direction, intersection::{Intersection, IntersectionId, LightStatus, TrafficLight}, vehicle::{CAR_SPACE, REFERENCE_SPEED}};
+use std::collections::HashMap;
+
+///A triangular fundamental diagram, which sets the flow of a road from its density. Below the
+/// critical density traffic moves at the free speed, above it queues spread back up the road
+/// at the wave speed until the road is jammed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FundamentalDiagram
+{
+    ///The speed of traffic on an uncongested road, in metres a second
+    pub free_speed : f64,
+    ///The speed congestion spreads back up the road at, in metres a second, no faster than the free speed
+    pub wave_speed : f64,
+    ///The density of a standing queue, in vehicles a metre of lane
+    pub jam_density : f64
+}
+
+impl Default for FundamentalDiagram
+{
+    fn default() -> Self {
+        FundamentalDiagram{free_speed: REFERENCE_SPEED, wave_speed: 5.0, jam_density: 1.0 / CAR_SPACE}
+    }
+}
+
+impl FundamentalDiagram
+{
+    ///The most vehicles a lane can carry, in vehicles a second
+    pub fn capacity(&self) -> f64
+    {
+        self.free_speed * self.wave_speed * self.jam_density / (self.free_speed + self.wave_speed)
+    }
+
+    ///The flow of a lane at a density
+    /// # Parameters
+    /// * `density` : `f64` - The vehicles a metre of lane
+    /// # Returns
+    /// * `f64` - The vehicles a second passing a point of the lane
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::simulator::macroscopic::FundamentalDiagram;
+    /// let diagram = FundamentalDiagram{free_speed: 15.0, wave_speed: 5.0, jam_density: 0.12};
+    /// assert!((diagram.capacity() - 0.45).abs() < 1e-9);
+    /// assert!((diagram.flow(0.01) - 0.15).abs() < 1e-9);
+    /// assert_eq!(diagram.flow(0.12), 0.0);
+    /// ```
+    pub fn flow(&self, density : f64) -> f64
+    {
+        (self.free_speed * density).min(self.wave_speed * (self.jam_density - density)).max(0.0)
+    }
+}
+
+///The settings of the cell transmission model. Every road is cut into cells a vehicle at the free
+/// speed crosses in one tick, and each tick every cell sends on as much of its traffic as the
+/// next cell can take.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ctm
+{
+    ///The number of seconds a tick lasts
+    pub tick_seconds : f64,
+    pub diagram : FundamentalDiagram,
+    ///The ticks between the samples of the density and flow of every road
+    pub sample_interval : usize
+}
+
+impl Default for Ctm
+{
+    fn default() -> Self {
+        Ctm{tick_seconds: 1.0, diagram: FundamentalDiagram::default(), sample_interval: 60}
+    }
+}
+
+impl Ctm
+{
+    ///The vehicles a cell of a road with a number of lanes can send on in a tick
+    fn sending(&self, lanes : u8, vehicles : f64) -> f64
+    {
+        vehicles.min(self.diagram.capacity() * f64::from(lanes) * self.tick_seconds)
+    }
+
+    ///The vehicles a cell of a road with a number of lanes can take in a tick
+    fn receiving(&self, lanes : u8, vehicles : f64) -> f64
+    {
+        let jam = self.diagram.jam_density * self.diagram.free_speed * self.tick_seconds * f64::from(lanes);
+        let spare = self.diagram.wave_speed / self.diagram.free_speed * (jam - vehicles);
+        spare.min(self.diagram.capacity() * f64::from(lanes) * self.tick_seconds).max(0.0)
+    }
+}
+
+///The state of one road averaged over a sample interval
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LinkSample
+{
+    ///The tick the sample interval ended on
+    pub tick : usize,
+    ///The intersection the road starts at and the arm it leaves by
+    pub link : (IntersectionId, u8),
+    ///The mean density of the road, in vehicles a kilometre of lane
+    pub density : f64,
+    ///The mean flow leaving the road, in vehicles an hour
+    pub flow : f64,
+    ///The vehicles on the road at the end of the interval
+    pub vehicles : f64
+}
+
+///A road cut into cells
+#[derive(Clone, Debug)]
+struct CellLink
+{
+    from : IntersectionId,
+    arm : u8,
+    to : IntersectionId,
+    ///The side of `to` the road arrives at
+    side : u8,
+    length : f64,
+    lanes : u8,
+    ///The vehicles in every cell, from the start of the road
+    cells : Vec<f64>,
+    ///Where the traffic leaving the road goes and the share going there, `None` for traffic ending its trip
+    splits : Vec<(Option<usize>, f64)>,
+    ///The traffic that has not got onto the road from its origin yet
+    entry_queue : f64,
+    density_sum : f64,
+    outflow : f64
+}
+
+///A macroscopic simulation of the network of a `Simulator` by the cell transmission model. Traffic
+/// is a fluid of vehicles that leaves each road in the shares its demand routes take, and may only
+/// turn when the signals of the intersection let it.
+pub struct CellTransmission
+{
+    ctm : Ctm,
+    tick : usize,
+    intersections : Vec<Intersection>,
+    links : Vec<CellLink>,
+    index : HashMap<(IntersectionId, u8), usize>,
+    ///The trips an hour starting on each road, and the time profile scaling them
+    sources : Vec<(usize, f64)>,
+    profile : Option<TimeProfile>,
+    series : Vec<LinkSample>,
+    completed : f64,
+    travel_time : f64
+}
+
+impl Simulator
+{
+    ///Builds a cell transmission model of the network, with the signal plans of its intersections and the trips
+    /// of its demand. Every pair's trips take the shortest route open now, and the share of the traffic on a road
+    /// making each turn comes from the routes using it.
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::simulator::{Simulator, demand::{Demand, OdPair}, macroscopic::Ctm};
+    /// use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan};
+    /// let mut sim = Simulator::new();
+    /// sim.add_intersections(3);
+    /// sim.add_link(IntersectionId(1), 1, IntersectionId(2), 3, 20, 1);
+    /// sim.add_link(IntersectionId(2), 1, IntersectionId(3), 3, 20, 1);
+    /// sim.set_signal_plan(IntersectionId(2), SignalPlan::four_phase(20, 5));
+    /// sim.set_demand(Demand::new(vec![OdPair{origin: IntersectionId(1), destination: IntersectionId(3), trips_per_hour: 600.0}]));
+    /// let mut ctm = sim.cell_transmission(Ctm::default());
+    /// ctm.run(600);
+    /// assert!(ctm.completed() > 0.0);
+    /// assert_eq!(ctm.series().len(), 2 * 10);
+    /// ```
+    pub fn cell_transmission(&self, ctm : Ctm) -> CellTransmission
+    {
+        let cell_length = ctm.diagram.free_speed * ctm.tick_seconds;
+        let mut links = Vec::new();
+        let mut index = HashMap::new();
+        let mut ids: Vec<&IntersectionId> = self.road.road.keys().collect();
+        ids.sort();
+        for from in ids{
+            for (arm, node) in self.road.road[from].iter().enumerate(){
+                let Some(node) = node else { continue };
+                let length = f64::from(node.dist_from_source) * REFERENCE_SPEED * ctm.tick_seconds;
+                let cells = ((length / cell_length).round() as usize).max(1);
+                index.insert((*from, arm as u8), links.len());
+                links.push(CellLink{from: *from, arm: arm as u8, to: node.dest_int_id, side: node.direction, length, lanes: node.lanes.max(1),
+                    cells: vec![0.0; cells], splits: Vec::new(), entry_queue: 0.0, density_sum: 0.0, outflow: 0.0});
+            }
+        }
+        let mut sources = Vec::new();
+        let mut turns: HashMap<(usize, Option<usize>), f64> = HashMap::new();
+        let closed = self.closed_links();
+        for pair in self.demand.iter().flat_map(|demand| demand.pairs.iter()).filter(|pair| pair.trips_per_hour > 0.0){
+            let Some(route) = self.road.shortest_path(pair.origin, pair.destination, &closed) else { continue };
+            let route: Vec<usize> = route.links.iter().filter_map(|link| index.get(link).copied()).collect();
+            let Some(first) = route.first() else { continue };
+            sources.push((*first, pair.trips_per_hour));
+            for (step, link) in route.iter().enumerate(){
+                *turns.entry((*link, route.get(step + 1).copied())).or_default() += pair.trips_per_hour;
+            }
+        }
+        for ((link, next), trips) in turns{
+            links[link].splits.push((next, trips));
+        }
+        for link in links.iter_mut(){
+            let total: f64 = link.splits.iter().map(|(_, trips)| trips).sum();
+            if total <= 0.0{
+                link.splits.clear();
+                continue;
+            }
+            link.splits.sort_by_key(|(next, _)| *next);
+            for (_, share) in link.splits.iter_mut(){
+                *share /= total;
+            }
+        }
+        let intersections = self.intersections.iter().map(|intersection| {
+            let mut copy = Intersection::new(intersection.id);
+            copy.signal_plan = intersection.signal_plan.clone();
+            copy
+        }).collect();
+        CellTransmission{ctm, tick: 0, intersections, links, index, sources, profile: self.demand.as_ref().map(|demand| demand.profile.clone()),
+            series: Vec::new(), completed: 0.0, travel_time: 0.0}
+    }
+}
+
+impl CellTransmission
+{
+    pub fn settings(&self) -> &Ctm
+    {
+        &self.ctm
+    }
+
+    pub fn timestep(&self) -> usize
+    {
+        self.tick
+    }
+
+    ///Runs the model for a number of ticks
+    pub fn run(&mut self, ticks : usize)
+    {
+        for _ in 0..ticks{
+            self.step();
+        }
+    }
+
+    ///The density and flow of every road, one sample for each road every sample interval
+    pub fn series(&self) -> &[LinkSample]
+    {
+        &self.series
+    }
+
+    ///The density of a road now, in vehicles a kilometre of lane
+    pub fn density(&self, from : IntersectionId, arm : u8) -> Option<f64>
+    {
+        let link = &self.links[*self.index.get(&(from, arm))?];
+        Some(link.cells.iter().sum::<f64>() / (link.length / 1000.0 * f64::from(link.lanes)))
+    }
+
+    ///The vehicles in every cell of a road, from its start
+    pub fn cells(&self, from : IntersectionId, arm : u8) -> Option<&[f64]>
+    {
+        Some(&self.links[*self.index.get(&(from, arm))?].cells)
+    }
+
+    ///The vehicles on the roads of the network
+    pub fn vehicles(&self) -> f64
+    {
+        self.links.iter().flat_map(|link| link.cells.iter()).sum()
+    }
+
+    ///The vehicles waiting at their origins to get onto the network
+    pub fn waiting(&self) -> f64
+    {
+        self.links.iter().map(|link| link.entry_queue).sum()
+    }
+
+    ///The vehicles that have reached their destination
+    pub fn completed(&self) -> f64
+    {
+        self.completed
+    }
+
+    ///The seconds vehicles have spent on the roads of the network, summed over every vehicle
+    pub fn travel_time(&self) -> f64
+    {
+        self.travel_time
+    }
+
+    ///Checks if the lights let traffic arriving at a side of an intersection leave by an arm
+    fn may_turn(lights : &[TrafficLight;4], side : u8, exit : u8) -> bool
+    {
+        let light = lights[usize::from((side + 2) % 4)];
+        let status = match Direction::toward(side, exit){
+            Direction::Straight | Direction::Right => light.main_status,
+            Direction::Left | Direction::UTurn => light.left_turn_status
+        };
+        matches!(status, LightStatus::Green)
+    }
+
+    ///Moves the traffic on by one tick. All flows are found from the state at the start of the tick before any is applied.
+    ///
+    /// The traffic leaving a road diverges by the FIFO rule of the cell transmission model: the road sends
+    /// `min(S, R_j / β_j)` over every turn `j` it has traffic for, where `S` is what its last cell can send,
+    /// `β_j` the share of its traffic making the turn and `R_j` what the road the turn goes into can take, none
+    /// while the light for the turn is not green. Vehicles leave in the order they arrived, so one blocked turn
+    /// holds up the traffic behind it for every other turn. Where several roads merge into one that cannot take
+    /// all they send, each is cut back in proportion to what it sends, and cut back as a whole.
+    fn step(&mut self)
+    {
+        let tick = self.tick;
+        let lights: Vec<[TrafficLight;4]> = self.intersections.iter().map(|intersection| intersection.next_lights(tick)).collect();
+        let receiving: Vec<f64> = self.links.iter().map(|link| self.ctm.receiving(link.lanes, link.cells[0])).collect();
+        //what every road sends over all its turns before merges are cut back, nothing from roads no route uses
+        let sending: Vec<f64> = self.links.iter().map(|link| {
+            if link.splits.is_empty(){
+                return 0.0;
+            }
+            let mut sending = self.ctm.sending(link.lanes, *link.cells.last().unwrap_or(&0.0));
+            for (next, share) in link.splits.iter(){
+                let Some(next) = next else { continue };
+                let green = lights.get(link.to.index()).is_none_or(|lights| Self::may_turn(lights, link.side, self.links[*next].arm));
+                let room = if green {receiving[*next]} else {0.0};
+                sending = sending.min(room / share);
+            }
+            sending
+        }).collect();
+        let mut wanted = vec![0.0; self.links.len()];
+        for (from, link) in self.links.iter().enumerate(){
+            for (next, share) in link.splits.iter(){
+                if let Some(next) = next{
+                    wanted[*next] += sending[from] * share;
+                }
+            }
+        }
+        let mut inflow = vec![0.0; self.links.len()];
+        let mut outflow = vec![0.0; self.links.len()];
+        for (from, link) in self.links.iter().enumerate(){
+            let cut = link.splits.iter()
+                .filter_map(|(next, _)| next.filter(|next| wanted[*next] > receiving[*next]))
+                .map(|next| receiving[next] / wanted[next])
+                .fold(1.0, f64::min);
+            let sent = sending[from] * cut;
+            for (next, share) in link.splits.iter(){
+                match next{
+                    Some(next) => inflow[*next] += sent * share,
+                    None => self.completed += sent * share
+                }
+            }
+            outflow[from] = sent;
+        }
+        let factor = self.profile.as_ref().map_or(1.0, |profile| profile.factor(tick));
+        for (first, trips) in self.sources.iter(){
+            self.links[*first].entry_queue += trips * self.ctm.tick_seconds / 3600.0 * factor;
+        }
+        let ctm = self.ctm;
+        for (at, link) in self.links.iter_mut().enumerate(){
+            let lanes = f64::from(link.lanes);
+            let entering = link.entry_queue.min((receiving[at] - inflow[at]).max(0.0));
+            link.entry_queue -= entering;
+            //what each cell passes to the next, found before any cell changes
+            let moving: Vec<f64> = link.cells.windows(2)
+                .map(|pair| ctm.sending(link.lanes, pair[0]).min(ctm.receiving(link.lanes, pair[1])))
+                .collect();
+            for (cell, flow) in moving.iter().enumerate(){
+                link.cells[cell] -= flow;
+                link.cells[cell + 1] += flow;
+            }
+            link.cells[0] += inflow[at] + entering;
+            if let Some(last) = link.cells.last_mut(){
+                *last -= outflow[at];
+            }
+            link.outflow += outflow[at];
+            let vehicles: f64 = link.cells.iter().sum();
+            link.density_sum += vehicles / (link.length / 1000.0 * lanes);
+            self.travel_time += vehicles * self.ctm.tick_seconds;
+        }
+        self.tick += 1;
+        if self.ctm.sample_interval > 0 && self.tick.is_multiple_of(self.ctm.sample_interval){
+            let interval = self.ctm.sample_interval as f64;
+            for link in self.links.iter_mut(){
+                self.series.push(LinkSample{tick: self.tick, link: (link.from, link.arm), density: link.density_sum / interval,
+                    flow: link.outflow * 3600.0 / (interval * self.ctm.tick_seconds), vehicles: link.cells.iter().sum()});
+                link.density_sum = 0.0;
+                link.outflow = 0.0;
+            }
+        }
+    }
+}
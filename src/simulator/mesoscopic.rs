@@ -0,0 +1,510 @@
+use crate::simulator::{Simulator, Current, Departure, incidents::{Closure, Incident}, demand::{Demand, pick_weighted, poisson}, trips::TripRecord};
+use crate::traffic_logic::{car::{CarId, Direction}, driver::DriverProfile, intersection::{Intersection, IntersectionId, LightStatus, TrafficLight}, road::Road, vehicle::{VehicleClass, CAR_SPACE, REFERENCE_SPEED}};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+///How fast vehicles drive a road from how crowded it is, the free speed times
+/// `(1 - (density / jam_density)^alpha)^beta`, which is Greenshields' model with both exponents 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpeedDensity
+{
+    ///The density of a standing queue, in passenger car spaces a metre of lane
+    pub jam_density : f64,
+    pub alpha : f64,
+    pub beta : f64,
+    ///The smallest share of the free speed vehicles drive at, however crowded the road
+    pub min_factor : f64
+}
+
+impl Default for SpeedDensity
+{
+    fn default() -> Self {
+        SpeedDensity{jam_density: 1.0 / CAR_SPACE, alpha: 1.0, beta: 1.0, min_factor: 0.05}
+    }
+}
+
+impl SpeedDensity
+{
+    ///The share of the free speed vehicles drive at
+    /// # Parameters
+    /// * `density` : `f64` - The passenger car spaces a metre of lane taken up on the road
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::simulator::mesoscopic::SpeedDensity;
+    /// let model = SpeedDensity{jam_density: 0.1, alpha: 1.0, beta: 1.0, min_factor: 0.1};
+    /// assert_eq!(model.speed_factor(0.0), 1.0);
+    /// assert!((model.speed_factor(0.05) - 0.5).abs() < 1e-9);
+    /// assert_eq!(model.speed_factor(0.2), 0.1);
+    /// ```
+    pub fn speed_factor(&self, density : f64) -> f64
+    {
+        let ratio = (density / self.jam_density).clamp(0.0, 1.0);
+        (1.0 - ratio.powf(self.alpha)).powf(self.beta).max(self.min_factor)
+    }
+}
+
+///The settings of the mesoscopic model. Vehicles drive every road in a time set by how crowded it
+/// is when they join it, then wait in a queue at its end until their light is green, the road they
+/// turn into has room and the end of the road has the capacity to let them out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Meso
+{
+    ///The number of seconds a tick lasts
+    pub tick_seconds : f64,
+    pub speed_density : SpeedDensity,
+    ///The passenger cars a lane lets out of the end of a road while it has a green light, in vehicles an hour
+    pub exit_capacity : f64
+}
+
+impl Default for Meso
+{
+    fn default() -> Self {
+        Meso{tick_seconds: 1.0, speed_density: SpeedDensity::default(), exit_capacity: 1800.0}
+    }
+}
+
+///What the mesoscopic model leaves out of the simulator it was built from. It only drives trips to a
+/// destination, so cars that turn at random, buses and the turning proportions those cars use are not in it,
+/// and every trip keeps the route it started on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Unmodelled
+{
+    ///The cars without a destination, both on the network and scheduled
+    pub random_cars : usize,
+    pub transit_lines : usize,
+    ///The sides of intersections with turning proportions
+    pub turning_proportions : usize,
+    ///The trips on the network that would pick their route again from how busy the roads are, and one
+    /// more if a share of the trips started from now on would
+    pub rerouting : usize
+}
+
+///A vehicle in the mesoscopic model
+#[derive(Clone, Debug)]
+struct Agent
+{
+    serial : u64,
+    car_id : CarId,
+    class : VehicleClass,
+    driver : Option<DriverProfile>,
+    origin : IntersectionId,
+    destination : IntersectionId,
+    ///The roads still to drive after the one the vehicle is on
+    route : VecDeque<(IntersectionId, u8)>,
+    depart_tick : usize,
+    free_flow_time : u64,
+    wait_time : usize,
+    path : Vec<(IntersectionId, u8, usize)>,
+    ///The tick the vehicle reaches the end of its road or, once there, the tick it got there. Vehicles on a
+    /// road never reach its end before the vehicle ahead of them.
+    exit_tick : usize
+}
+
+///The vehicles on a road or waiting to get into the network at an origin, in the order they got there
+#[derive(Clone, Debug, Default)]
+struct ExitQueue
+{
+    agents : VecDeque<Agent>,
+    ///The passenger cars the end of the road may still let out this tick
+    credit : f64
+}
+
+///A road of the mesoscopic model
+#[derive(Clone, Debug)]
+struct QueueLink
+{
+    from : IntersectionId,
+    to : IntersectionId,
+    ///The side of `to` the road arrives at
+    side : u8,
+    distance : u32,
+    length : f64,
+    lanes : u8,
+    queue : ExitQueue
+}
+
+impl QueueLink
+{
+    ///The passenger car spaces taken up on the road
+    fn occupancy(&self) -> f64
+    {
+        self.queue.agents.iter().map(|agent| agent.class.storage_spaces()).sum()
+    }
+
+    ///The passenger car spaces a metre of lane taken up by the vehicles still driving the road, the vehicles
+    /// queueing at its end only take up storage
+    fn density(&self, tick : usize) -> f64
+    {
+        let driving: f64 = self.queue.agents.iter().filter(|agent| agent.exit_tick > tick).map(|agent| agent.class.storage_spaces()).sum();
+        driving / (self.length * f64::from(self.lanes))
+    }
+}
+
+///Where the vehicle at the front of a queue is trying to go
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source
+{
+    Link(usize),
+    Origin(IntersectionId)
+}
+
+///A mesoscopic simulation of the network and demand of a `Simulator`. Vehicles are single agents that
+/// move from road to road with no position along the road, so a scenario can be run in this mode and its
+/// trips compared with those of the `Simulator`.
+pub struct Mesoscopic
+{
+    meso : Meso,
+    tick : usize,
+    road : Road,
+    intersections : Vec<Intersection>,
+    closures : Vec<Closure>,
+    incidents : Vec<Incident>,
+    demand : Option<Demand>,
+    ///The share of drivers of each profile the vehicles starting a trip are given
+    driver_mix : Vec<(DriverProfile, f64)>,
+    ///The trips to a destination scheduled in the simulator, in the order they start
+    scheduled : VecDeque<Departure>,
+    unmodelled : Unmodelled,
+    links : Vec<QueueLink>,
+    index : HashMap<(IntersectionId, u8), usize>,
+    ///The trips waiting to start at each origin
+    pending : BTreeMap<IntersectionId, VecDeque<(IntersectionId, VehicleClass)>>,
+    ///The vehicles that have started their trip and wait at the lights of their origin
+    origins : BTreeMap<IntersectionId, ExitQueue>,
+    completed_trips : Vec<TripRecord>,
+    next_car_id : u32,
+    next_trip : u64
+}
+
+impl Simulator
+{
+    ///Builds a mesoscopic model of the network, with the signal plans, road closures, incidents, driver mix and demand
+    /// of the simulator. The cars on a trip and the trips scheduled to start later are carried over with their IDs and
+    /// routes, cars waiting at an intersection join the end of the road they wait at and the trips in the entry queues
+    /// still wait at their origin. What the model leaves out is counted in `Mesoscopic::unmodelled`.
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::simulator::{Simulator, demand::{Demand, OdPair}, mesoscopic::Meso};
+    /// use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan};
+    /// let mut sim = Simulator::new();
+    /// sim.add_intersections(3);
+    /// sim.add_link(IntersectionId(1), 1, IntersectionId(2), 3, 20, 1);
+    /// sim.add_link(IntersectionId(2), 1, IntersectionId(3), 3, 20, 1);
+    /// sim.set_signal_plan(IntersectionId(1), SignalPlan::uncontrolled());
+    /// sim.set_signal_plan(IntersectionId(2), SignalPlan::four_phase(20, 5));
+    /// sim.set_demand(Demand::new(vec![OdPair{origin: IntersectionId(1), destination: IntersectionId(3), trips_per_hour: 600.0}]));
+    /// let mut meso = sim.mesoscopic(Meso::default());
+    /// meso.run(600);
+    /// assert!(!meso.completed_trips().is_empty());
+    /// assert!(meso.completed_trips().iter().all(|trip| trip.travel_time() >= 40));
+    /// ```
+    pub fn mesoscopic(&self, meso : Meso) -> Mesoscopic
+    {
+        let mut links = Vec::new();
+        let mut index = HashMap::new();
+        let mut ids: Vec<&IntersectionId> = self.road.road.keys().collect();
+        ids.sort();
+        for from in ids{
+            for (arm, node) in self.road.road[from].iter().enumerate(){
+                let Some(node) = node else { continue };
+                index.insert((*from, arm as u8), links.len());
+                links.push(QueueLink{from: *from, to: node.dest_int_id, side: node.direction, distance: node.dist_from_source,
+                    length: f64::from(node.dist_from_source) * REFERENCE_SPEED * meso.tick_seconds, lanes: node.lanes.max(1), queue: ExitQueue::default()});
+            }
+        }
+        let intersections = self.intersections.iter().map(|intersection| {
+            let mut copy = Intersection::new(intersection.id);
+            copy.signal_plan = intersection.signal_plan.clone();
+            copy
+        }).collect();
+        let unmodelled = Unmodelled{
+            random_cars: self.cars.iter().filter(|car| car.trip.is_none() && !self.bus_runs.contains_key(&car.id)).count()
+                + self.departures.iter().filter(|departure| departure.destination.is_none()).count(),
+            transit_lines: self.transit_lines.len(),
+            turning_proportions: self.intersections.iter().flat_map(|intersection| intersection.turning).flatten().count(),
+            rerouting: self.cars.iter().filter(|car| car.trip.as_ref().is_some_and(|trip| trip.reroutes)).count() + usize::from(self.reroute_share > 0.0)
+        };
+        let pending = self.entry_queues.iter()
+            .map(|(origin, queue)| (*origin, queue.iter().map(|entry| (entry.destination, entry.class)).collect()))
+            .collect();
+        let mut model = Mesoscopic{meso, tick: self.timestep, road: self.road.clone(), intersections, closures: self.closures.clone(), incidents: self.incidents.clone(),
+            demand: self.demand.clone(), driver_mix: self.driver_mix.clone(),
+            scheduled: self.departures.iter().filter(|departure| departure.destination.is_some()).cloned().collect(), unmodelled,
+            links, index, pending, origins: BTreeMap::new(), completed_trips: Vec::new(), next_car_id: self.next_car_id, next_trip: self.next_trip};
+        for car in self.cars.iter().filter(|car| !self.bus_runs.contains_key(&car.id)){
+            let (Some(trip), Some(pos)) = (&car.trip, self.car_positions.get(&car.id)) else { continue };
+            let mut agent = Agent{serial: trip.serial, car_id: car.id, class: car.class, driver: car.driver, origin: trip.origin, destination: trip.destination,
+                route: trip.route.links.clone(), depart_tick: trip.depart_tick, free_flow_time: trip.free_flow_time, wait_time: car.wait_time,
+                path: trip.path.clone(), exit_tick: self.timestep};
+            match (pos.current_intersection, pos.in_between){
+                (Some(at), _) => model.place(agent, at),
+                (None, Some(between)) => {
+                    let Some(link) = self.link_arm(&between).and_then(|arm| model.index.get(&(between.int_1_id, arm)).copied()) else { continue };
+                    agent.exit_tick += between.distance_to_target as usize;
+                    model.links[link].queue.agents.push_back(agent);
+                },
+                (None, None) => {}
+            }
+        }
+        for link in model.links.iter_mut(){
+            link.queue.agents.make_contiguous().sort_by_key(|agent| agent.exit_tick);
+        }
+        model
+    }
+}
+
+impl Mesoscopic
+{
+    pub fn settings(&self) -> &Meso
+    {
+        &self.meso
+    }
+
+    pub fn timestep(&self) -> usize
+    {
+        self.tick
+    }
+
+    ///Runs the model for a number of ticks
+    pub fn run(&mut self, ticks : usize)
+    {
+        for _ in 0..ticks{
+            self.step();
+        }
+    }
+
+    ///Every trip that has reached its destination, in the order they got there. Vehicles carried over from the simulator
+    /// keep their ID and the model numbers new vehicles on from the last ID the simulator gave.
+    pub fn completed_trips(&self) -> &[TripRecord]
+    {
+        &self.completed_trips
+    }
+
+    ///The vehicles on the roads of the network and waiting at the lights of their origin
+    pub fn vehicles(&self) -> usize
+    {
+        self.links.iter().map(|link| link.queue.agents.len()).sum::<usize>()
+            + self.origins.values().map(|queue| queue.agents.len()).sum::<usize>()
+    }
+
+    ///What the model leaves out of the simulator it was built from
+    pub fn unmodelled(&self) -> Unmodelled
+    {
+        self.unmodelled
+    }
+
+    ///The trips waiting to start because their origin has not let them in yet
+    pub fn waiting(&self) -> usize
+    {
+        self.pending.values().map(VecDeque::len).sum()
+    }
+
+    ///The vehicles on a road
+    pub fn link_vehicles(&self, from : IntersectionId, arm : u8) -> Option<usize>
+    {
+        Some(self.links[*self.index.get(&(from, arm))?].queue.agents.len())
+    }
+
+    ///The ticks a vehicle of a class joining a road now would take to reach its end, without waiting there
+    pub fn link_travel_time(&self, from : IntersectionId, arm : u8, class : VehicleClass) -> Option<usize>
+    {
+        let link = &self.links[*self.index.get(&(from, arm))?];
+        Some(self.travel_ticks(link, class, link.density(self.tick)))
+    }
+
+    fn travel_ticks(&self, link : &QueueLink, class : VehicleClass, density : f64) -> usize
+    {
+        let free = f64::from(class.travel_ticks(link.distance, false));
+        ((free / self.meso.speed_density.speed_factor(density)).ceil() as usize).max(1)
+    }
+
+    ///Puts a vehicle starting or waiting at an intersection at the end of the road that arrives at its side, ahead of
+    /// the vehicles still driving it, or in the queue of its origin if no road arrives there
+    fn place(&mut self, agent : Agent, at : Current)
+    {
+        match self.links.iter_mut().find(|link| link.to == at.int_id && link.side == at.direction){
+            Some(link) => {
+                let place = link.queue.agents.partition_point(|ahead| ahead.exit_tick <= agent.exit_tick);
+                link.queue.agents.insert(place, agent);
+            },
+            None => self.origins.entry(at.int_id).or_default().agents.push_back(agent)
+        }
+    }
+
+    fn closed_links(&self) -> HashSet<(IntersectionId, u8)>
+    {
+        self.closures.iter()
+            .filter(|closure| closure.is_active(self.tick))
+            .map(|closure| (closure.int_id, closure.arm))
+            .collect()
+    }
+
+    ///Checks if a road has room for one more vehicle
+    fn has_room(&self, link : usize, class : VehicleClass) -> bool
+    {
+        let link = &self.links[link];
+        let storage = self.meso.speed_density.jam_density * link.length * f64::from(link.lanes);
+        link.occupancy() + class.storage_spaces() <= storage.max(class.storage_spaces())
+    }
+
+    ///Finds the vehicle that goes next from a queue and the road it drives onto. Every turn has its own queue, as if
+    /// each had its own lane at the end of the road, and the first vehicle in the queue of a turn may go if its
+    /// light is green, the road it turns into is open and that road has room. No vehicle passes one that has not
+    /// reached the end of the road yet.
+    /// # Returns
+    /// `Option<(usize, usize)>` : The place of the vehicle in the queue and the index of its next road
+    fn ready(&self, source : Source, lights : &[[TrafficLight;4]], closed : &HashSet<(IntersectionId, u8)>) -> Option<(usize, usize)>
+    {
+        let (queue, int_id) = match source{
+            Source::Link(link) => (&self.links[link].queue, self.links[link].to),
+            Source::Origin(origin) => (&self.origins[&origin], origin)
+        };
+        let mut seen = [false; 4];
+        for (place, agent) in queue.agents.iter().enumerate(){
+            let Some(next) = agent.route.front().copied() else { continue };
+            if agent.exit_tick > self.tick{
+                break;
+            }
+            if seen[usize::from(next.1)]{
+                continue;
+            }
+            seen[usize::from(next.1)] = true;
+            //vehicles starting their trip drive straight through their origin
+            let side = match source{
+                Source::Link(link) => self.links[link].side,
+                Source::Origin(_) => (next.1 + 2) % 4
+            };
+            let light = lights.get(int_id.index()).map_or(TrafficLight{main_status: LightStatus::Green, left_turn_status: LightStatus::Green}, |lights| lights[usize::from((side + 2) % 4)]);
+            let status = match Direction::toward(side, next.1){
+                Direction::Straight | Direction::Right => light.main_status,
+                Direction::Left | Direction::UTurn => light.left_turn_status
+            };
+            let Some(link) = self.index.get(&next).copied() else { continue };
+            if matches!(status, LightStatus::Green) && !closed.contains(&next) && self.has_room(link, agent.class){
+                return Some((place, link));
+            }
+        }
+        None
+    }
+
+    ///Lets vehicles out of a queue while they can go and the end of the road has the capacity, which all its turns
+    /// share. The capacity builds up every tick but only up to what the next vehicle needs, so a queue does not save
+    /// up its capacity over a red light and let a burst of vehicles out when it turns green.
+    fn discharge(&mut self, source : Source, lights : &[[TrafficLight;4]], closed : &HashSet<(IntersectionId, u8)>)
+    {
+        let lanes = match source{
+            Source::Link(link) => self.links[link].lanes,
+            Source::Origin(_) => 1
+        };
+        let mut per_tick = self.meso.exit_capacity * self.meso.tick_seconds / 3600.0 * f64::from(lanes);
+        //roads with an incident only let part of a car out each tick
+        if let Source::Link(link) = source{
+            let link = &self.links[link];
+            for incident in self.incidents.iter().filter(|incident| incident.is_active(self.tick) && (incident.int_1_id, incident.int_2_id) == (link.from, link.to)){
+                per_tick = per_tick.min(incident.capacity);
+            }
+        }
+        let mut filled = false;
+        loop {
+            let ready = self.ready(source, lights, closed);
+            let queue = self.queue_mut(source);
+            let pce = ready.and_then(|(place, _)| queue.agents.get(place)).map_or(1.0, |agent| agent.class.params().pce);
+            if !filled{
+                queue.credit = (queue.credit + per_tick).min(per_tick.max(pce));
+                filled = true;
+            }
+            let Some((place, next)) = ready else { return };
+            if queue.credit < pce{
+                return;
+            }
+            queue.credit -= pce;
+            let mut agent = queue.agents.remove(place).expect("The queue has a vehicle ready");
+            let (from, arm) = agent.route.pop_front().expect("The vehicle has a next road");
+            agent.wait_time += self.tick - agent.exit_tick;
+            agent.path.push((from, arm, self.tick));
+            let link = &self.links[next];
+            let behind = link.queue.agents.back().map_or(0, |ahead| ahead.exit_tick);
+            agent.exit_tick = (self.tick + self.travel_ticks(link, agent.class, link.density(self.tick) + agent.class.storage_spaces() / (link.length * f64::from(link.lanes)))).max(behind);
+            self.links[next].queue.agents.push_back(agent);
+        }
+    }
+
+    fn queue_mut(&mut self, source : Source) -> &mut ExitQueue
+    {
+        match source{
+            Source::Link(link) => &mut self.links[link].queue,
+            Source::Origin(origin) => self.origins.get_mut(&origin).expect("Origin queues are only looked at while they exist")
+        }
+    }
+
+    ///Moves the model on by one tick
+    fn step(&mut self)
+    {
+        let tick = self.tick;
+        let closed = self.closed_links();
+        //scheduled trips start where they were scheduled, as in the simulator, without waiting for an entry queue
+        while self.scheduled.front().is_some_and(|departure| departure.tick <= tick){
+            let Departure{at, destination, route, ..} = self.scheduled.pop_front().expect("There is a departure due");
+            let Some(destination) = destination else { continue };
+            let route = match route.filter(|route| route.links.front().is_some_and(|(from, _)| *from == at.int_id)){
+                Some(route) => route,
+                None => match self.road.shortest_path(at.int_id, destination, &closed){
+                    Some(route) => route,
+                    None => continue
+                }
+            };
+            if route.links.is_empty(){
+                continue;
+            }
+            let car_id = CarId(self.next_car_id);
+            self.next_car_id += 1;
+            let serial = self.next_trip;
+            self.next_trip += 1;
+            self.place(Agent{serial, car_id, class: VehicleClass::PassengerCar, driver: pick_weighted(&self.driver_mix), origin: at.int_id, destination, route: route.links, depart_tick: tick,
+                free_flow_time: route.cost, wait_time: 0, path: Vec::new(), exit_tick: tick}, at);
+        }
+        if let Some(demand) = &self.demand{
+            for pair in demand.pairs.iter(){
+                for _ in 0..poisson(demand.rate(pair, tick)){
+                    self.pending.entry(pair.origin).or_default().push_back((pair.destination, demand.sample_class()));
+                }
+            }
+        }
+        //vehicles on the last road of their trip leave as soon as they reach its end, past any queue
+        for link in self.links.iter_mut(){
+            let (arrived, driving): (VecDeque<Agent>, VecDeque<Agent>) = link.queue.agents.drain(..)
+                .partition(|agent| agent.route.is_empty() && agent.exit_tick <= tick);
+            link.queue.agents = driving;
+            for agent in arrived{
+                self.completed_trips.push(TripRecord{serial: agent.serial, car_id: agent.car_id, class: agent.class, driver: agent.driver, origin: agent.origin, destination: agent.destination,
+                    depart_tick: agent.depart_tick, arrive_tick: tick, wait_time: agent.wait_time, free_flow_time: agent.free_flow_time,
+                    reroutes: false, route_changes: 0, path: agent.path});
+            }
+        }
+        let lights: Vec<[TrafficLight;4]> = self.intersections.iter().map(|intersection| intersection.next_lights(tick)).collect();
+        for link in 0..self.links.len(){
+            self.discharge(Source::Link(link), &lights, &closed);
+        }
+        //one trip a tick starts at every origin, as with the entry queues of the simulator
+        let origins: Vec<IntersectionId> = self.pending.keys().copied().collect();
+        for origin in origins{
+            let Some((destination, class)) = self.pending.get_mut(&origin).and_then(VecDeque::pop_front) else { continue };
+            if let Some(route) = self.road.shortest_path(origin, destination, &closed){
+                let car_id = CarId(self.next_car_id);
+                self.next_car_id += 1;
+                let serial = self.next_trip;
+                self.next_trip += 1;
+                self.origins.entry(origin).or_default().agents.push_back(Agent{serial, car_id, class, driver: pick_weighted(&self.driver_mix), origin, destination, route: route.links, depart_tick: tick,
+                    free_flow_time: route.cost, wait_time: 0, path: Vec::new(), exit_tick: tick});
+            }
+        }
+        self.pending.retain(|_, queue| !queue.is_empty());
+        let origins: Vec<IntersectionId> = self.origins.keys().copied().collect();
+        for origin in origins{
+            self.discharge(Source::Origin(origin), &lights, &closed);
+        }
+        self.origins.retain(|_, queue| !queue.agents.is_empty());
+        self.tick += 1;
+    }
+}
@@ -0,0 +1,377 @@
+use crate::simulator::{Simulator, Between, lanes::lane_allows};
+use crate::traffic_logic::{car::{Car, CarId, Direction}, intersection::{IntersectionId, LightStatus}, vehicle::{VehicleClass, REFERENCE_SPEED}};
+use crate::traffic_logic::road::Road;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+///How far before the stop bar a car stopping for its light counts as standing at it, in metres
+const AT_STOP_BAR : f64 = 1.0;
+
+///The speed below which a car counts as standing, in metres a second
+const STANDING : f64 = 0.1;
+
+///The parameters of the Intelligent Driver Model, which sets how hard every car speeds up or
+/// brakes from its own speed, the gap to the car ahead and how fast it is closing that gap.
+/// The desired speed and the maximum acceleration come from the vehicle class.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Idm
+{
+    ///The number of seconds a tick lasts
+    pub tick_seconds : f64,
+    ///The time gap to the car ahead drivers keep, in seconds
+    pub time_headway : f64,
+    ///The gap to the car ahead drivers keep when standing, in metres
+    pub min_gap : f64,
+    ///The deceleration drivers are comfortable with, in metres a second squared
+    pub comfortable_deceleration : f64,
+    ///The hardest a car can brake, in metres a second squared
+    pub max_deceleration : f64,
+    ///How quickly drivers ease off as they near their desired speed
+    pub exponent : f64,
+    ///The seconds a standing driver takes to set off once it may. Every driver already sees the car
+    /// ahead of it as it was on the last tick, and the driver at the stop bar also waits out the
+    /// reaction time of its driver profile when its light turns green.
+    pub reaction_time : f64,
+    ///Keeps the position and speed of every car on every tick
+    pub record_trajectories : bool
+}
+
+impl Default for Idm
+{
+    fn default() -> Self {
+        Idm{tick_seconds: 1.0, time_headway: 1.5, min_gap: 2.0, comfortable_deceleration: 2.0, max_deceleration: 9.0, exponent: 4.0, reaction_time: 0.0, record_trajectories: false}
+    }
+}
+
+impl Idm
+{
+    pub fn with_trajectories(mut self) -> Idm
+    {
+        self.record_trajectories = true;
+        self
+    }
+
+    ///The acceleration of a car from the Intelligent Driver Model
+    /// # Parameters
+    /// * `class` : `VehicleClass` - The kind of vehicle, which sets its desired speed and maximum acceleration
+    /// * `speed` : `f64` - The speed of the car, in metres a second
+    /// * `gap` : `f64` - The distance to the back of the car ahead, in metres, infinite on a free road
+    /// * `closing` : `f64` - How much faster the car is than the car ahead, in metres a second
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::simulator::microscopic::Idm;
+    /// use traffic_sim::traffic_logic::vehicle::VehicleClass;
+    /// let idm = Idm::default();
+    /// //a standing car on a free road speeds up as hard as it can
+    /// assert_eq!(idm.acceleration(VehicleClass::PassengerCar, 0.0, f64::INFINITY, 0.0), 2.6);
+    /// //and brakes when it closes in on a standing car
+    /// assert!(idm.acceleration(VehicleClass::PassengerCar, 13.0, 20.0, 13.0) < 0.0);
+    /// ```
+    pub fn acceleration(&self, class : VehicleClass, speed : f64, gap : f64, closing : f64) -> f64
+    {
+        let params = class.params();
+        let desired = params.max_speed.min(REFERENCE_SPEED);
+        let free = 1.0 - (speed / desired).powf(self.exponent);
+        let interaction = if gap.is_finite() {
+            let wanted = self.min_gap + (speed * self.time_headway + speed * closing / (2.0 * (params.acceleration * self.comfortable_deceleration).sqrt())).max(0.0);
+            (wanted / gap.max(0.1)).powi(2)
+        } else {0.0};
+        (params.acceleration * (free - interaction)).max(-self.max_deceleration)
+    }
+
+    ///The acceleration of a car behind another in its lane, or at the front of its lane
+    /// # Parameters
+    /// * `leader` : `Option<(&Kinematics, f64)>` - The car ahead and its length, `None` at the front of the lane
+    /// * `stop` : `bool` - The car at the front of the lane brakes for the stop bar
+    pub(crate) fn follow(&self, class : VehicleClass, state : &Kinematics, leader : Option<(&Kinematics, f64)>, stop : bool) -> f64
+    {
+        match leader{
+            Some((ahead, length)) => self.acceleration(class, state.speed, ahead.position - length - state.position, state.speed - ahead.speed),
+            //the stop bar is a standing car just beyond the end of the road
+            None if stop => self.acceleration(class, state.speed, state.remaining() + self.min_gap, state.speed),
+            None => self.acceleration(class, state.speed, f64::INFINITY, 0.0)
+        }
+    }
+}
+
+///Where a car is on its road and how fast it is going
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Kinematics
+{
+    ///The road the car is on, as the intersection it left and the one it is going to
+    pub link : (IntersectionId, IntersectionId),
+    ///The length of the road in metres
+    pub length : f64,
+    ///The lane the car is in, counting from 0 at the kerb
+    pub lane : u8,
+    ///The number of lanes of the road
+    pub lanes : u8,
+    ///The distance the front of the car has come from the start of the road, in metres
+    pub position : f64,
+    ///In metres a second
+    pub speed : f64,
+    ///In metres a second squared
+    pub acceleration : f64,
+    ///The car is braking for the stop bar because its light tells it to stop
+    pub stopping : bool,
+    ///The ticks the car has been standing at the stop bar
+    pub waiting : usize,
+    ///The seconds a standing car still waits before it sets off, `None` while it is not about to
+    pub(crate) start_delay : Option<f64>
+}
+
+impl Kinematics
+{
+    ///The distance left to the stop bar at the end of the road, in metres
+    pub fn remaining(&self) -> f64
+    {
+        (self.length - self.position).max(0.0)
+    }
+
+    ///Checks if the car has reached the intersection at the end of its road, either by driving over
+    /// the stop bar or by standing at it for its light
+    pub(crate) fn at_stop_bar(&self) -> bool
+    {
+        self.position > self.length || (self.stopping && self.position >= self.length - AT_STOP_BAR)
+    }
+
+    ///Checks if the car can make a turn from its lane
+    pub fn allows(&self, turn : Direction) -> bool
+    {
+        lane_allows(self.lane, self.lanes, turn)
+    }
+
+    ///Checks if the car makes a turn from its lane, either because it can or because it has waited
+    /// at the stop bar for longer than its patience to get into the lane it needs
+    pub(crate) fn makes_turn(&self, turn : Direction, patience : usize) -> bool
+    {
+        self.allows(turn) || self.waiting >= patience
+    }
+
+    ///Stops the car at the stop bar
+    pub(crate) fn hold(&mut self)
+    {
+        self.position = self.position.min(self.length);
+        self.speed = 0.0;
+        self.acceleration = 0.0;
+        self.waiting += 1;
+    }
+}
+
+///Where a car was on one tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrajectoryPoint
+{
+    pub tick : usize,
+    pub car_id : CarId,
+    pub link : (IntersectionId, IntersectionId),
+    pub lane : u8,
+    pub position : f64,
+    pub speed : f64,
+    pub acceleration : f64
+}
+
+impl Simulator
+{
+    ///Switches the microscopic mode on or off. In the microscopic mode cars on a road have a position
+    /// and speed in metres and follow the car ahead with the Intelligent Driver Model. Cars brake to
+    /// stand at the stop bar while their light tells them to stop, instead of joining the queue of the
+    /// intersection, so queues build up along the road and set off one car after the other once the
+    /// light turns green, each driver a little after the one ahead. On roads with several lanes cars
+    /// change lanes by the lane changing model.
+    pub fn set_microscopic(&mut self, idm : Option<Idm>)
+    {
+        if idm.is_none(){
+            self.kinematics.clear();
+        }
+        self.idm = idm;
+    }
+
+    pub fn microscopic(&self) -> Option<&Idm>
+    {
+        self.idm.as_ref()
+    }
+
+    ///Gets where a car is on its road in the microscopic mode
+    /// # Returns
+    /// `Option<&Kinematics>` : Returns `None` if the mode is off or the car is not driving on a road
+    pub fn kinematics(&self, car_id : CarId) -> Option<&Kinematics>
+    {
+        self.kinematics.get(&car_id)
+    }
+
+    ///The position and speed of every car on every tick, while trajectories are recorded
+    pub fn trajectories(&self) -> &[TrajectoryPoint]
+    {
+        &self.trajectories
+    }
+
+    ///Finds the roads a car cannot drive onto. In the microscopic mode that is because the last car in
+    /// every lane has not yet left the room it needs at their start, otherwise because the cars on the
+    /// road and queued at its end take up its `link_storage`.
+    /// # Returns
+    /// `HashSet<(IntersectionId, IntersectionId)>` : The roads as the intersection they leave and the one they go to
+    pub(crate) fn full_links(&self) -> HashSet<(IntersectionId, IntersectionId)>
+    {
+        let Some(idm) = self.idm else {
+            return self.link_occupancy().into_iter()
+                .filter(|((int_id, arm), occupancy)| self.link_storage(*int_id, *arm).is_some_and(|storage| *occupancy >= storage))
+                .filter_map(|((int_id, arm), _)| Some((int_id, self.road.road.get(&int_id)?[usize::from(arm)]?.dest_int_id)))
+                .collect();
+        };
+        let mut blocked: HashMap<(IntersectionId, IntersectionId), (u8, HashSet<u8>)> = HashMap::new();
+        for (car_id, state) in self.kinematics.iter(){
+            if self.get_car(*car_id).is_some_and(|car| state.position - car.class.params().length < idm.min_gap){
+                blocked.entry(state.link).or_insert((state.lanes, HashSet::new())).1.insert(state.lane);
+            }
+        }
+        blocked.into_iter().filter(|(_, (lanes, blocked))| blocked.len() >= usize::from(*lanes)).map(|(link, _)| link).collect()
+    }
+
+    ///Gets the side of the intersection at the end of a road cars arrive at
+    fn arrival_side(&self, link : (IntersectionId, IntersectionId)) -> Option<u8>
+    {
+        self.road.road.get(&link.0)?.iter().flatten().find(|node| node.dest_int_id == link.1).map(|node| node.direction)
+    }
+
+    ///Checks if the road a car leaves an intersection by is too full to drive onto
+    pub(crate) fn exit_blocked(road : &Road, full : &HashSet<(IntersectionId, IntersectionId)>, int_id : IntersectionId, exit : u8) -> bool
+    {
+        !full.is_empty() && road.road.get(&int_id)
+            .and_then(|arms| arms[usize::from(exit)])
+            .is_some_and(|node| full.contains(&(int_id, node.dest_int_id)))
+    }
+
+    ///Checks if a car coming to the end of its road has to stop at the stop bar, for its light, because
+    /// the road it turns onto is full, because no road leads out of the intersection or because it
+    /// cannot turn from its lane. On yellow drivers that
+    /// decided to go on it keep going, the others only stop if they can without braking harder than is comfortable.
+    pub(crate) fn must_stop(&self, idm : &Idm, car : &Car, state : &Kinematics, between : &Between, full : &HashSet<(IntersectionId, IntersectionId)>) -> bool
+    {
+        if car.trip.as_ref().is_some_and(|trip| trip.destination == between.int_2_id){
+            return false;
+        }
+        if !self.road.road.get(&between.int_2_id).is_some_and(|arms| arms.iter().any(Option::is_some)){
+            return true;
+        }
+        let exit = car.next_exit(between.int_2_id, between.from);
+        let turn = Direction::toward(between.from, exit);
+        if Self::exit_blocked(&self.road, full, between.int_2_id, exit) || !state.makes_turn(turn, self.mobil.patience){
+            return true;
+        }
+        let intersection = &self.intersections[between.int_2_id.index()];
+        let light = intersection.lights[usize::from((between.from+2)%4)];
+        let status = match turn{
+            Direction::Left | Direction::UTurn => light.left_turn_status,
+            Direction::Right | Direction::Straight => light.main_status
+        };
+        match status{
+            LightStatus::Green => false,
+            LightStatus::Yellow => car.yellow_decision != Some(true) && state.speed * state.speed / (2.0 * idm.comfortable_deceleration) <= state.remaining(),
+            LightStatus::Red => true
+        }
+    }
+
+    ///Moves every car driving on a road by the Intelligent Driver Model, all cars at once from where
+    /// they were on the last tick
+    /// # Parameters
+    /// * `full` : `&HashSet<(IntersectionId, IntersectionId)>` - The roads cars cannot drive onto, from `full_links`
+    pub(crate) fn tick_idm(&mut self, full : &HashSet<(IntersectionId, IntersectionId)>)
+    {
+        let Some(idm) = self.idm else { return };
+        let step = REFERENCE_SPEED * idm.tick_seconds;
+        let positions = &self.car_positions;
+        self.kinematics.retain(|car_id, _| positions.get(car_id).is_some_and(|pos| pos.in_between.is_some()));
+        let mut links: HashMap<(IntersectionId, IntersectionId, u8), Vec<CarId>> = HashMap::new();
+        for (car_id, pos) in self.car_positions.iter(){
+            if let Some(between) = pos.in_between{
+                links.entry((between.int_1_id, between.int_2_id, between.from)).or_default().push(*car_id);
+            }
+        }
+        let mut updates = Vec::new();
+        for cars in links.values_mut(){
+            let between = self.car_positions[&cars[0]].in_between.expect("Car is on a road");
+            let link = (between.int_1_id, between.int_2_id);
+            let Some(arm) = self.link_arm(&between) else { continue };
+            let Some(node) = self.road.road.get(&between.int_1_id).and_then(|arms| arms[usize::from(arm)]) else { continue };
+            let length = f64::from(node.dist_from_source) * step;
+            let lanes = node.lanes.max(1);
+            for car_id in cars.iter(){
+                let distance = self.car_positions[car_id].in_between.expect("Car is on a road").distance_to_target;
+                let state = match self.kinematics.get(car_id){
+                    Some(state) if state.link == link => continue,
+                    //the car has just driven over the intersection onto this road, into the lane nearest to it
+                    Some(old) => {
+                        let turn = self.arrival_side(old.link).map_or(Direction::Straight, |side| Direction::toward(side, arm));
+                        let lane = self.entry_lane(link, lanes, turn);
+                        Kinematics{link, length, lane, lanes, position: (old.position - old.length).clamp(0.0, length), speed: old.speed, acceleration: old.acceleration, stopping: false, waiting: 0, start_delay: None}
+                    },
+                    None => {
+                        let lane = self.entry_lane(link, lanes, Direction::Straight);
+                        let position = (length - f64::from(distance + 1) * step).max(0.0);
+                        let speed = if position > 0.0 {REFERENCE_SPEED} else {0.0};
+                        //cars setting off from a queue have already waited out their reaction
+                        Kinematics{link, length, lane, lanes, position, speed, acceleration: 0.0, stopping: false, waiting: 0, start_delay: Some(0.0)}
+                    }
+                };
+                self.kinematics.insert(*car_id, state);
+            }
+            cars.sort_by(|a, b| self.kinematics[b].position.total_cmp(&self.kinematics[a].position));
+            if lanes > 1{
+                self.change_lanes(&idm, cars, &between, full);
+            }
+            let lights = self.intersections[between.int_2_id.index()].lights;
+            for (index, car_id) in cars.iter().enumerate(){
+                if let Some(car) = self.get_car_mut(*car_id){
+                    car.see_light(&lights, usize::from((between.from+2)%4));
+                }
+                let (Some(car), Some(state)) = (self.get_car(*car_id), self.kinematics.get(car_id)) else { continue };
+                let mut state = *state;
+                let leader = cars[..index].iter().rev()
+                    .find(|ahead| self.kinematics[*ahead].lane == state.lane)
+                    .and_then(|ahead| Some((self.kinematics.get(ahead)?, self.get_car(*ahead)?.class.params().length)));
+                state.stopping = leader.is_none() && self.must_stop(&idm, car, &state, &between, full);
+                let limit = match leader{
+                    Some((ahead, ahead_length)) => (ahead.position - ahead_length).max(state.position),
+                    None if state.stopping => length,
+                    None => f64::INFINITY
+                };
+                let mut acceleration = idm.follow(car.class, &state, leader, state.stopping);
+                if self.bus_runs.get(car_id).is_some_and(|bus| bus.is_dwelling()){
+                    acceleration = -state.speed / idm.tick_seconds;
+                }
+                if state.speed < STANDING && acceleration > 0.0{
+                    let reaction = car.driver.filter(|_| leader.is_none()).map_or(0, |driver| driver.params().reaction_ticks) as f64 * idm.tick_seconds;
+                    let delay = state.start_delay.get_or_insert(idm.reaction_time + reaction);
+                    if *delay > 0.0{
+                        *delay -= idm.tick_seconds;
+                        acceleration = 0.0;
+                    }
+                }
+                else if state.speed >= STANDING{
+                    state.start_delay = None;
+                    state.waiting = 0;
+                }
+                let speed = (state.speed + acceleration * idm.tick_seconds).max(0.0);
+                let travelled = if speed == 0.0 && acceleration < 0.0 {
+                    state.speed * state.speed / (-2.0 * acceleration)
+                } else {(state.speed + speed) / 2.0 * idm.tick_seconds};
+                state.position = (state.position + travelled).min(limit);
+                state.speed = speed;
+                state.acceleration = acceleration;
+                updates.push((*car_id, state, ((state.remaining() / step).ceil() as u32).max(1)));
+            }
+        }
+        let tick = self.timestep;
+        for (car_id, state, distance) in updates{
+            self.kinematics.insert(car_id, state);
+            if let Some(between) = self.car_positions.get_mut(&car_id).and_then(|pos| pos.in_between.as_mut()){
+                between.distance_to_target = distance;
+            }
+            if idm.record_trajectories{
+                self.trajectories.push(TrajectoryPoint{tick, car_id, link: state.link, lane: state.lane, position: state.position, speed: state.speed, acceleration: state.acceleration});
+            }
+        }
+    }
+}
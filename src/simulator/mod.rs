@@ -1,24 +1,49 @@
-
-use crate::traffic_logic::{road::{Road, Node}, car::{Car, Direction}, intersection::{Intersection, TrafficLight, LightConfig}};
-use std::{collections::HashMap};
+pub mod builder;
+pub mod incidents;
+pub mod trips;
+pub mod assignment;
+pub mod demand;
+pub mod estimation;
+pub mod transit;
+pub mod drivers;
+pub mod violations;
+pub mod microscopic;
+pub mod lanes;
+pub mod macroscopic;
+pub mod mesoscopic;
+pub mod saturation;
+
+
+use crate::traffic_logic::{road::{Road, Node}, car::{Car, CarId, Direction, TurningProportions}, route::Route, vehicle::{VehicleClass, CAR_SPACE, REFERENCE_SPEED}, driver::DriverProfile, geometry::{Point, point_along}, validation::{Issue, ValidationReport}, intersection::{Intersection, IntersectionId, TrafficLight, LightStatus, SignalPlan}};
+use incidents::{Closure, Incident};
+use trips::TripRecord;
+use demand::{Demand, EntryRecord, PendingEntry};
+use transit::{BusRun, LineState, StopEvent};
+use violations::{Entry, EntryEvent, NonCompliance, Violation};
+use microscopic::{Idm, Kinematics, TrajectoryPoint};
+use lanes::{LaneChange, Mobil};
+use saturation::SaturationFlow;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 #[derive(Clone, Copy)]
 pub struct Between
 {
     //The IntersectionID of the intersection the car just left
-    pub int_1_id:u8,
+    pub int_1_id:IntersectionId,
     ///The IntersectionID of the intersection the car is going to
-    pub int_2_id:u8,
+    pub int_2_id:IntersectionId,
     //How far the car is from the target intersection
-    pub distance_to_target:u8,
-    //The direction the car is coming from/will arrive at
+    pub distance_to_target:u32,
+    ///The side of `int_2_id` the car will arrive at, the arm its road joins. This is not the way the car
+    /// is heading: a car driving east on a straight road arrives at the west side. Once there it waits
+    /// at that side and obeys the light at index `(from+2)%4`, the one facing it across the intersection.
     pub from:u8,
 }
 
 #[derive(Clone, Copy)]
 pub struct Current{
     //The IntersectionID of the intersection
-    pub int_id : u8,
+    pub int_id : IntersectionId,
     ///The direction the car is sitting at
     pub direction: u8
 }
@@ -40,10 +65,10 @@ pub struct Position
 
     ///An option of a tuple with the following members
     /// # Members
-    /// * `Intersection_1_ID` : `u8` - The ID of the intersection the car is coming from 
-    /// * `Intersection_2_ID` : `u8` - The ID of the intersection the car is going to
-    /// * `current_distance_to_target` : `u8` - the current distance from the destination Intersection
-    /// * `from_direction` : `u8 - The direction the car is going 0,1,2,3
+    /// * `Intersection_1_ID` : `IntersectionId` - The ID of the intersection the car is coming from 
+    /// * `Intersection_2_ID` : `IntersectionId` - The ID of the intersection the car is going to
+    /// * `current_distance_to_target` : `u32` - the current distance from the destination Intersection
+    /// * `from_direction` : `u8` - The 0,1,2,3 side of the destination intersection the car will arrive at
     pub in_between : Option<Between>
 }
 
@@ -57,42 +82,127 @@ impl Position
 
 }
 
+///A car that will be placed at an intersection once the simulation reaches `tick`
+#[derive(Clone)]
+pub struct Departure
+{
+    pub tick : usize,
+    pub at : Current,
+    ///The intersection the car drives to, cars without one turn at random
+    pub destination : Option<IntersectionId>,
+    ///The route the car takes to its destination, the shortest route when the car appears if there is none
+    pub route : Option<Route>
+}
+
 pub struct Simulator
 {
     road:Road,
-    car_positions: HashMap<u8, Position>,
+    departures: Vec<Departure>,
+    closures: Vec<Closure>,
+    incidents: Vec<Incident>,
+    ///The share of a car each road with an incident can currently let out
+    incident_credit: HashMap<(IntersectionId, IntersectionId), f64>,
+    car_positions: HashMap<CarId, Position>,
     cars : Vec<Car>,
+    ///The place of every car in `cars`
+    car_index: HashMap<CarId, usize>,
+    ///The IDs of cars that have left the simulation, given to the next cars added
+    free_car_ids: Vec<CarId>,
     intersections : Vec<Intersection>,
+    ///Every trip that has reached its destination, in the order they arrived
+    completed_trips: Vec<TripRecord>,
+    ///The share of cars starting a trip that reroute at every intersection
+    reroute_share: f64,
+    ///The ticks each passenger car space taken up on a road adds to its live cost
+    queue_delay: f64,
+    ///The origin–destination matrix trips are started from every tick
+    demand: Option<Demand>,
+    ///Trips started by the demand that are waiting to enter the network at their origin
+    entry_queues: HashMap<IntersectionId, VecDeque<PendingEntry>>,
+    ///Every trip that has left an entry queue
+    entry_log: Vec<EntryRecord>,
+    transit_lines: Vec<LineState>,
+    ///The line and progress of every bus driving
+    bus_runs: HashMap<CarId, BusRun>,
+    ///Every time a bus has served a stop
+    stop_events: Vec<StopEvent>,
+    ///The share of drivers of each profile new cars are given
+    driver_mix: Vec<(DriverProfile, f64)>,
+    ///How often drivers run the lights, no one does if it is not set
+    non_compliance: Option<NonCompliance>,
+    ///Every car that has entered an intersection against its light
+    violations: Vec<Violation>,
+    ///The cars that entered an intersection recently enough to still be in it
+    recent_entries: VecDeque<EntryEvent>,
+    ///The car following model of the microscopic mode, cars count down the ticks left on their road if it is off
+    idm: Option<Idm>,
+    ///Where every car driving on a road is in the microscopic mode
+    kinematics: HashMap<CarId, Kinematics>,
+    ///Where every car was on every tick, if the microscopic mode records it
+    trajectories: Vec<TrajectoryPoint>,
+    ///How cars change lanes in the microscopic mode
+    mobil: Mobil,
+    lane_changes: Vec<LaneChange>,
+    ///How fast queues at the lights discharge, one car a tick if it is off
+    saturation: Option<SaturationFlow>,
     timestep: usize,
-    next_int_id : u8,
-    next_car_id : u8
+    next_int_id : u32,
+    next_car_id : u32,
+    ///The serial of the next trip to set off
+    next_trip : u64
+}
 
+impl Default for Simulator
+{
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 #[allow(dead_code)]
 impl Simulator
 {
     pub fn new() -> Simulator
     {
         let intersections:Vec<Intersection> = Vec::new();
-        Simulator{road:Road::new(), car_positions: HashMap::new(), timestep:0, cars:Vec::new(), intersections, next_car_id: 0, next_int_id:1}
+        Simulator{road:Road::new(), departures: Vec::new(), closures: Vec::new(), incidents: Vec::new(), incident_credit: HashMap::new(), car_positions: HashMap::new(), timestep:0, cars:Vec::new(), car_index: HashMap::new(), free_car_ids: Vec::new(), completed_trips: Vec::new(), reroute_share: 0.0, queue_delay: 2.0, demand: None, entry_queues: HashMap::new(), entry_log: Vec::new(), transit_lines: Vec::new(), bus_runs: HashMap::new(), stop_events: Vec::new(), driver_mix: Vec::new(), non_compliance: None, violations: Vec::new(), recent_entries: VecDeque::new(), idm: None, kinematics: HashMap::new(), trajectories: Vec::new(), mobil: Mobil::default(), lane_changes: Vec::new(), saturation: None, intersections, next_car_id: 1, next_trip: 1, next_int_id:1}
     }
 
     pub fn add_intersection(&mut self){
-        let intersection = Intersection::new(self.next_int_id);
+        let intersection = Intersection::new(IntersectionId(self.next_int_id));
         self.intersections.push(intersection);
         self.next_int_id += 1;
     }
 
-    pub fn add_intersections(&mut self, num:u8){
-        for _ in 0..num.into(){
+    pub fn add_intersections(&mut self, num:u32){
+        for _ in 0..num{
             self.add_intersection();
         }
     }
 
-    pub fn add_road(&mut self, int_1: u8, direction: u8, int_2: u8, distance:u8){
-        let len = self.intersections.len();
-        if len < int_1.into() || len < int_2.into(){
-            println!("{}", len);
+    ///Connects two intersections in both directions, with the road joining `int_2` on the arm opposite to
+    /// `direction`. Cars driving from `int_1` arrive at that arm of `int_2`, and cars driving back arrive at
+    /// `direction` of `int_1`, see `add_link`.
+    pub fn add_road(&mut self, int_1: IntersectionId, direction: u8, int_2: IntersectionId, distance:u32){
+        let new_dir = (direction+2)%4;
+        self.add_link(int_1, direction, int_2, new_dir, distance, 1);
+        self.add_link(int_2, new_dir, int_1, direction, distance, 1);
+    }
+
+    ///Connects one intersection to another in a single direction.
+    ///
+    /// The road is stored under the arm it leaves `int_1` from and records the arm `arrival` it joins `int_2`
+    /// on, which is the side of `int_2` cars on it arrive at and queue at. A car waiting at side `s` obeys
+    /// the light at index `(s+2)%4` and turns relative to `s`, so the arms do not need to be opposite.
+    /// # Parameters
+    /// * `int_1` : `IntersectionId` - The ID of the intersection the road leaves from
+    /// * `direction` : `u8` - The arm of `int_1` the road leaves from
+    /// * `int_2` : `IntersectionId` - The ID of the intersection the road goes to
+    /// * `arrival` : `u8` - The arm of `int_2` cars arrive at
+    /// * `distance` : `u32` - The number of ticks it takes to drive the road
+    /// * `lanes` : `u8` - The number of lanes in this direction
+    pub fn add_link(&mut self, int_1: IntersectionId, direction: u8, int_2: IntersectionId, arrival: u8, distance: u32, lanes: u8){
+        if self.get_intersection(int_1).is_none() || self.get_intersection(int_2).is_none(){
             panic!("Cannot add a connection to an intersection that does not exist");
         }
         let road = &mut self.road.road;
@@ -100,15 +210,8 @@ impl Simulator
         if let Some(entry) = ent{
             panic!("Intersection {} is already connected to Intersection {} in direction {}", int_1, entry.dest_int_id, direction);
         }
-        
-        *ent = Some(Node::new(int_2, distance, direction));
-        let new_dir = usize::from(direction+2)%4;
-        let ent = &mut road.entry(int_2).or_insert([None, None, None, None])[new_dir];
-        if let Some(entry) = ent{
-            panic!("Intersection {} is already connected to Intersection {} in direction {}", int_2, entry.dest_int_id, new_dir);
-        }
-        *ent = Some(Node::new(int_1, distance, new_dir.try_into().unwrap()));
-
+        *ent = Some(Node::new(int_2, distance, arrival, lanes));
+        road.entry(int_2).or_insert([None, None, None, None]);
     }
 
 
@@ -141,202 +244,593 @@ impl Simulator
         
     }
 
-    pub fn add_car(&mut self, pos:Position){
-        self.cars.push(Car::new(self.next_car_id));
-        let car_id = self.next_car_id;
-        let int_id = pos.current_intersection.expect("Car does not have a current intersection").int_id;
+    ///Places a car waiting at an intersection, or driving on a road if the position has no current intersection.
+    /// The car picks its turn from the turning proportions of the side it waits or arrives at, if it has them,
+    /// and goes straight otherwise
+    /// # Returns
+    /// `CarId` : The ID of the new car, the ID of a car that has left the simulation if there is one
+    pub fn add_car(&mut self, pos:Position) -> CarId{
+        let car_id = self.free_car_ids.pop().unwrap_or_else(|| {
+            let id = CarId(self.next_car_id);
+            self.next_car_id += 1;
+            id
+        });
+        let mut car = Car::new(car_id);
+        match (pos.current_intersection, pos.in_between){
+            (Some(current), _) => {
+                let intersection = self.get_intersection_mut(current.int_id).unwrap_or_else(|| panic!("Could not find intersection with ID {}", current.int_id));
+                intersection.add_car_to_queue(car_id, current.direction);
+                if let Some(turning) = intersection.turning[usize::from(current.direction)]{
+                    car.randomize_intent(Some(&turning));
+                }
+            },
+            (None, Some(between)) => {
+                if self.link_arm(&between).is_none(){
+                    panic!("Intersection {} has no road to side {} of Intersection {}", between.int_1_id, between.from, between.int_2_id);
+                }
+                if let Some(turning) = self.get_intersection(between.int_2_id).and_then(|intersection| intersection.turning[usize::from(between.from)]){
+                    car.randomize_intent(Some(&turning));
+                }
+                car.at_intersection = false;
+            },
+            (None, None) => panic!("Car does not have a current intersection or road")
+        }
+        car.driver = self.sample_driver();
+        self.car_index.insert(car_id, self.cars.len());
+        self.cars.push(car);
+        self.car_positions.insert(car_id, pos);
+        car_id
+    }
+
+    ///Takes a car out of the simulation, its ID is given to the next car added
+    /// # Returns
+    /// `bool` : Returns `false` if there is no car with that ID
+    pub fn remove_car(&mut self, car_id:CarId) -> bool{
+        let Some(index) = self.car_index.remove(&car_id) else {
+            return false;
+        };
+        self.cars.swap_remove(index);
+        if let Some(moved) = self.cars.get(index){
+            self.car_index.insert(moved.id, index);
+        }
+        if let Some(current) = self.car_positions.remove(&car_id).and_then(|pos| pos.current_intersection){
+            if let Some(intersection) = self.get_intersection_mut(current.int_id){
+                intersection.light_queues[usize::from(current.direction)].retain(|id| *id != car_id);
+            }
+        }
+        self.bus_runs.remove(&car_id);
+        self.kinematics.remove(&car_id);
+        self.free_car_ids.push(car_id);
+        true
+    }
+
+    ///Gives the serial of a trip setting off, trips are numbered from 1 in the order they set off
+    pub(crate) fn next_trip_serial(&mut self) -> u64
+    {
+        let serial = self.next_trip;
+        self.next_trip += 1;
+        serial
+    }
+
+    ///Makes sure no later trip gets a serial already given to a trip that was set up by hand
+    pub(crate) fn reserve_trip_serial(&mut self, serial : u64)
+    {
+        self.next_trip = self.next_trip.max(serial + 1);
+    }
+
+    ///Schedules a car to be placed at an intersection on a later tick
+    /// # Parameters
+    /// * `tick` : `usize` - The timestep the car appears on, cars scheduled in the past appear on the next tick
+    /// * `at` : `Current` - The intersection and direction the car will wait at
+    pub fn schedule_car(&mut self, tick:usize, at:Current){
+        let index = self.departures.partition_point(|departure| departure.tick <= tick);
+        self.departures.insert(index, Departure{tick, at, destination: None, route: None});
+    }
+
+    ///Gives an intersection a fixed time signal plan, replacing its random lights
+    pub fn set_signal_plan(&mut self, int_id:IntersectionId, plan:SignalPlan){
+        let intersection = self.get_intersection_mut(int_id).unwrap_or_else(|| panic!("Could not find intersection with ID {}", int_id));
+        intersection.signal_plan = Some(plan);
+    }
+
+    ///Checks the network and the cars on it for problems that would make the simulation panic,
+    /// loop forever or behave unexpectedly
+    pub fn validate(&self) -> ValidationReport{
+        let ids: Vec<IntersectionId> = self.intersections.iter().map(|intersection| intersection.id).collect();
+        let mut report = self.road.validate(&ids);
+        let placed = self.car_positions.values().filter_map(|pos| pos.current_intersection.map(|current| current.int_id)
+            .or(pos.in_between.map(|between| between.int_2_id)));
+        let scheduled = self.departures.iter().flat_map(|departure| [Some(departure.at.int_id), departure.destination]).flatten();
+        for id in placed.chain(scheduled){
+            let issue = Issue::MissingIntersection(id);
+            if !ids.contains(&id) && !report.issues.contains(&issue){
+                report.issues.push(issue);
+            }
+        }
+        report
+    }
+
+    ///Sets how often cars without a route make each turn from one side of an intersection
+    /// # Parameters
+    /// * `int_id` : `IntersectionId` - The ID of the intersection
+    /// * `side` : `u8` - The side cars wait at, the arm they arrive on
+    /// * `turning` : `Option<TurningProportions>` - The share of each turn, `None` makes every turn as likely
+    pub fn set_turning_proportions(&mut self, int_id:IntersectionId, side:u8, turning:Option<TurningProportions>){
+        let intersection = self.get_intersection_mut(int_id).unwrap_or_else(|| panic!("Could not find intersection with ID {}", int_id));
+        intersection.turning[usize::from(side)] = turning;
+    }
+
+    ///Places an intersection on the map
+    pub fn set_position(&mut self, int_id:IntersectionId, position:Point){
         let intersection = self.get_intersection_mut(int_id).unwrap_or_else(|| panic!("Could not find intersection with ID {}", int_id));
-        intersection.add_car_to_queue(car_id, pos.current_intersection.unwrap().direction);
-        self.car_positions.insert(self.next_car_id, pos);
-        self.next_car_id += 1;
+        intersection.position = Some(position);
+    }
+
+    ///Sets the points a road bends through, in order from the intersection it leaves
+    /// # Parameters
+    /// * `int_id` : `IntersectionId` - The ID of the intersection the road leaves from
+    /// * `arm` : `u8` - The arm of the intersection the road leaves from
+    /// * `shape` : `Vec<Point>` - The points between the two intersections, a straight road has none
+    pub fn set_shape(&mut self, int_id:IntersectionId, arm:u8, shape:Vec<Point>){
+        if shape.is_empty(){
+            self.road.shapes.remove(&(int_id, arm));
+        }
+        else {
+            self.road.shapes.insert((int_id, arm), shape);
+        }
+    }
+
+    ///Gets the full line a road follows on the map, from the intersection it leaves to the one it goes to
+    /// # Returns
+    /// `Option<Vec<Point>>` : Returns `None` if there is no such road or either intersection has no position
+    pub fn road_line(&self, int_id:IntersectionId, arm:u8) -> Option<Vec<Point>>{
+        let node = self.road.road.get(&int_id)?[usize::from(arm)]?;
+        let mut line = vec![self.get_intersection(int_id)?.position?];
+        line.extend(self.road.get_shape(int_id, arm).iter().copied());
+        line.push(self.get_intersection(node.dest_int_id)?.position?);
+        Some(line)
+    }
+
+    ///Gets where a car is on the map, cars between intersections are placed along their road by how far they have driven
+    /// # Returns
+    /// `Option<Point>` : Returns `None` if the car does not exist or the intersections it is at or between have no position
+    pub fn car_location(&self, car_id:CarId) -> Option<Point>{
+        let pos = self.car_positions.get(&car_id)?;
+        if let Some(current) = pos.current_intersection{
+            return self.get_intersection(current.int_id)?.position;
+        }
+        let between = pos.in_between?;
+        let arm = self.link_arm(&between)?;
+        let distance = self.road.road.get(&between.int_1_id)?[usize::from(arm)]?.dist_from_source;
+        let line = self.road_line(between.int_1_id, arm)?;
+        let along = match self.kinematics.get(&car_id){
+            Some(state) => state.position / state.length,
+            None => 1.0 - f64::from(between.distance_to_target) / f64::from(distance)
+        };
+        point_along(&line, along.clamp(0.0, 1.0))
+    }
+
+    ///Counts the cars driving on every road
+    /// # Returns
+    /// `HashMap<(IntersectionId, u8), usize>` : The number of cars keyed by the intersection and arm the road leaves from, empty roads are left out
+    pub fn link_counts(&self) -> HashMap<(IntersectionId, u8), usize>{
+        let mut counts = HashMap::new();
+        for between in self.car_positions.values().filter_map(|pos| pos.in_between){
+            if let Some(arm) = self.link_arm(&between){
+                *counts.entry((between.int_1_id, arm)).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    ///Measures how full every road is, the room taken up by the cars driving on it and queued at its end.
+    /// Cars do not drive onto a road once this reaches its `link_storage`.
+    /// # Returns
+    /// `HashMap<(IntersectionId, u8), f64>` : Passenger car spaces keyed by the intersection and arm the road leaves from, empty roads are left out
+    pub fn link_occupancy(&self) -> HashMap<(IntersectionId, u8), f64>{
+        let mut occupancy = HashMap::new();
+        for (car_id, pos) in self.car_positions.iter(){
+            let Some(between) = pos.in_between else { continue };
+            let (Some(arm), Some(car)) = (self.link_arm(&between), self.get_car(*car_id)) else { continue };
+            *occupancy.entry((between.int_1_id, arm)).or_insert(0.0) += car.class.storage_spaces();
+        }
+        for (from, arms) in self.road.road.iter(){
+            for (arm, node) in arms.iter().enumerate(){
+                let Some(node) = node else { continue };
+                let queued = self.queue_occupancy(node.dest_int_id, node.direction);
+                if queued > 0.0{
+                    *occupancy.entry((*from, arm as u8)).or_insert(0.0) += queued;
+                }
+            }
+        }
+        occupancy
+    }
+
+    ///Gets the room a road has for the cars driving on it and queued at its end, its length over `CAR_SPACE`
+    /// in every lane
+    /// # Returns
+    /// `Option<f64>` : Passenger car spaces, `None` if the intersection has no road leaving on that arm
+    pub fn link_storage(&self, int_id:IntersectionId, arm:u8) -> Option<f64>{
+        let node = self.road.road.get(&int_id)?[usize::from(arm)]?;
+        Some(f64::from(node.dist_from_source) * REFERENCE_SPEED / CAR_SPACE * f64::from(node.lanes.max(1)))
+    }
+
+    ///Measures the room taken up by the cars waiting at one side of an intersection, in passenger car spaces
+    pub fn queue_occupancy(&self, int_id:IntersectionId, side:u8) -> f64{
+        self.get_intersection(int_id).map_or(0.0, |intersection| intersection.light_queues[usize::from(side)].iter()
+            .filter_map(|car_id| self.get_car(*car_id))
+            .map(|car| car.class.storage_spaces())
+            .sum())
+    }
+
+    ///Changes the kind of vehicle a car is
+    /// # Returns
+    /// `bool` : Returns `false` if there is no car with that ID
+    pub fn set_vehicle_class(&mut self, car_id:CarId, class:VehicleClass) -> bool{
+        match self.get_car_mut(car_id){
+            Some(car) => {
+                car.class = class;
+                true
+            },
+            None => false
+        }
+    }
+
+    ///Gets the arm of `int_1_id` the road a car is driving on leaves from
+    fn link_arm(&self, between:&Between) -> Option<u8>{
+        self.road.road.get(&between.int_1_id)?.iter()
+            .position(|node| node.is_some_and(|node| node.dest_int_id == between.int_2_id && node.direction == between.from))
+            .map(|arm| arm as u8)
+    }
+
+    pub fn road(&self) -> &Road{
+        &self.road
+    }
+
+    pub fn intersections(&self) -> &[Intersection]{
+        &self.intersections
     }
 
-    fn create_random_lights(&self) ->  HashMap<u8, [TrafficLight;4]>
+    pub fn cars(&self) -> &[Car]{
+        &self.cars
+    }
+
+    pub fn departures(&self) -> &[Departure]{
+        &self.departures
+    }
+
+    pub fn timestep(&self) -> usize{
+        self.timestep
+    }
+
+    ///Moves the clock to a tick without running the ticks before it, for a run that picks up where another left off
+    pub(crate) fn set_timestep(&mut self, tick:usize){
+        self.timestep = tick;
+    }
+
+    pub fn get_position(&self, car_id:CarId) -> Option<&Position>{
+        self.car_positions.get(&car_id)
+    }
+
+    fn create_random_lights(&self) ->  HashMap<IntersectionId, [TrafficLight;4]>
     {
-        let mut new_map: HashMap<u8, [TrafficLight;4]> = HashMap::new();
+        let mut new_map: HashMap<IntersectionId, [TrafficLight;4]> = HashMap::new();
         self.intersections.iter().for_each(|intersection| {
-            let id = intersection.id.clone();
+            let id = intersection.id;
             //let random_lights: [TrafficLight;4] = [TrafficLight::rand(), TrafficLight::rand(), TrafficLight::rand(), TrafficLight::rand()];
-            let config:LightConfig = rand::random();
-            let random_lights = config.get_lights();
+            let random_lights = intersection.next_lights(self.timestep);
             new_map.insert(id, random_lights);
         });
         new_map
     }
 
+    fn spawn_departures(&mut self)
+    {
+        let due = self.departures.partition_point(|departure| departure.tick <= self.timestep);
+        let departures: Vec<Departure> = self.departures.drain(..due).collect();
+        for departure in departures{
+            match (departure.destination, departure.route){
+                (Some(destination), Some(route)) => {
+                    self.add_trip_on_route(departure.at, destination, route);
+                },
+                (Some(destination), None) => {
+                    self.add_trip(departure.at, destination);
+                },
+                (None, _) => {
+                    self.add_car(Position::new(Some(departure.at), None));
+                }
+            }
+        }
+    }
+
+
+    ///Moves a car that is allowed to leave an intersection onto the road its intention points to.
+    /// Cars with a trip follow their route, finding a new one if the next road is closed, other cars
+    /// pick a new intention from the turning proportions of their side until there is an open road that way.
+    /// Once on the road, cars without a trip pick their turn at the next intersection from the proportions
+    /// of the side they arrive at.
+    /// # Returns
+    /// `bool` : Returns `false` if there is no open road the car can take, the car does not move
+    fn depart(road: &Road, closed: &HashSet<(IntersectionId, u8)>, turnings: &[[Option<TurningProportions>; 4]], car: &mut Car, car_pos: &mut Position, at: Current, tick: usize) -> bool
+    {
+        let Current{int_id, direction: side} = at;
+        let turning = turnings.get(int_id.index()).and_then(|sides| sides[usize::from(side)]);
+        let is_open = |arm: u8| road.road.get(&int_id).is_some_and(|arms| arms[usize::from(arm)].is_some()) && !closed.contains(&(int_id, arm));
+        if !(0..4).any(is_open){
+            return false;
+        }
+        if let Some(trip) = &mut car.trip{
+            if !trip.route.links.front().is_some_and(|(at, arm)| *at == int_id && is_open(*arm)){
+                match road.shortest_path(int_id, trip.destination, closed){
+                    Some(route) => trip.route = route,
+                    None => return false
+                }
+            }
+            let (_, arm) = trip.route.links.pop_front().expect("Should never get here");
+            car.intention = Direction::toward(side, arm);
+            trip.path.push((int_id, arm, tick));
+        }
+        let open_turning = turning.and_then(|turning| turning.only(|turn| is_open(Direction::get_next_direction(side, turn))));
+        while !is_open(Direction::get_next_direction(side, car.intention)){
+            car.randomize_intent(open_turning.as_ref());
+        }
+        let next = road.get_next_node(int_id, side, car.intention).expect("Should never get here");
+        let new_in_between = Between{
+                                        int_1_id: int_id,
+                                        int_2_id: next.dest_int_id,
+                                        distance_to_target: car.class.travel_ticks(next.dist_from_source, car_pos.current_intersection.is_some())-1,
+                                        from: next.direction};
+        car_pos.in_between = Some( new_in_between );
+        car_pos.current_intersection = None;
+        car.at_intersection = false;
+        //turn the way the route goes at the next intersection
+        match car.trip.as_ref(){
+            Some(trip) => if let Some((_, arm)) = trip.route.links.front(){
+                car.intention = Direction::toward(next.direction, *arm);
+            },
+            None => car.randomize_intent(turnings.get(next.dest_int_id.index()).and_then(|sides| sides[usize::from(next.direction)]).as_ref())
+        }
+        true
+    }
 
-    fn tick_cars(&mut self)
+    fn tick_cars(&mut self, gaps: &HashMap<(IntersectionId, u8), u32>, full: &HashSet<(IntersectionId, IntersectionId)>)
     {
+        self.tick_incidents();
+        let closed = self.closed_links();
+        let tick = self.timestep;
+        let turnings: Vec<[Option<TurningProportions>; 4]> = self.intersections.iter().map(|intersection| intersection.turning).collect();
+        let live_costs = if self.cars.iter().any(|car| car.trip.as_ref().is_some_and(|trip| trip.reroutes)) {self.live_link_costs()} else {HashMap::new()};
+        let mut arrived = Vec::new();
+        let mut entries = Vec::new();
         self.cars.iter_mut().for_each(|car|{
             let car_pos = self.car_positions.get_mut(&car.id).unwrap();
             if let Some(current) = car_pos.current_intersection{
-                let intersection_id = current.int_id;
-                let direction = current.direction;
-                if !car.at_intersection{ //car is at intersection but not in list, means it must drive 
-                    let next_intersection_opt = self.road.get_next_intersection(intersection_id, direction, car.intention.clone());
-                    match next_intersection_opt{
-                        Some(next_intersection) => {
-                            let new_in_between = Between{
-                                                            int_1_id: intersection_id,
-                                                            int_2_id: next_intersection.0,
-                                                            distance_to_target: self.road.get_distance(intersection_id, next_intersection.0).unwrap()-1,
-                                                            from: Direction::get_next_direction(direction, car.intention)};
-                            car_pos.in_between = Some( new_in_between );
-                            car_pos.current_intersection = None;
-                            car.at_intersection = false;
-                                
-                        },
-                        None => {
-                            while let None = self.road.get_next_intersection(intersection_id, direction, car.intention){
-                                car.randomize_intent();
-                            }
-                            let next_intersection = self.road.get_next_intersection(intersection_id, direction, car.intention.clone()).expect("Should never get here");
-                            let new_in_between = Between{
-                                                            int_1_id: intersection_id,
-                                                            int_2_id: next_intersection.0,
-                                                            distance_to_target: self.road.get_distance(intersection_id, next_intersection.0).unwrap()-1,
-                                                            from: Direction::get_next_direction(direction, car.intention)};
-                            car_pos.in_between = Some( new_in_between );
-                            car_pos.current_intersection = None;
-                            car.at_intersection = false;
-                                    
-                            
-
-                        }
-                    }
-                    
+                //car is at intersection but not in list, means it must drive 
+                if !car.at_intersection && !Self::depart(&self.road, &closed, &turnings, car, car_pos, current, tick){
+                    //every way out is closed, wait at the front of the queue
+                    car.at_intersection = true;
+                    car.wait_time += 1;
+                    self.intersections[current.int_id.index()]
+                    .light_queues[usize::from(current.direction)].push_front(car.id);
                 }
             }
             else {
                 let mut in_between = car_pos.in_between.unwrap();
-                if in_between.distance_to_target == 1
+                if let Some(bus) = self.bus_runs.get_mut(&car.id){
+                    if Self::dwell(&mut self.transit_lines, &mut self.stop_events, bus, car, &in_between, tick){
+                        return;
+                    }
+                }
+                let state = self.kinematics.get_mut(&car.id);
+                if state.as_ref().map_or(in_between.distance_to_target == 1, |state| state.at_stop_bar())
                 {
+                    //roads with an incident only let part of a car out each tick
+                    if let Some(credit) = self.incident_credit.get_mut(&(in_between.int_1_id, in_between.int_2_id)){
+                        if *credit < 1.0{
+                            car.wait_time += 1;
+                            if let Some(state) = state{
+                                state.hold();
+                            }
+                            return;
+                        }
+                        *credit -= 1.0;
+                    }
+                    if car.trip.as_ref().is_some_and(|trip| trip.destination == in_between.int_2_id){
+                        arrived.push(car.id);
+                        return;
+                    }
+                    //rerouting cars pick their next road from how busy the roads are now
+                    if let Some(trip) = car.trip.as_mut().filter(|trip| trip.reroutes){
+                        if let Some(route) = Self::live_route(&self.road, &closed, &live_costs, in_between.int_2_id, trip.destination){
+                            if route.links.front() != trip.route.links.front(){
+                                trip.route_changes += 1;
+                            }
+                            car.intention = Direction::toward(in_between.from, route.links[0].1);
+                            trip.route = route;
+                        }
+                    }
                     
-                    let intersection = self.intersections.iter_mut()
-                    .find(|int| int.id == in_between.int_2_id).unwrap_or_else(||{
+                    let intersection = self.intersections.get_mut(in_between.int_2_id.index()).unwrap_or_else(||{
                         
                         panic!("Could not find intersection with id {}", in_between.int_2_id)
                     });
-                    if car.can_go(&intersection.lights, usize::from(in_between.from+2)%4) {//lights at target intersection are green
-                        let direction = (in_between.from+2)%4;
-                        let next_intersection_opt = self.road.get_next_intersection(intersection.id, direction, car.intention.clone());
-                        match next_intersection_opt{
-                            Some(next_intersection) => {
-                                let new_in_between = Between{
-                                                                int_1_id: intersection.id,
-                                                                int_2_id: next_intersection.0,
-                                                                distance_to_target: self.road.get_distance(intersection.id, next_intersection.0).unwrap()-1,
-                                                                from: Direction::get_next_direction(direction, car.intention)};
-                                car_pos.in_between = Some( new_in_between );
-                                car_pos.current_intersection = None;
-                                car.at_intersection = false;
-                                    
-                            },
-                            None => {
-                                while let None = self.road.get_next_intersection(intersection.id, direction, car.intention){
-                                    car.randomize_intent();
-                                }
-                                let next_intersection = self.road.get_next_intersection(intersection.id, direction, car.intention.clone()).expect("Should never get here");
-                                let new_in_between = Between{
-                                                                int_1_id: intersection.id,
-                                                                int_2_id: next_intersection.0,
-                                                                distance_to_target: self.road.get_distance(intersection.id, next_intersection.0).unwrap()-1,
-                                                                from: Direction::get_next_direction(direction, car.intention)};
-                                car_pos.in_between = Some( new_in_between );
-                                car_pos.current_intersection = None;
-                                car.at_intersection = false;
-                                        
-                                
-
-                            }
-                        }
+                    //lights at target intersection are green
+                    let exit = car.next_exit(intersection.id, in_between.from);
+                    //cars only turn from the wrong lane once they have run out of patience
+                    let wrong_lane = state.as_ref().is_some_and(|state| !state.makes_turn(Direction::toward(in_between.from, exit), self.mobil.patience));
+                    //with a saturation flow cars join the back of a queue rather than drive past it
+                    let queued = self.saturation.is_some() && !intersection.light_queues[usize::from(in_between.from)].is_empty();
+                    let entry = if wrong_lane || queued || Self::exit_blocked(&self.road, full, intersection.id, exit) {Entry::Stop}
+                        else {Self::permitted(car, intersection, in_between.from, gaps, self.non_compliance.as_ref())};
+                    if entry != Entry::Stop && Self::depart(&self.road, &closed, &turnings, car, car_pos, Current{int_id: intersection.id, direction: in_between.from}, tick){
+                        entries.push((car.id, intersection.id, in_between.from, Direction::get_next_direction(in_between.from, car.intention), entry));
+                        return;
+                    }
+                    //in the microscopic mode cars queue on the road behind the stop bar
+                    if let Some(state) = state{
+                        state.hold();
+                        car.wait_time += 1;
                         return;
                     }
 
 
                     let new_curr = Current{int_id: in_between.int_2_id, direction: in_between.from};
                     car_pos.current_intersection = Some(new_curr);
-                    self.intersections.iter_mut()
-                    .find(|int| int.id == in_between.int_2_id).unwrap()
-                    .add_car_to_queue(car.id, new_curr.direction);
+                    intersection.add_car_to_queue(car.id, new_curr.direction);
                     
                     car_pos.in_between = None;
-                    car.randomize_intent();
                     car.at_intersection = true;
                     
                 }
-                else {
+                else if state.is_none() {
                     in_between.distance_to_target -= 1;
                     car_pos.in_between = Some(in_between);
                 }
             }
-        })
+        });
+        for car_id in arrived{
+            self.finish_trip(car_id);
+        }
+        for (car_id, int_id, side, exit, entry) in entries{
+            self.record_entry(car_id, int_id, side, exit, entry);
+        }
+        self.check_conflicts();
     }
     
 
-    fn tick_lights(&mut self, new_lights: HashMap<u8, [TrafficLight;4]>)
+    ///Lets the car at the front of every queue leave if it may go and it has had as many ticks of
+    /// green as its PCE, so heavy vehicles hold up the queue behind them for longer. A driver at the
+    /// front when its light turns green first waits out its reaction time. A driver running the light goes at once.
+    ///
+    /// With a saturation flow every queue instead builds up the PCE its lanes let out in the effective green of
+    /// each tick and lets out as many cars as that pays for, which can be several a tick. Drivers without a profile
+    /// keep leaving in the part of the yellow that is effective green. A driver with a profile keeps to the decision it
+    /// made when the light turned yellow, which wins over the saturation flow: it stops if it decided to, and if it
+    /// decided to go it still only leaves while the yellow is effective green.
+    fn tick_lights(&mut self, new_lights: HashMap<IntersectionId, [TrafficLight;4]>, gaps: &HashMap<(IntersectionId, u8), u32>, full: &HashSet<(IntersectionId, IntersectionId)>)
     {
-        let mut ids_to_notify: Vec<(u8, u8, u8)> = Vec::new(); // vector holds car IDs followed by main light index, then intersection ID
+        let mut entries = Vec::new();
+        let saturation = self.saturation;
+        let lanes = if saturation.is_some() {self.approach_lanes()} else {HashMap::new()};
         for (id, new) in new_lights.iter()
         {
-            let mut i:u8 = 0;
-            let intersection = self.intersections.iter_mut().find(|int_id|{
-                int_id.id == *id
-            }).unwrap();
-
-            intersection.light_queues.iter_mut()
-            .for_each(|q| {
-                match q.pop_front(){
-                    None => (),
-                    Some(c_id) => {ids_to_notify.push((c_id, (i+2)%4, intersection.id))}
-                }
-                
-                    i+=1;
-            });
+            let intersection = &mut self.intersections[id.index()];
+            let old = intersection.lights;
             intersection.lights = *new;
-
-        }
-        //notify the cars and remove from list
-        
-        ids_to_notify.iter().for_each(|(car_id, main_light_index, intersection_id)|{
-            let changed = self.get_car_mut(*car_id).unwrap().notify(usize::from(*main_light_index), new_lights.get(intersection_id).unwrap());
-            if !changed{
-                self.get_intersection_mut(*intersection_id).unwrap().light_queues[usize::from(main_light_index+2)%4].push_front(*car_id);
+            if let Some(saturation) = &saturation{
+                for (shown, (old, new)) in intersection.shown_for.iter_mut().zip(old.iter().zip(new.iter())){
+                    shown[0] = if old.main_status == new.main_status {shown[0] + saturation.tick_seconds} else {0.0};
+                    shown[1] = if old.left_turn_status == new.left_turn_status {shown[1] + saturation.tick_seconds} else {0.0};
+                }
             }
-        })
-
-
+            let yellow = intersection.signal_plan.as_ref().map_or(1, |plan| plan.yellow.max(1)) as f64 * saturation.map_or(1.0, |saturation| saturation.tick_seconds);
+            for side in 0..4{
+                //the queue only builds up what it may let out once a tick
+                let mut filled = false;
+                while let Some(car_id) = intersection.light_queues[side].front().copied(){
+                    let car = &mut self.cars[self.car_index[&car_id]];
+                    let main_light_index = (side+2)%4;
+                    let exit = car.next_exit(*id, side as u8);
+                    let status = car.light_for(new, main_light_index);
+                    let shown_for = intersection.shown_for[main_light_index][usize::from(matches!(car.intention, Direction::Left | Direction::UTurn))];
+                    let share = saturation.map_or(1.0, |saturation| saturation.green_share(status, shown_for, yellow));
+                    let mut entry = if Self::exit_blocked(&self.road, full, *id, exit) {Entry::Stop}
+                        else {Self::permitted(car, intersection, side as u8, gaps, self.non_compliance.as_ref())};
+                    //queues discharging at the saturation flow keep going through the effective green of the yellow,
+                    // unless the driver has decided for itself
+                    if saturation.is_some() && car.driver.is_none() && status == LightStatus::Yellow && share > 0.0 && !Self::exit_blocked(&self.road, full, *id, exit){
+                        entry = Entry::Legal;
+                    }
+                    match entry{
+                        Entry::Stop => {
+                            intersection.discharge[side] = 0.0;
+                            car.wait_time += 1;
+                            break;
+                        },
+                        Entry::Against(_) => {
+                            intersection.discharge[side] = 0.0;
+                            intersection.light_queues[side].pop_front();
+                            car.at_intersection = false;
+                            car.reaction_left = None;
+                            entries.push((car_id, *id, side as u8, exit, entry));
+                            break;
+                        },
+                        Entry::Legal => ()
+                    }
+                    let onset = car.light_for(&old, main_light_index) != LightStatus::Green && status == LightStatus::Green;
+                    if let Some(driver) = car.driver.filter(|_| onset && car.reaction_left.is_none()){
+                        car.reaction_left = Some(driver.params().reaction_ticks);
+                    }
+                    if let Some(left) = car.reaction_left.as_mut().filter(|left| **left > 0){
+                        *left -= 1;
+                        car.wait_time += 1;
+                        break;
+                    }
+                    if !filled{
+                        let lanes = lanes.get(&(*id, side as u8)).copied().unwrap_or(1);
+                        intersection.discharge[side] += saturation.map_or(1.0, |saturation| saturation.per_tick(lanes) * share);
+                        filled = true;
+                    }
+                    let pce = car.class.params().pce;
+                    if intersection.discharge[side] < pce{
+                        car.wait_time += 1;
+                        break;
+                    }
+                    intersection.discharge[side] = if saturation.is_some() {intersection.discharge[side] - pce} else {0.0};
+                    intersection.light_queues[side].pop_front();
+                    car.at_intersection = false;
+                    car.reaction_left = None;
+                    entries.push((car_id, *id, side as u8, exit, entry));
+                    if saturation.is_none(){
+                        break;
+                    }
+                }
+                if intersection.light_queues[side].is_empty(){
+                    intersection.discharge[side] = 0.0;
+                }
+            }
+        }
+        for (car_id, int_id, side, exit, entry) in entries{
+            self.record_entry(car_id, int_id, side, exit, entry);
+        }
     }
 
     
 
-    fn get_car_mut(&mut self, id:u8) -> Option<&mut Car>
+    pub(crate) fn get_car_mut(&mut self, id:CarId) -> Option<&mut Car>
     {
-        self.cars.iter_mut().find(|car| car.id == id)
+        let index = *self.car_index.get(&id)?;
+        self.cars.get_mut(index)
     }
     
-    fn get_car(&self, id:u8) -> Option<&Car>{
-        self.cars.iter().find(|car| car.id==id)
+    fn get_car(&self, id:CarId) -> Option<&Car>{
+        self.cars.get(*self.car_index.get(&id)?)
     }
 
-    fn get_intersection_mut(&mut self, id:u8) -> Option<&mut Intersection>
+    fn get_intersection_mut(&mut self, id:IntersectionId) -> Option<&mut Intersection>
     {
-        self.intersections.iter_mut().find(|int| int.id == id)
+        self.intersections.get_mut(id.index())
     }
 
-    fn get_intersection(&self, id:u8) -> Option<&Intersection>{
-        self.intersections.iter().find(|int| int.id == id)
+    fn get_intersection(&self, id:IntersectionId) -> Option<&Intersection>{
+        self.intersections.get(id.index())
     }
 
-    fn get_pos_mut(&mut self, car_id:u8)-> Option<&mut Position>
+    fn get_pos_mut(&mut self, car_id:CarId)-> Option<&mut Position>
     {
         self.car_positions.get_mut(&car_id)
     }
 
     fn play_timestep(&mut self)
     {
-        self.tick_lights(self.create_random_lights());
-        self.tick_cars();
+        self.spawn_departures();
+        self.dispatch_buses();
+        self.tick_demand();
+        let gaps = if self.cars.iter().any(|car| car.driver.is_some()) {self.approach_gaps()} else {HashMap::new()};
+        let full = self.full_links();
+        self.tick_lights(self.create_random_lights(), &gaps, &full);
+        self.tick_idm(&full);
+        self.tick_cars(&gaps, &full);
     }
 
 
@@ -0,0 +1,115 @@
+use crate::simulator::Simulator;
+use crate::traffic_logic::intersection::{IntersectionId, LightStatus};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+///How queues at the lights discharge, as in the Highway Capacity Manual. A queue lets vehicles out at the
+/// saturation flow rate of its lanes, but only for the effective green: the green and yellow less the
+/// start-up lost time while the first drivers react and speed up and the clearance lost time at the end
+/// of the yellow that drivers do not use.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaturationFlow
+{
+    ///The number of seconds a tick lasts
+    pub tick_seconds : f64,
+    ///The passenger cars a lane lets out in an hour of effective green
+    pub rate : f64,
+    ///The seconds at the start of a green no vehicles leave in
+    pub start_up_lost_time : f64,
+    ///The seconds at the end of a yellow no vehicles leave in
+    pub clearance_lost_time : f64
+}
+
+impl Default for SaturationFlow
+{
+    fn default() -> Self {
+        SaturationFlow{tick_seconds: 1.0, rate: 1900.0, start_up_lost_time: 2.0, clearance_lost_time: 2.0}
+    }
+}
+
+impl SaturationFlow
+{
+    ///The share of a tick that is effective green for a light
+    /// # Parameters
+    /// * `status` : `LightStatus` - What the light shows on the tick
+    /// * `shown_for` : `f64` - The seconds the light had shown it before the tick
+    /// * `yellow` : `f64` - The seconds the yellow lasts
+    /// # Returns
+    /// `f64` : Between 0 and 1, and 1 on red for the turns drivers make in gaps in the traffic
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::simulator::saturation::SaturationFlow;
+    /// use traffic_sim::traffic_logic::intersection::LightStatus;
+    /// let flow = SaturationFlow::default();
+    /// //the first two seconds of green are lost
+    /// assert_eq!(flow.green_share(LightStatus::Green, 1.0, 3.0), 0.0);
+    /// assert_eq!(flow.green_share(LightStatus::Green, 2.0, 3.0), 1.0);
+    /// //and so are the last two seconds of a three second yellow
+    /// assert_eq!(flow.green_share(LightStatus::Yellow, 0.0, 3.0), 1.0);
+    /// assert_eq!(flow.green_share(LightStatus::Yellow, 1.0, 3.0), 0.0);
+    /// ```
+    pub fn green_share(&self, status : LightStatus, shown_for : f64, yellow : f64) -> f64
+    {
+        let share = match status{
+            LightStatus::Green => shown_for + self.tick_seconds - self.start_up_lost_time,
+            LightStatus::Yellow => yellow - self.clearance_lost_time - shown_for,
+            LightStatus::Red => self.tick_seconds
+        };
+        share.clamp(0.0, self.tick_seconds) / self.tick_seconds
+    }
+
+    ///The vehicles an approach lets out in an hour, the saturation flow of its lanes times its share of the cycle that is effective green
+    /// # Parameters
+    /// * `green` : `f64` - The seconds of green in a cycle, not counting the yellow
+    /// * `yellow` : `f64` - The seconds of yellow that follow it
+    /// * `cycle` : `f64` - The seconds the cycle lasts
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::simulator::saturation::SaturationFlow;
+    /// let flow = SaturationFlow::default();
+    /// //27 seconds of green and 3 of yellow give 26 seconds of effective green
+    /// assert!((flow.capacity(27.0, 3.0, 80.0, 1) - 1900.0 * 26.0 / 80.0).abs() < 1e-9);
+    /// ```
+    pub fn capacity(&self, green : f64, yellow : f64, cycle : f64, lanes : u8) -> f64
+    {
+        let effective = (green + yellow - self.start_up_lost_time - self.clearance_lost_time).max(0.0);
+        self.rate * f64::from(lanes) * effective / cycle
+    }
+
+    ///The passenger cars the lanes of an approach let out in a whole tick of effective green
+    pub(crate) fn per_tick(&self, lanes : u8) -> f64
+    {
+        self.rate * f64::from(lanes) * self.tick_seconds / 3600.0
+    }
+}
+
+impl Simulator
+{
+    ///Lets queues at the lights discharge several vehicles a tick at a saturation flow rate, `None` goes back to one vehicle a tick.
+    /// Cars in the microscopic mode do not queue at the lights and follow the car ahead of them instead. The time every light
+    /// has shown what it shows and what every queue has built up towards its next car start again from nothing.
+    pub fn set_saturation_flow(&mut self, saturation : Option<SaturationFlow>)
+    {
+        self.saturation = saturation;
+        for intersection in self.intersections.iter_mut(){
+            intersection.shown_for = [[0.0; 2]; 4];
+            intersection.discharge = [0.0; 4];
+        }
+    }
+
+    pub fn saturation_flow(&self) -> Option<&SaturationFlow>
+    {
+        self.saturation.as_ref()
+    }
+
+    ///The lanes of the road arriving at every side of every intersection
+    pub(crate) fn approach_lanes(&self) -> HashMap<(IntersectionId, u8), u8>
+    {
+        self.road.road.values()
+            .flat_map(|nodes| nodes.iter().flatten())
+            .map(|node| ((node.dest_int_id, node.direction), node.lanes.max(1)))
+            .collect()
+    }
+}
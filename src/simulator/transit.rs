@@ -0,0 +1,344 @@
+use crate::simulator::{Simulator, Current, Between, demand::poisson};
+use crate::traffic_logic::{car::{Car, CarId}, intersection::IntersectionId, route::Route, vehicle::VehicleClass};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+///A bus is on time if it arrives at most this many ticks before its scheduled time
+const ON_TIME_EARLY : i64 = 60;
+///A bus is on time if it arrives at most this many ticks after its scheduled time
+const ON_TIME_LATE : i64 = 300;
+///Buses arriving at a stop within this share of the scheduled headway after the bus ahead are bunched
+const BUNCHED_HEADWAY : f64 = 0.5;
+
+///When the buses of a line set off from its first intersection
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Schedule
+{
+    ///`count` buses, one every `headway` ticks from `first`
+    Headway{first: usize, headway: usize, count: usize},
+    ///A bus on every listed tick
+    Timetable(Vec<usize>)
+}
+
+impl Schedule
+{
+    ///Every tick a bus sets off on, in order
+    pub fn departures(&self) -> Vec<usize>
+    {
+        let mut ticks = match self{
+            Self::Headway{first, headway, count} => (0..*count).map(|run| first + run * headway).collect(),
+            Self::Timetable(ticks) => ticks.clone()
+        };
+        ticks.sort();
+        ticks
+    }
+}
+
+///A place on a line where buses stop to let passengers on
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stop
+{
+    ///The road the stop is on, `0` is the road from the first intersection of the line to the second
+    pub segment : usize,
+    ///How far before the end of the road the stop is, in ticks
+    pub ticks_before_end : u32,
+    ///The passengers that arrive at the stop an hour, a tick is taken to be a second long
+    pub passengers_per_hour : f64
+}
+
+///A bus line, a fixed sequence of intersections its buses drive through
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransitLine
+{
+    pub name : String,
+    ///Every intersection the line passes in order, each connected to the next by a road
+    pub intersections : Vec<IntersectionId>,
+    pub stops : Vec<Stop>,
+    pub schedule : Schedule,
+    ///The ticks a bus stands at every stop to open and close its doors
+    pub dead_time : usize,
+    ///The ticks each passenger takes to board
+    pub boarding_ticks : f64
+}
+
+impl TransitLine
+{
+    pub fn new(name : &str, intersections : Vec<IntersectionId>, schedule : Schedule) -> TransitLine
+    {
+        TransitLine{name: name.to_string(), intersections, stops: Vec::new(), schedule, dead_time: 5, boarding_ticks: 2.0}
+    }
+
+    ///Adds a stop on the road leaving the `segment`th intersection of the line
+    pub fn stop(mut self, segment : usize, ticks_before_end : u32, passengers_per_hour : f64) -> TransitLine
+    {
+        self.stops.push(Stop{segment, ticks_before_end: ticks_before_end.max(1), passengers_per_hour});
+        self
+    }
+
+    ///The ticks a bus is planned to stand at a stop, if it is on time
+    fn planned_dwell(&self, stop : &Stop, headway : f64) -> f64
+    {
+        self.dead_time as f64 + self.boarding_ticks * stop.passengers_per_hour / 3600.0 * headway
+    }
+}
+
+///A bus arriving at and leaving a stop
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StopEvent
+{
+    ///The index of the line in the order lines were added
+    pub line : usize,
+    ///Which bus of the line it is, in the order of the schedule
+    pub run : usize,
+    pub stop : usize,
+    pub car_id : CarId,
+    pub scheduled_arrival : usize,
+    pub arrival_tick : usize,
+    pub departure_tick : usize,
+    pub boarded : usize
+}
+
+impl StopEvent
+{
+    ///The ticks the bus arrived after its scheduled time, negative if it was early
+    pub fn delay(&self) -> i64
+    {
+        self.arrival_tick as i64 - self.scheduled_arrival as i64
+    }
+}
+
+///How well the buses of a line kept to their schedule
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LineMetrics
+{
+    pub name : String,
+    ///The number of buses that have set off
+    pub dispatched : usize,
+    ///The number of times a bus has served a stop
+    pub stop_visits : usize,
+    ///The mean ticks buses arrived at stops after their scheduled time
+    pub mean_delay : f64,
+    ///The share of stop arrivals from a minute early to five minutes late
+    pub on_time_share : f64,
+    ///The mean ticks between buses arriving at the same stop
+    pub mean_headway : f64,
+    ///The standard deviation of the headways divided by their mean, 0 for perfectly regular buses
+    pub headway_cv : f64,
+    ///The share of headways shorter than half the scheduled headway
+    pub bunching_share : f64
+}
+
+///The state of a line during a run
+#[derive(Clone, Debug)]
+pub(crate) struct LineState
+{
+    line : TransitLine,
+    route : Route,
+    ///The ticks every bus sets off on, in order
+    departures : Vec<usize>,
+    ///The mean ticks between buses setting off
+    headway : f64,
+    ///The ticks after setting off a bus is planned to arrive at every stop
+    planned_arrivals : Vec<usize>,
+    ///The tick the last bus left every stop
+    last_departure : Vec<Option<usize>>,
+    ///The number of buses that have set off
+    dispatched : usize
+}
+
+///A bus driving its line
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct BusRun
+{
+    line : usize,
+    run : usize,
+    next_stop : usize,
+    ///The tick the bus arrived at the stop it is standing at, the tick it leaves and the passengers that board
+    dwell : Option<(usize, usize, usize)>
+}
+
+impl BusRun
+{
+    ///Checks if the bus is standing at a stop
+    pub(crate) fn is_dwelling(&self) -> bool
+    {
+        self.dwell.is_some()
+    }
+}
+
+impl Simulator
+{
+    ///Adds a bus line, its buses set off from its first intersection as scheduled and follow it to
+    /// its last intersection. Buses stand at every stop for the dead time of the line plus the time
+    /// the passengers that arrived since the bus ahead left take to board, so a late bus picks up
+    /// more passengers and gets later still, and the bus behind it catches up.
+    /// # Returns
+    /// `Option<usize>` : The index of the line, `None` if two of its intersections in a row are not connected or it has fewer than two
+    pub fn add_transit_line(&mut self, mut line : TransitLine) -> Option<usize>
+    {
+        let mut links = VecDeque::new();
+        for pair in line.intersections.windows(2){
+            let arm = self.road.road.get(&pair[0])?.iter()
+                .position(|node| node.is_some_and(|node| node.dest_int_id == pair[1]))?;
+            links.push_back((pair[0], arm as u8));
+        }
+        if links.is_empty(){
+            return None;
+        }
+        line.stops.retain(|stop| stop.segment < links.len());
+        line.stops.sort_by(|a, b| a.segment.cmp(&b.segment).then(b.ticks_before_end.cmp(&a.ticks_before_end)));
+
+        let departures = line.schedule.departures();
+        let headway = if departures.len() > 1 {(departures[departures.len() - 1] - departures[0]) as f64 / (departures.len() - 1) as f64} else {0.0};
+        //the ticks it takes a bus to reach the end of every road
+        let mut road_ends = Vec::new();
+        let mut elapsed = 0;
+        for (index, (int_id, arm)) in links.iter().enumerate(){
+            let distance = self.road.road[int_id][usize::from(*arm)].map_or(1, |node| node.dist_from_source);
+            elapsed += VehicleClass::Bus.travel_ticks(distance, index == 0) as usize;
+            road_ends.push(elapsed);
+        }
+        let mut dwelled: f64 = 0.0;
+        let mut planned_arrivals = Vec::new();
+        for stop in line.stops.iter(){
+            planned_arrivals.push((road_ends[stop.segment] - (stop.ticks_before_end as usize).min(road_ends[stop.segment])) + dwelled.round() as usize);
+            dwelled += line.planned_dwell(stop, headway);
+        }
+        let cost = links.iter().filter_map(|(int_id, arm)| self.road.road[int_id][usize::from(*arm)]).map(|node| u64::from(node.dist_from_source)).sum();
+        let stop_count = line.stops.len();
+        self.transit_lines.push(LineState{line, route: Route{links, cost}, departures, headway, planned_arrivals, last_departure: vec![None; stop_count], dispatched: 0});
+        Some(self.transit_lines.len() - 1)
+    }
+
+    pub fn transit_lines(&self) -> Vec<&TransitLine>
+    {
+        self.transit_lines.iter().map(|state| &state.line).collect()
+    }
+
+    ///Skips the first buses of a line's schedule, for a run that picks up where another left off
+    pub(crate) fn set_dispatched(&mut self, line : usize, dispatched : usize)
+    {
+        if let Some(state) = self.transit_lines.get_mut(line){
+            state.dispatched = dispatched.min(state.departures.len());
+        }
+    }
+
+    ///Gets the line, the run and the next stop of a bus driving its line
+    pub(crate) fn bus_run(&self, car_id : CarId) -> Option<(usize, usize, usize)>
+    {
+        self.bus_runs.get(&car_id).map(|bus| (bus.line, bus.run, bus.next_stop))
+    }
+
+    ///Makes a car a bus of a line that has already served the stops before `next_stop`
+    /// # Returns
+    /// `bool` : Returns `false` if there is no such line
+    pub(crate) fn resume_bus(&mut self, car_id : CarId, line : usize, run : usize, next_stop : usize) -> bool
+    {
+        if line >= self.transit_lines.len(){
+            return false;
+        }
+        self.bus_runs.insert(car_id, BusRun{line, run, next_stop, dwell: None});
+        true
+    }
+
+    ///Every time a bus has served a stop, in the order the buses left
+    pub fn stop_events(&self) -> &[StopEvent]
+    {
+        &self.stop_events
+    }
+
+    ///Sums up how every line has kept to its schedule so far, in the order lines were added
+    pub fn line_metrics(&self) -> Vec<LineMetrics>
+    {
+        self.transit_lines.iter().enumerate().map(|(index, state)| {
+            let events: Vec<&StopEvent> = self.stop_events.iter().filter(|event| event.line == index).collect();
+            let mut metrics = LineMetrics{name: state.line.name.clone(), dispatched: state.dispatched, stop_visits: events.len(), ..Default::default()};
+            if !events.is_empty(){
+                metrics.mean_delay = events.iter().map(|event| event.delay() as f64).sum::<f64>() / events.len() as f64;
+                metrics.on_time_share = events.iter().filter(|event| (-ON_TIME_EARLY..=ON_TIME_LATE).contains(&event.delay())).count() as f64 / events.len() as f64;
+            }
+            //(actual, scheduled) headway between buses arriving at the same stop one after the other
+            let mut headways = Vec::new();
+            for stop in 0..state.line.stops.len(){
+                let mut arrivals: Vec<(usize, usize)> = events.iter().filter(|event| event.stop == stop).map(|event| (event.arrival_tick, event.run)).collect();
+                arrivals.sort();
+                for pair in arrivals.windows(2){
+                    let scheduled = state.departures[pair[1].1].abs_diff(state.departures[pair[0].1]);
+                    headways.push(((pair[1].0 - pair[0].0) as f64, scheduled as f64));
+                }
+            }
+            if !headways.is_empty(){
+                let count = headways.len() as f64;
+                metrics.mean_headway = headways.iter().map(|(actual, _)| actual).sum::<f64>() / count;
+                let variance = headways.iter().map(|(actual, _)| (actual - metrics.mean_headway).powi(2)).sum::<f64>() / count;
+                metrics.headway_cv = if metrics.mean_headway > 0.0 {variance.sqrt() / metrics.mean_headway} else {0.0};
+                metrics.bunching_share = headways.iter().filter(|(actual, scheduled)| *actual < BUNCHED_HEADWAY * scheduled).count() as f64 / count;
+            }
+            metrics
+        }).collect()
+    }
+
+    ///Sets off the buses scheduled for this tick
+    pub(crate) fn dispatch_buses(&mut self)
+    {
+        for line in 0..self.transit_lines.len(){
+            let state = &self.transit_lines[line];
+            let due = state.departures[state.dispatched..].iter().take_while(|tick| **tick <= self.timestep).count();
+            for _ in 0..due{
+                let state = &self.transit_lines[line];
+                let (first, arm) = state.route.links[0];
+                let destination = *state.line.intersections.last().expect("A line has at least two intersections");
+                let run = state.dispatched;
+                let route = state.route.clone();
+                self.transit_lines[line].dispatched += 1;
+                let Some(car_id) = self.add_trip_on_route(Current{int_id: first, direction: (arm + 2) % 4}, destination, route) else { continue };
+                let car = self.get_car_mut(car_id).expect("Car was just added");
+                car.class = VehicleClass::Bus;
+                if let Some(trip) = &mut car.trip{
+                    trip.reroutes = false;
+                }
+                self.bus_runs.insert(car_id, BusRun{line, run, next_stop: 0, dwell: None});
+            }
+        }
+    }
+
+    ///Holds a bus at the stop it has reached until its passengers have boarded
+    /// # Returns
+    /// `bool` : Returns `true` if the bus stands at a stop this tick
+    pub(crate) fn dwell(lines : &mut [LineState], events : &mut Vec<StopEvent>, bus : &mut BusRun, car : &mut Car, between : &Between, tick : usize) -> bool
+    {
+        let state = &mut lines[bus.line];
+        let Some(stop) = state.line.stops.get(bus.next_stop).copied() else {
+            return false;
+        };
+        let (from, to) = (state.line.intersections[stop.segment], state.line.intersections[stop.segment + 1]);
+        if between.int_1_id != from || between.int_2_id != to || between.distance_to_target > stop.ticks_before_end{
+            return false;
+        }
+        let (arrival, leave, boarded) = *bus.dwell.get_or_insert_with(|| {
+            //the first bus picks up the passengers of one headway
+            let since = state.last_departure[bus.next_stop].map_or(state.headway, |last| (tick - last) as f64);
+            let boarded = poisson(stop.passengers_per_hour / 3600.0 * since);
+            let dwell = state.line.dead_time + (boarded as f64 * state.line.boarding_ticks).ceil() as usize;
+            (tick, tick + dwell, boarded)
+        });
+        if tick < leave{
+            car.wait_time += 1;
+            return true;
+        }
+        state.last_departure[bus.next_stop] = Some(tick);
+        events.push(StopEvent{
+            line: bus.line,
+            run: bus.run,
+            stop: bus.next_stop,
+            car_id: car.id,
+            scheduled_arrival: state.departures[bus.run] + state.planned_arrivals[bus.next_stop],
+            arrival_tick: arrival,
+            departure_tick: tick,
+            boarded
+        });
+        bus.next_stop += 1;
+        bus.dwell = None;
+        false
+    }
+}
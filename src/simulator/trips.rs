@@ -0,0 +1,214 @@
+use crate::simulator::{Simulator, Position, Current, Departure};
+use crate::traffic_logic::{car::{CarId, Direction}, intersection::IntersectionId, road::Road, route::{Route, Trip}, vehicle::VehicleClass, driver::DriverProfile};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+///A trip that has reached its destination
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TripRecord
+{
+    ///The number of the trip, the ID of the car may have been given to a later car
+    pub serial : u64,
+    pub car_id : CarId,
+    pub class : VehicleClass,
+    pub driver : Option<DriverProfile>,
+    pub origin : IntersectionId,
+    pub destination : IntersectionId,
+    ///The tick the car was placed at its origin
+    pub depart_tick : usize,
+    ///The tick the car reached its destination
+    pub arrive_tick : usize,
+    ///The number of ticks the car spent waiting at lights and closed roads
+    pub wait_time : usize,
+    ///The number of ticks the route takes without waiting, when the car set off
+    pub free_flow_time : u64,
+    ///The car found a new route at every intersection from how busy the roads were
+    pub reroutes : bool,
+    ///The number of times the car changed the road it was going to take
+    pub route_changes : usize,
+    ///Every road the car drove, as the intersection and arm it left from and the tick it left
+    pub path : Vec<(IntersectionId, u8, usize)>
+}
+
+impl TripRecord
+{
+    pub fn travel_time(&self) -> usize
+    {
+        self.arrive_tick - self.depart_tick
+    }
+}
+
+///How the vehicles of one class are doing
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClassMetrics
+{
+    ///The number of vehicles of the class in the simulation now
+    pub vehicles : usize,
+    ///The number of trips by the class that have reached their destination
+    pub completed_trips : usize,
+    ///The mean travel time of the completed trips
+    pub mean_travel_time : f64,
+    ///The mean ticks spent waiting, by the completed trips and the vehicles still in the simulation
+    pub mean_wait_time : f64
+}
+
+impl Simulator
+{
+    ///Places a car that drives the shortest route to a destination and leaves the simulation once it gets there
+    /// # Parameters
+    /// * `at` : `Current` - The intersection and direction the car starts waiting at
+    /// * `destination` : `IntersectionId` - The intersection the car drives to
+    /// # Returns
+    /// `Option<CarId>` : Returns `None` and places no car if the destination cannot be reached
+    pub fn add_trip(&mut self, at : Current, destination : IntersectionId) -> Option<CarId>
+    {
+        let route = self.road.shortest_path(at.int_id, destination, &self.closed_links())?;
+        self.add_trip_on_route(at, destination, route)
+    }
+
+    ///Places a car that drives a given route to a destination, finding a new route if the next road
+    /// on it is closed or does not leave the intersection the car is at
+    /// # Returns
+    /// `Option<CarId>` : Returns `None` and places no car if the route is empty
+    pub fn add_trip_on_route(&mut self, at : Current, destination : IntersectionId, route : Route) -> Option<CarId>
+    {
+        let first_arm = route.links.front()?.1;
+        let car_id = self.add_car(Position::new(Some(at), None));
+        let serial = self.next_trip_serial();
+        let depart_tick = self.timestep;
+        let reroutes = self.reroute_share > 0.0 && rand::random::<f64>() < self.reroute_share;
+        let car = self.get_car_mut(car_id).expect("Car was just added");
+        car.intention = Direction::toward(at.direction, first_arm);
+        car.trip = Some(Trip{serial, origin: at.int_id, destination, depart_tick, free_flow_time: route.cost, route, reroutes, route_changes: 0, path: Vec::new()});
+        Some(car_id)
+    }
+
+    ///Sets the share of cars starting a trip from now on that find a new route at every intersection,
+    /// using `live_link_costs` instead of the distance of each road
+    /// # Parameters
+    /// * `share` : `f64` - Between 0, no car reroutes, and 1, every car reroutes
+    pub fn set_reroute_share(&mut self, share : f64)
+    {
+        self.reroute_share = share.clamp(0.0, 1.0);
+    }
+
+    pub fn reroute_share(&self) -> f64
+    {
+        self.reroute_share
+    }
+
+    ///Sets how many ticks each passenger car space taken up on a road adds to the time it takes to
+    /// drive it in `live_link_costs`, 2 by default
+    /// # Parameters
+    /// * `ticks` : `f64` - The delay per car ahead, 0 makes rerouting cars take the shortest route
+    pub fn set_queue_delay(&mut self, ticks : f64)
+    {
+        self.queue_delay = ticks.max(0.0);
+    }
+
+    pub fn queue_delay(&self) -> f64
+    {
+        self.queue_delay
+    }
+
+    ///Estimates how many ticks it takes to drive every road on the current tick. Every car already
+    /// driving on the road or queued at its end adds `queue_delay` ticks to its distance for every
+    /// passenger car space it takes up, as it has to get through the intersection first.
+    /// # Returns
+    /// `HashMap<(IntersectionId, u8), u64>` : The ticks keyed by the intersection and arm the road leaves from
+    pub fn live_link_costs(&self) -> HashMap<(IntersectionId, u8), u64>
+    {
+        let occupancy = self.link_occupancy();
+        let mut costs = HashMap::new();
+        for (from, arms) in self.road.road.iter(){
+            for (arm, node) in arms.iter().enumerate(){
+                let Some(node) = node else { continue };
+                let ahead = occupancy.get(&(*from, arm as u8)).copied().unwrap_or(0.0);
+                let delay = (ahead * self.queue_delay).ceil() as u64;
+                costs.insert((*from, arm as u8), u64::from(node.dist_from_source) + delay);
+            }
+        }
+        costs
+    }
+
+    ///Finds the quickest route from an intersection using the live cost of every road
+    pub(crate) fn live_route(road : &Road, closed : &HashSet<(IntersectionId, u8)>, costs : &HashMap<(IntersectionId, u8), u64>, from : IntersectionId, to : IntersectionId) -> Option<Route>
+    {
+        road.shortest_path_by(from, to, |int_id, arm, node| {
+            if closed.contains(&(int_id, arm)){
+                return None;
+            }
+            Some(costs.get(&(int_id, arm)).copied().unwrap_or(u64::from(node.dist_from_source)))
+        })
+    }
+
+    ///Schedules a car to start a trip on a later tick, see `add_trip`
+    /// # Parameters
+    /// * `tick` : `usize` - The timestep the car appears on, cars scheduled in the past appear on the next tick
+    pub fn schedule_trip(&mut self, tick : usize, at : Current, destination : IntersectionId)
+    {
+        let index = self.departures.partition_point(|departure| departure.tick <= tick);
+        self.departures.insert(index, Departure{tick, at, destination: Some(destination), route: None});
+    }
+
+    ///Schedules a car to start a trip on a given route on a later tick, see `add_trip_on_route`
+    pub fn schedule_trip_on_route(&mut self, tick : usize, at : Current, destination : IntersectionId, route : Route)
+    {
+        let index = self.departures.partition_point(|departure| departure.tick <= tick);
+        self.departures.insert(index, Departure{tick, at, destination: Some(destination), route: Some(route)});
+    }
+
+    pub fn completed_trips(&self) -> &[TripRecord]
+    {
+        &self.completed_trips
+    }
+
+    ///Breaks the completed trips and the vehicles still in the simulation down by vehicle class,
+    /// classes with neither are left out
+    pub fn class_metrics(&self) -> BTreeMap<VehicleClass, ClassMetrics>
+    {
+        //class -> (metrics, total travel time, total wait time)
+        let mut totals: BTreeMap<VehicleClass, (ClassMetrics, usize, usize)> = BTreeMap::new();
+        for record in self.completed_trips.iter(){
+            let total = totals.entry(record.class).or_default();
+            total.0.completed_trips += 1;
+            total.1 += record.travel_time();
+            total.2 += record.wait_time;
+        }
+        for car in self.cars.iter(){
+            let total = totals.entry(car.class).or_default();
+            total.0.vehicles += 1;
+            total.2 += car.wait_time;
+        }
+        totals.into_iter().map(|(class, (mut metrics, travel, wait))| {
+            if metrics.completed_trips > 0{
+                metrics.mean_travel_time = travel as f64 / metrics.completed_trips as f64;
+            }
+            metrics.mean_wait_time = wait as f64 / (metrics.completed_trips + metrics.vehicles) as f64;
+            (class, metrics)
+        }).collect()
+    }
+
+    ///Records the trip of a car that has reached its destination and takes the car out of the simulation
+    pub(crate) fn finish_trip(&mut self, car_id : CarId)
+    {
+        let Some(car) = self.get_car(car_id) else { return };
+        if let Some(trip) = &car.trip{
+            self.completed_trips.push(TripRecord{
+                serial: trip.serial,
+                car_id,
+                class: car.class,
+                driver: car.driver,
+                origin: trip.origin,
+                destination: trip.destination,
+                depart_tick: trip.depart_tick,
+                arrive_tick: self.timestep,
+                wait_time: car.wait_time,
+                free_flow_time: trip.free_flow_time,
+                reroutes: trip.reroutes,
+                route_changes: trip.route_changes,
+                path: trip.path.clone()
+            });
+        }
+        self.remove_car(car_id);
+    }
+}
@@ -0,0 +1,208 @@
+use crate::simulator::Simulator;
+use crate::traffic_logic::{car::{Car, CarId}, driver::DriverProfile, intersection::{IntersectionId, LightStatus}};
+use std::collections::{HashMap, VecDeque};
+
+///The ticks a car that has entered an intersection takes to clear it, cars entering within this many
+/// ticks of each other on crossing paths are in conflict
+const CLEARANCE_TICKS : usize = 2;
+
+///How a car broke the signals
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ViolationKind
+{
+    ///Entered on a red light
+    Red,
+    ///Entered on a yellow light the driver would have stopped for
+    LateYellow
+}
+
+///The chance drivers enter an intersection when their light tells them to stop
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NonCompliance
+{
+    ///The chance a driver of each profile enters on red each tick it waits at the stop line, `None` for drivers without a profile
+    pub red : HashMap<Option<DriverProfile>, f64>,
+    ///The chance a driver of each profile enters on a yellow it would have stopped for
+    pub late_yellow : HashMap<Option<DriverProfile>, f64>,
+    ///Multiplies the chances at one side of an intersection, for approaches where drivers run the lights more or less often
+    pub approach_factors : HashMap<(IntersectionId, u8), f64>
+}
+
+impl NonCompliance
+{
+    ///The chance a driver enters against the light at one side of an intersection
+    pub fn chance(&self, kind : ViolationKind, driver : Option<DriverProfile>, int_id : IntersectionId, side : u8) -> f64
+    {
+        let chances = match kind{
+            ViolationKind::Red => &self.red,
+            ViolationKind::LateYellow => &self.late_yellow
+        };
+        let factor = self.approach_factors.get(&(int_id, side)).copied().unwrap_or(1.0);
+        (chances.get(&driver).copied().unwrap_or(0.0) * factor).clamp(0.0, 1.0)
+    }
+}
+
+///A car entering an intersection against its light
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation
+{
+    pub tick : usize,
+    pub int_id : IntersectionId,
+    ///The side the car entered from
+    pub side : u8,
+    ///The arm the car left by
+    pub exit : u8,
+    pub car_id : CarId,
+    pub driver : Option<DriverProfile>,
+    pub kind : ViolationKind,
+    ///The cars that entered legally on a path crossing or merging with the violator's while it was in the intersection
+    pub conflicts : Vec<CarId>
+}
+
+///How a car at the stop line of an intersection acts on its light
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Entry
+{
+    Stop,
+    Legal,
+    Against(ViolationKind)
+}
+
+///A car entering an intersection, kept until it has cleared it
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct EntryEvent
+{
+    tick : usize,
+    int_id : IntersectionId,
+    side : u8,
+    exit : u8,
+    car_id : CarId,
+    ///The index of the violation in the log, `None` if the car entered legally
+    violation : Option<usize>
+}
+
+///Checks if two movements through an intersection cross or merge. A movement is a chord between the
+/// arm it enters from and the arm it leaves by, two chords cross if exactly one end of one lies
+/// between the ends of the other going round the intersection. Movements whose chords share one end
+/// only keep clear of each other if one of them is a right turn, which stays in the corner, and
+/// opposing movements along the same chord pass each other.
+/// # Examples
+///
+/// ```rust
+/// use traffic_sim::simulator::violations::movements_conflict;
+/// //north to south through and east to west through cross
+/// assert!(movements_conflict((0, 2), (1, 3)));
+/// //two right turns from opposite sides do not
+/// assert!(!movements_conflict((0, 3), (2, 1)));
+/// //entering the same road merges
+/// assert!(movements_conflict((0, 2), (1, 2)));
+/// //a left turn from the south crosses the north to south through it turns in front of
+/// assert!(movements_conflict((0, 2), (2, 3)));
+/// //a right turn from the south does not, nor does the south to north through
+/// assert!(!movements_conflict((0, 2), (2, 1)));
+/// assert!(!movements_conflict((0, 2), (2, 0)));
+/// ```
+pub fn movements_conflict(a : (u8, u8), b : (u8, u8)) -> bool
+{
+    if a.0 == b.0{
+        return false;
+    }
+    if a.1 == b.1{
+        return true;
+    }
+    let between = |point : u8| {
+        let span = (a.1 + 4 - a.0) % 4;
+        let offset = (point + 4 - a.0) % 4;
+        offset > 0 && offset < span
+    };
+    if b == (a.1, a.0){
+        return false;
+    }
+    if b.0 == a.1 || b.1 == a.0{
+        let right_turn = |movement : (u8, u8)| movement.1 == (movement.0 + 3) % 4;
+        return !right_turn(a) && !right_turn(b);
+    }
+    between(b.0) != between(b.1)
+}
+
+impl Simulator
+{
+    ///Sets how often drivers enter against their light, replacing the chances set before
+    pub fn set_non_compliance(&mut self, non_compliance : NonCompliance)
+    {
+        self.non_compliance = Some(non_compliance);
+    }
+
+    pub fn non_compliance(&self) -> Option<&NonCompliance>
+    {
+        self.non_compliance.as_ref()
+    }
+
+    ///Every car that has entered an intersection against its light, in the order they entered
+    pub fn violations(&self) -> &[Violation]
+    {
+        &self.violations
+    }
+
+    ///Decides if a driver that may not go enters anyway
+    pub(crate) fn runs_light(non_compliance : Option<&NonCompliance>, car : &Car, int_id : IntersectionId, status : LightStatus, side : u8) -> Entry
+    {
+        let Some(non_compliance) = non_compliance else {
+            return Entry::Stop;
+        };
+        let kind = match status{
+            LightStatus::Red => ViolationKind::Red,
+            LightStatus::Yellow => ViolationKind::LateYellow,
+            LightStatus::Green => return Entry::Stop
+        };
+        if rand::random::<f64>() < non_compliance.chance(kind, car.driver, int_id, side){
+            Entry::Against(kind)
+        }
+        else {
+            Entry::Stop
+        }
+    }
+
+    ///Notes a car entering an intersection, logging a violation if it went against its light
+    pub(crate) fn record_entry(&mut self, car_id : CarId, int_id : IntersectionId, side : u8, exit : u8, entry : Entry)
+    {
+        let violation = match entry{
+            Entry::Stop => return,
+            Entry::Legal => None,
+            Entry::Against(kind) => {
+                let driver = self.get_car(car_id).and_then(|car| car.driver);
+                self.violations.push(Violation{tick: self.timestep, int_id, side, exit, car_id, driver, kind, conflicts: Vec::new()});
+                Some(self.violations.len() - 1)
+            }
+        };
+        self.recent_entries.push_back(EntryEvent{tick: self.timestep, int_id, side, exit, car_id, violation});
+    }
+
+    ///Flags the cars that entered this tick on a path conflicting with a violator still in the intersection,
+    /// and the violators that entered this tick into a conflicting car's path
+    pub(crate) fn check_conflicts(&mut self)
+    {
+        let tick = self.timestep;
+        while self.recent_entries.front().is_some_and(|entry| entry.tick + CLEARANCE_TICKS < tick){
+            self.recent_entries.pop_front();
+        }
+        let recent: &VecDeque<EntryEvent> = &self.recent_entries;
+        let mut found = Vec::new();
+        for (index, new) in recent.iter().enumerate().filter(|(_, entry)| entry.tick == tick){
+            //pairs of entries on this tick are only looked at once
+            for other in recent.iter().take(index){
+                if new.int_id != other.int_id || new.car_id == other.car_id || !movements_conflict((new.side, new.exit), (other.side, other.exit)){
+                    continue;
+                }
+                match (new.violation, other.violation){
+                    (Some(violation), None) => found.push((violation, other.car_id)),
+                    (None, Some(violation)) => found.push((violation, new.car_id)),
+                    _ => ()
+                }
+            }
+        }
+        for (violation, car_id) in found{
+            self.violations[violation].conflicts.push(car_id);
+        }
+    }
+}
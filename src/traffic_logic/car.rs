@@ -1,7 +1,26 @@
 use rand::{Rng, prelude::Distribution, distributions::Standard};
 
-use crate::traffic_logic::intersection::{TrafficLight, LightStatus};
-use std::hash::Hash;
+use crate::traffic_logic::{intersection::{IntersectionId, TrafficLight, LightStatus}, route::Trip, vehicle::VehicleClass, driver::DriverProfile};
+use serde::{Deserialize, Serialize};
+use std::{hash::Hash, fmt::Display};
+
+///The ID of a car, the IDs of cars that leave the simulation are given to new cars
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CarId(pub u32);
+
+impl From<u32> for CarId
+{
+    fn from(id: u32) -> CarId {
+        CarId(id)
+    }
+}
+
+impl Display for CarId{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
 
 #[derive(Clone, Copy)]
 ///Enum to represent directions a car can drive at an intersection
@@ -25,6 +44,68 @@ impl Distribution<Direction> for Standard {
     }
 }
 
+///How often cars waiting at one side of an intersection make each turn, from a turning movement count.
+/// The shares are weights and do not need to add up to one.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TurningProportions
+{
+    pub left : f64,
+    pub straight : f64,
+    pub right : f64,
+    #[serde(default)]
+    pub u_turn : f64
+}
+
+impl TurningProportions
+{
+    pub fn new(left : f64, straight : f64, right : f64, u_turn : f64) -> TurningProportions
+    {
+        TurningProportions{left, straight, right, u_turn}
+    }
+
+    pub fn share(&self, turn : Direction) -> f64
+    {
+        match turn{
+            Direction::Left => self.left,
+            Direction::Straight => self.straight,
+            Direction::Right => self.right,
+            Direction::UTurn => self.u_turn
+        }
+    }
+
+    ///Keeps the shares of the turns that are allowed
+    /// # Returns
+    /// `Option<TurningProportions>` : Returns `None` if none of the allowed turns are ever made
+    pub fn only(&self, allowed : impl Fn(Direction) -> bool) -> Option<TurningProportions>
+    {
+        let keep = |turn : Direction| if allowed(turn) {self.share(turn).max(0.0)} else {0.0};
+        let kept = TurningProportions::new(keep(Direction::Left), keep(Direction::Straight), keep(Direction::Right), keep(Direction::UTurn));
+        (kept.total() > 0.0).then_some(kept)
+    }
+
+    fn total(&self) -> f64
+    {
+        self.left.max(0.0) + self.straight.max(0.0) + self.right.max(0.0) + self.u_turn.max(0.0)
+    }
+}
+
+impl Distribution<Direction> for TurningProportions {
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Direction {
+        let total = self.total();
+        if total <= 0.0{
+            return Standard.sample(rng);
+        }
+        let mut pick = rng.gen_range(0.0..total);
+        for turn in [Direction::Left, Direction::Straight, Direction::Right]{
+            let share = self.share(turn).max(0.0);
+            if pick < share{
+                return turn;
+            }
+            pick -= share;
+        }
+        Direction::UTurn
+    }
+}
 
 impl Direction
 {
@@ -57,14 +138,47 @@ impl Direction
             _ => source
         }
     }
+
+    ///Gets the turn a car makes to leave an intersection by an arm, the opposite of `get_next_direction`
+    /// # Parameters
+    /// - `source` : `u8` - the cardinal direction in which the car is sitting relative to the intersection
+    /// - `next` : `u8` - the cardinal direction of the arm the car leaves by
+    ///
+    ///# Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::traffic_logic::car::Direction;
+    /// let turn = Direction::toward(3, 0);
+    /// assert_eq!(Direction::get_next_direction(3, turn), 0);
+    /// ```
+    pub fn toward(source : u8, next : u8) -> Self
+    {
+        match (next + 4 - source) % 4{
+            1 => Self::Left,
+            3 => Self::Right,
+            2 => Self::Straight,
+            _ => Self::UTurn
+        }
+    }
 }
 
 pub struct Car
 {
-    pub id : u8,
+    pub id : CarId,
     pub wait_time : usize,
     pub intention : Direction,
-    pub at_intersection:bool
+    pub at_intersection:bool,
+    ///Where the car is driving to, cars without a trip turn at random
+    pub trip : Option<Trip>,
+    pub class : VehicleClass,
+    ///How the driver behaves at signals, drivers without a profile only go on green and never turn on red
+    pub driver : Option<DriverProfile>,
+    ///The ticks the driver still needs to set off after its light turned green while it was waiting
+    /// at the front of the queue, `None` until that happens
+    pub reaction_left : Option<usize>,
+    ///Whether the driver goes through the yellow light it faces, decided once when it sees the light
+    /// turn yellow, `None` while its light is not yellow
+    pub yellow_decision : Option<bool>
 }
 
 impl Eq for Car {}
@@ -91,19 +205,25 @@ impl Hash for Car
 impl Car
 {
 
-    pub fn new(id : u8) -> Car
+    pub fn new(id : CarId) -> Car
     {
-        Car { id, wait_time: 0, intention: Direction::Straight, at_intersection: true }
+        Car { id, wait_time: 0, intention: Direction::Straight, at_intersection: true, trip: None, class: VehicleClass::PassengerCar, driver: None, reaction_left: None, yellow_decision: None }
     }
 
-    pub fn randomize_intent(&mut self){
-        self.intention = rand::random();
+    ///Picks the turn the car makes next
+    /// # Parameters
+    /// * `turning` : `Option<&TurningProportions>` - How often cars make each turn from the side the car waits at, every turn is as likely without it
+    pub fn randomize_intent(&mut self, turning : Option<&TurningProportions>){
+        self.intention = match turning{
+            Some(turning) => rand::thread_rng().sample(turning),
+            None => rand::random()
+        };
     }
 
     pub fn notify(&mut self, main_light_index : usize, lights : &[TrafficLight;4]) -> bool
     {
-        
-        if self.can_go(lights, main_light_index) == true
+        self.see_light(lights, main_light_index);
+        if self.can_go(lights, main_light_index)
         {
             self.at_intersection = false;
             true
@@ -115,24 +235,48 @@ impl Car
         
     }
 
+    ///Looks at the light for the way the car is turning. When it has turned yellow a driver with a
+    /// profile decides whether to go through it, with the chance of its profile, and keeps to that
+    /// until the light changes again. Drivers without a profile always stop, though with a saturation flow
+    /// their queue keeps leaving in the part of the yellow that is effective green.
+    pub fn see_light(&mut self, lights : &[TrafficLight;4], main_light_index : usize)
+    {
+        self.yellow_decision = match self.light_for(lights, main_light_index){
+            LightStatus::Yellow => Some(self.yellow_decision.unwrap_or_else(|| self.driver.is_some_and(|driver| rand::random::<f64>() < driver.params().yellow_go))),
+            LightStatus::Green | LightStatus::Red => None
+        };
+    }
+
+    ///Checks if the car goes on the light for the way it is turning. Every car goes on green, on
+    /// yellow only a driver that decided to go in `see_light` does.
     pub fn can_go(&self, lights : &[TrafficLight;4], main_light_index : usize) -> bool
+    {
+        match self.light_for(lights, main_light_index)
+        {
+            LightStatus::Green => true,
+            LightStatus::Yellow => self.yellow_decision == Some(true),
+            LightStatus::Red => false
+        }
+    }
+
+    ///Gets the arm the car will leave an intersection by, the next road of its route if it has one
+    pub fn next_exit(&self, int_id : IntersectionId, side : u8) -> u8
+    {
+        match self.trip.as_ref().and_then(|trip| trip.route.links.front()){
+            Some((at, arm)) if *at == int_id => *arm,
+            _ => Direction::get_next_direction(side, self.intention)
+        }
+    }
+
+    ///Gets the light that applies to the way the car is turning, the left arrow for left and U turns
+    pub fn light_for(&self, lights : &[TrafficLight;4], main_light_index : usize) -> LightStatus
     {
         let forward_light = &lights[main_light_index];
         //println!("{:?}", forward_light);
         match self.intention
         {
-            Direction::Left =>{
-                if let LightStatus::Green = forward_light.left_turn_status {true} else {false}
-            },
-            
-            Direction::Straight | Direction::Right => {
-                if let LightStatus::Green = forward_light.main_status {true} else {false} 
-            },
-
-            _ => {
-                if let LightStatus::Green = forward_light.left_turn_status {true} else {false}
-            }
-
+            Direction::Straight | Direction::Right => forward_light.main_status,
+            Direction::Left | Direction::UTurn => forward_light.left_turn_status
         }
     }
     
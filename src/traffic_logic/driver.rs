@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+///How a driver behaves at signals
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Serialize, Deserialize)]
+pub enum DriverProfile
+{
+    Aggressive,
+    Normal,
+    Cautious
+}
+
+///The choices a driver profile makes at signals
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DriverParams
+{
+    ///The chance the driver goes through a yellow light instead of stopping
+    pub yellow_go : f64,
+    ///The fewest ticks before the next conflicting car arrives that the driver turns in front of it,
+    /// for left turns on a green light without a green arrow and right turns on red
+    pub critical_gap : u32,
+    ///The chance the driver turns right on red when there is a gap
+    pub turn_on_red : f64,
+    ///The ticks the driver takes to set off when its light turns green
+    pub reaction_ticks : usize
+}
+
+impl DriverProfile
+{
+    pub const ALL : [DriverProfile; 3] = [Self::Aggressive, Self::Normal, Self::Cautious];
+
+    pub fn params(&self) -> DriverParams
+    {
+        match self{
+            Self::Aggressive => DriverParams{yellow_go: 0.9, critical_gap: 3, turn_on_red: 0.9, reaction_ticks: 0},
+            Self::Normal => DriverParams{yellow_go: 0.4, critical_gap: 5, turn_on_red: 0.5, reaction_ticks: 1},
+            Self::Cautious => DriverParams{yellow_go: 0.05, critical_gap: 7, turn_on_red: 0.1, reaction_ticks: 2}
+        }
+    }
+}
+
+impl Display for DriverProfile
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self{
+            Self::Aggressive => "aggressive",
+            Self::Normal => "normal",
+            Self::Cautious => "cautious"
+        };
+        write!(f, "{}", name)
+    }
+}
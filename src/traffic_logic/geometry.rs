@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque, hash_map::Entry};
+
+///The mean radius of the Earth in metres
+const EARTH_RADIUS_M : f64 = 6_371_000.0;
+
+///A location on the map, either planar x/y coordinates or longitude/latitude
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct Point
+{
+    pub x : f64,
+    pub y : f64
+}
+
+impl Point
+{
+    pub fn new(x : f64, y : f64) -> Point
+    {
+        Point{x, y}
+    }
+
+    pub fn distance(&self, other : &Point) -> f64
+    {
+        ((other.x - self.x).powi(2) + (other.y - self.y).powi(2)).sqrt()
+    }
+
+    ///The compass bearing in degrees from this point to another, 0 is north (+y) and 90 is east (+x)
+    pub fn bearing_to(&self, other : &Point) -> f64
+    {
+        (other.x - self.x).atan2(other.y - self.y).to_degrees().rem_euclid(360.0)
+    }
+
+    ///The distance in metres to another point along the surface of the Earth, both points as longitude (x)
+    /// and latitude (y) in degrees
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::traffic_logic::geometry::Point;
+    /// //a degree of longitude is half as long at 60 degrees north as on the equator
+    /// let equator = Point::new(0.0, 0.0).geo_distance(&Point::new(1.0, 0.0));
+    /// let north = Point::new(0.0, 60.0).geo_distance(&Point::new(1.0, 60.0));
+    /// assert!((equator - 111_195.0).abs() < 1.0);
+    /// assert!((north / equator - 0.5).abs() < 1e-3);
+    /// ```
+    pub fn geo_distance(&self, other : &Point) -> f64
+    {
+        let (lat1, lat2) = (self.y.to_radians(), other.y.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.x - self.x).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+    }
+
+    ///The compass bearing in degrees from this point to another along the surface of the Earth, both points
+    /// as longitude (x) and latitude (y) in degrees, 0 is north and 90 is east
+    pub fn geo_bearing_to(&self, other : &Point) -> f64
+    {
+        let (lat1, lat2) = (self.y.to_radians(), other.y.to_radians());
+        let dlon = (other.x - self.x).to_radians();
+        let y = dlon.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * dlon.cos();
+        y.atan2(x).to_degrees().rem_euclid(360.0)
+    }
+
+    ///Gets the point a fraction of the way to another point
+    pub fn lerp(&self, other : &Point, fraction : f64) -> Point
+    {
+        Point{x: self.x + (other.x - self.x) * fraction, y: self.y + (other.y - self.y) * fraction}
+    }
+}
+
+///The total length of a line through every point in order
+pub fn polyline_length(points : &[Point]) -> f64
+{
+    points.windows(2).map(|pair| pair[0].distance(&pair[1])).sum()
+}
+
+///The total length in metres of a line through every point in order, the points as longitude (x) and latitude (y)
+pub fn geo_polyline_length(points : &[Point]) -> f64
+{
+    points.windows(2).map(|pair| pair[0].geo_distance(&pair[1])).sum()
+}
+
+///Gets the point a fraction of the way along a line through every point in order
+/// # Returns
+/// `Option<Point>` : Returns `None` if there are no points
+pub fn point_along(points : &[Point], fraction : f64) -> Option<Point>
+{
+    let first = *points.first()?;
+    let mut remaining = polyline_length(points) * fraction.clamp(0.0, 1.0);
+    for pair in points.windows(2){
+        let length = pair[0].distance(&pair[1]);
+        if remaining <= length && length > 0.0{
+            return Some(pair[0].lerp(&pair[1], remaining / length));
+        }
+        remaining -= length;
+    }
+    Some(*points.last().unwrap_or(&first))
+}
+
+///Picks the free arm of a junction closest to a bearing
+/// # Parameters
+/// * `arms` : `&HashMap<K, [bool;4]>` - The arms of every junction that are already used
+/// * `node` : `K` - The junction to pick an arm of
+/// * `bearing` : `f64` - The compass bearing the road leaves the junction at
+/// # Returns
+/// `Option<u8>` : Returns `None` if every arm is used
+pub fn pick_arm<K: Eq + std::hash::Hash>(arms: &HashMap<K, [bool;4]>, node: K, bearing: f64) -> Option<u8>
+{
+    let used = arms.get(&node).copied().unwrap_or([false;4]);
+    let mut candidates: Vec<(f64, u8)> = (0..4u8).map(|arm| {
+        let diff = (bearing - f64::from(arm) * 90.0).rem_euclid(360.0);
+        (diff.min(360.0 - diff), arm)
+    }).collect();
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+    candidates.into_iter().map(|(_, arm)| arm).find(|arm| !used[usize::from(*arm)])
+}
+
+///Gets where every node of a network goes on the map.
+///
+/// Nodes are placed at their positions if they all have one. Otherwise the network is laid out by
+/// walking the links from the first node along their arms, each link as long as its length, with every
+/// disconnected part placed to the right of the last.
+/// # Parameters
+/// * `nodes` : `&[(K, Option<Point>)]` - Every node in order with its position
+/// * `links` : `&[(K, u8, K, u8, f64)]` - Every link as the node and arm it leaves from, the node and arm it arrives at and its length
+/// * `gap` : `f64` - The space left between disconnected parts
+pub fn layout<K: Eq + std::hash::Hash + Clone>(nodes: &[(K, Option<Point>)], links: &[(K, u8, K, u8, f64)], gap: f64) -> HashMap<K, Point>
+{
+    if nodes.iter().all(|(_, pos)| pos.is_some()){
+        return nodes.iter().filter_map(|(node, pos)| Some((node.clone(), (*pos)?))).collect();
+    }
+    let offset = |arm: u8, length: f64| match arm{
+        0 => Point::new(0.0, length),
+        1 => Point::new(length, 0.0),
+        2 => Point::new(0.0, -length),
+        _ => Point::new(-length, 0.0)
+    };
+    let mut positions: HashMap<K, Point> = HashMap::new();
+    let mut next_x = 0.0;
+    for (start, _) in nodes.iter(){
+        if positions.contains_key(start){
+            continue;
+        }
+        positions.insert(start.clone(), Point::new(next_x, 0.0));
+        let mut queue = VecDeque::from([start.clone()]);
+        let mut max_x: f64 = next_x;
+        while let Some(current) = queue.pop_front(){
+            let here = positions[&current];
+            max_x = max_x.max(here.x);
+            for (from, from_arm, to, to_arm, length) in links.iter(){
+                let (next, step) = if *from == current{
+                    (to, offset(*from_arm, *length))
+                }
+                else if *to == current{
+                    (from, offset(*to_arm, *length))
+                }
+                else {
+                    continue
+                };
+                if let Entry::Vacant(entry) = positions.entry(next.clone()){
+                    entry.insert(Point::new(here.x + step.x, here.y + step.y));
+                    queue.push_back(next.clone());
+                }
+            }
+        }
+        next_x = max_x + gap;
+    }
+    positions
+}
@@ -3,8 +3,37 @@ use rand::{
     distributions::{Distribution, Standard},
     Rng,
 };
+use crate::traffic_logic::{geometry::Point, car::{CarId, TurningProportions}};
+use serde::{Deserialize, Serialize};
 use std::{hash::Hash, fmt::Display, collections::VecDeque};
 
+///The ID of an intersection, intersections are numbered from 1 in the order they are added
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct IntersectionId(pub u32);
+
+impl IntersectionId
+{
+    ///The place of the intersection in the simulator's list of intersections
+    pub(crate) fn index(self) -> usize
+    {
+        (self.0 as usize).wrapping_sub(1)
+    }
+}
+
+impl From<u32> for IntersectionId
+{
+    fn from(id: u32) -> IntersectionId {
+        IntersectionId(id)
+    }
+}
+
+impl Display for IntersectionId{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 
 
 
@@ -28,7 +57,7 @@ impl Display for LightStatus{
         write!(f,"{}", output)
     }
 }
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum LightConfig{
     NorthSouth,
     EastWest,
@@ -38,6 +67,8 @@ pub enum LightConfig{
     SouthLeft,
     EastLeft,
     WestLeft,
+    ///Every approach may go, used for intersections without signals
+    AllWay,
 
 }
 
@@ -90,6 +121,10 @@ impl LightConfig{
             Self::NorthLeft =>{
                 lights[north].left_turn_status = Green;
                 lights[north].main_status = Green;
+            },
+
+            Self::AllWay =>{
+                lights = [TrafficLight{main_status:Green, left_turn_status:Green};4];
             }
         };
         lights
@@ -97,6 +132,123 @@ impl LightConfig{
     }
 }
 
+///One step of a fixed time signal plan
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct Phase
+{
+    pub config: LightConfig,
+    ///The number of ticks the phase is shown for
+    pub duration: usize
+}
+
+///A fixed time signal plan that cycles through its phases in order
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct SignalPlan
+{
+    pub phases: Vec<Phase>,
+    ///The number of ticks into the cycle the plan is at on tick 0
+    #[serde(default)]
+    pub offset: usize,
+    ///The number of ticks at the end of each phase its green lights show yellow, if the next phase does not keep them green
+    #[serde(default)]
+    pub yellow: usize
+}
+
+impl SignalPlan
+{
+    pub fn new(phases: Vec<Phase>, offset: usize) -> SignalPlan
+    {
+        SignalPlan{phases, offset, yellow: 0}
+    }
+
+    ///Shows yellow for the last ticks of every phase
+    pub fn with_yellow(mut self, yellow: usize) -> SignalPlan
+    {
+        self.yellow = yellow;
+        self
+    }
+
+    ///A plan for an intersection without signals, every approach may always go
+    pub fn uncontrolled() -> SignalPlan
+    {
+        SignalPlan::new(vec![Phase{config: LightConfig::AllWay, duration: 1}], 0)
+    }
+
+    ///A plan that serves through traffic and then protected left turns, first north/south and then east/west
+    /// # Parameters
+    /// * `green` : `usize` - The number of ticks each through phase is shown for
+    /// * `turns` : `usize` - The number of ticks each left turn phase is shown for
+    pub fn four_phase(green: usize, turns: usize) -> SignalPlan
+    {
+        SignalPlan::new(vec![
+            Phase{config: LightConfig::NorthSouth, duration: green},
+            Phase{config: LightConfig::NorthSouthTurns, duration: turns},
+            Phase{config: LightConfig::EastWest, duration: green},
+            Phase{config: LightConfig::EastWestTurns, duration: turns},
+        ], 0)
+    }
+
+    ///Checks if this is the plan of an intersection without signals
+    pub fn is_uncontrolled(&self) -> bool
+    {
+        !self.phases.is_empty() && self.phases.iter().all(|phase| phase.config == LightConfig::AllWay)
+    }
+
+    ///The total number of ticks it takes to go through every phase
+    pub fn cycle_length(&self) -> usize
+    {
+        self.phases.iter().map(|phase| phase.duration).sum()
+    }
+
+    ///Gets the light configuration shown on a tick
+    /// # Parameters
+    /// * `tick` : `usize` - The timestep of the simulation
+    /// # Returns
+    /// `Option<LightConfig>` : Returns `None` if the plan has no phases with a duration
+    pub fn config_at(&self, tick: usize) -> Option<LightConfig>
+    {
+        self.phase_at(tick).map(|(index, _)| self.phases[index].config)
+    }
+
+    ///Gets the lights shown on a tick, with the yellow at the end of each phase
+    /// # Returns
+    /// `Option<[TrafficLight;4]>` : Returns `None` if the plan has no phases with a duration
+    pub fn lights_at(&self, tick: usize) -> Option<[TrafficLight;4]>
+    {
+        let (index, left) = self.phase_at(tick)?;
+        let mut lights = self.phases[index].config.get_lights();
+        if left <= self.yellow{
+            let next = self.phases[(index + 1) % self.phases.len()].config.get_lights();
+            for (light, next) in lights.iter_mut().zip(next.iter()){
+                if light.main_status == LightStatus::Green && next.main_status != LightStatus::Green{
+                    light.main_status = LightStatus::Yellow;
+                }
+                if light.left_turn_status == LightStatus::Green && next.left_turn_status != LightStatus::Green{
+                    light.left_turn_status = LightStatus::Yellow;
+                }
+            }
+        }
+        Some(lights)
+    }
+
+    ///Gets the index of the phase shown on a tick and the number of ticks it has left, counting that one
+    fn phase_at(&self, tick: usize) -> Option<(usize, usize)>
+    {
+        let cycle = self.cycle_length();
+        if cycle == 0{
+            return None;
+        }
+        let mut in_cycle = (tick + self.offset) % cycle;
+        for (index, phase) in self.phases.iter().enumerate(){
+            if in_cycle < phase.duration{
+                return Some((index, phase.duration - in_cycle));
+            }
+            in_cycle -= phase.duration;
+        }
+        None
+    }
+}
+
 impl Distribution<LightStatus> for Standard {
     fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> LightStatus {
         // match rng.gen_range(0, 3) { // rand 0.5, 0.6, 0.7
@@ -143,9 +295,19 @@ impl TrafficLight{
 #[derive(Debug)]
 pub struct Intersection
 {
-    pub id : u8,
+    pub id : IntersectionId,
     pub lights: [TrafficLight; 4],
-    pub light_queues : [VecDeque<u8>;4]
+    pub light_queues : [VecDeque<CarId>;4],
+    ///The fixed time plan controlling the lights, lights are random each tick if there is none
+    pub signal_plan : Option<SignalPlan>,
+    ///Where the intersection is on the map
+    pub position : Option<Point>,
+    ///How often cars without a route make each turn from each side, every turn is as likely from a side without any
+    pub turning : [Option<TurningProportions>; 4],
+    ///The ticks of green the car at the front of each queue has had, a car leaves once it has had as many as its PCE
+    pub discharge : [f64; 4],
+    ///The seconds each light has shown what it shows now, the main light and then the left turn arrow
+    pub shown_for : [[f64; 2]; 4]
 }
 
 impl Eq for Intersection {}
@@ -170,11 +332,11 @@ impl Hash for Intersection
 
 impl Intersection
 {
-    pub fn new(id: u8) -> Intersection{
-        Intersection{id, lights: [TrafficLight::rand();4], light_queues:[VecDeque::new(), VecDeque::new(), VecDeque::new(), VecDeque::new()]}
+    pub fn new(id: IntersectionId) -> Intersection{
+        Intersection{id, lights: [TrafficLight::rand();4], light_queues:[VecDeque::new(), VecDeque::new(), VecDeque::new(), VecDeque::new()], signal_plan: None, position: None, turning: [None; 4], discharge: [0.0; 4], shown_for: [[0.0; 2]; 4]}
     }
 
-    pub fn add_car_to_queue(&mut self, car_id:u8, dir:u8){
+    pub fn add_car_to_queue(&mut self, car_id:CarId, dir:u8){
         self.light_queues[usize::from(dir)].push_back(car_id);
     }
 
@@ -182,5 +344,19 @@ impl Intersection
         self.lights[usize::from(direction)]
     } 
 
+    ///Gets the light configuration the intersection will show on a tick
+    pub fn next_config(&self, tick: usize) -> LightConfig{
+        match &self.signal_plan{
+            Some(plan) => plan.config_at(tick).unwrap_or_else(rand::random),
+            None => rand::random()
+        }
+    }
+
+    ///Gets the lights the intersection will show on a tick, including the yellow of its signal plan
+    pub fn next_lights(&self, tick: usize) -> [TrafficLight;4]{
+        self.signal_plan.as_ref().and_then(|plan| plan.lights_at(tick))
+            .unwrap_or_else(|| self.next_config(tick).get_lights())
+    }
+
 
 }
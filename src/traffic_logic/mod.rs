@@ -1,4 +1,8 @@
 pub mod car;
 pub mod intersection;
 pub mod road;
-
+pub mod geometry;
+pub mod validation;
+pub mod route;
+pub mod vehicle;
+pub mod driver;
\ No newline at end of file
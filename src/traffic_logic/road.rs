@@ -1,21 +1,78 @@
-use crate::traffic_logic::car::Direction;
+use crate::traffic_logic::{car::Direction, geometry::Point, intersection::IntersectionId, validation::{Issue, ValidationReport}};
+use serde::{Deserialize, Serialize};
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+
+///The four arms of an intersection, matching the 0,1,2,3 direction IDs used throughout the simulator
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum CardinalDirection
+{
+    North,
+    East,
+    South,
+    West
+}
+
+impl CardinalDirection
+{
+    ///Gets the arm on the opposite side of the intersection
+    pub fn opposite(&self) -> CardinalDirection
+    {
+        match self{
+            Self::North => Self::South,
+            Self::East => Self::West,
+            Self::South => Self::North,
+            Self::West => Self::East
+        }
+    }
+}
+
+impl From<CardinalDirection> for u8
+{
+    fn from(dir: CardinalDirection) -> u8 {
+        match dir{
+            CardinalDirection::North => 0,
+            CardinalDirection::East => 1,
+            CardinalDirection::South => 2,
+            CardinalDirection::West => 3
+        }
+    }
+}
+
+impl TryFrom<u8> for CardinalDirection
+{
+    type Error = u8;
+
+    fn try_from(dir: u8) -> Result<CardinalDirection, u8> {
+        match dir{
+            0 => Ok(CardinalDirection::North),
+            1 => Ok(CardinalDirection::East),
+            2 => Ok(CardinalDirection::South),
+            3 => Ok(CardinalDirection::West),
+            _ => Err(dir)
+        }
+    }
+}
 
 #[derive(Clone, Copy)]
 pub struct Node{
-    pub dest_int_id:u8,
-    pub dist_from_source:u8,
-    pub direction:u8
+    pub dest_int_id:IntersectionId,
+    pub dist_from_source:u32,
+    ///The side of the destination intersection the car arrives at, the arm the road joins it on. It is
+    /// the side the car queues at there, not the way the car is heading nor the arm it left the source by.
+    pub direction:u8,
+    ///The number of lanes in this direction of travel
+    pub lanes:u8
 }
 
 impl Node{
-    pub fn new(dest_int_id:u8, dist_from_source:u8, direction:u8) -> Node{
-        Node{dest_int_id, dist_from_source, direction}
+    pub fn new(dest_int_id:IntersectionId, dist_from_source:u32, direction:u8, lanes:u8) -> Node{
+        Node{dest_int_id, dist_from_source, direction, lanes}
     }
 }
 
 
+#[derive(Clone)]
 pub struct Road
 {
     ///Represents a weighted graph between intersections, hashmap data structure which
@@ -23,11 +80,25 @@ pub struct Road
     /// # Tuple Members
     /// * `Destination Intersection ID` : The ID of the intersection
     /// * `Distance from Source` : The number of ticks it takes to get to the destination from the source
-    /// * `Direction` : The direction North South East West corresponding to 0,1,2,3 that car will arrive at
-    pub road : HashMap<u8, [Option<Node>;4]> 
+    /// * `Direction` : The side North East South West corresponding to 0,1,2,3 of the destination intersection that the car will arrive at
+    /// * `Lanes` : The number of lanes in this direction of travel
+    ///
+    /// The array is indexed by the arm of the source intersection the road leaves from. A road that is
+    /// only stored at one end is one way.
+    pub road : HashMap<IntersectionId, [Option<Node>;4]>,
+
+    ///The points a road bends through between its two intersections, hashed by the source
+    /// intersection ID and the arm the road leaves from. Roads without a shape are straight.
+    pub shapes : HashMap<(IntersectionId, u8), Vec<Point>>
 
 }
 
+impl Default for Road
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Road
 {
@@ -36,10 +107,19 @@ impl Road
     /// `Road`
     pub fn new() -> Road
     {
-        Road{road:HashMap::new()}
+        Road{road:HashMap::new(), shapes:HashMap::new()}
+    }
+
+    ///Gets the points a road bends through between its two intersections
+    /// # Parameters
+    /// * `source` : `IntersectionId` - The ID of the source intersection
+    /// * `arm` : `u8` - The arm of the source intersection the road leaves from
+    pub fn get_shape(&self, source : IntersectionId, arm : u8) -> &[Point]
+    {
+        self.shapes.get(&(source, arm)).map(|shape| shape.as_slice()).unwrap_or(&[])
     }
 
-    pub fn get_distance(&self, source : u8, dest : u8) -> Option<u8>
+    pub fn get_distance(&self, source : IntersectionId, dest : IntersectionId) -> Option<u32>
     {
         let distance = self.road.get(&source)?
         .iter()
@@ -54,25 +134,135 @@ impl Road
 
     ///Gets the next Intersection given a source intersection, inital direction, and intent
     /// # Parameters
-    /// * `source` : `u8` - The IntersectionID of the source intersection
-    /// * `source_dir` : `u8` The inital 0,1,2,3 Direction of the car
+    /// * `source` : `IntersectionId` - The ID of the source intersection
+    /// * `source_dir` : `u8` The 0,1,2,3 side of the source intersection the car waits at
     /// * `direction` : `Direction` - The direction intention of the car
     /// # Returns
-    /// `Option<(u8,u8)>` : Returns `None` if no Intersection was found
-    /// * `next_intersection_id` : `u8` - The IntersectionID of the next intersection
-    /// * `next_direction` : `u8` - The direction the car will arrive at
-    pub fn get_next_intersection(&self, source : u8, source_dir : u8, direction : Direction) -> Option<(u8, u8)>
+    /// `Option<(IntersectionId,u8)>` : Returns `None` if no Intersection was found
+    /// * `next_intersection_id` : `IntersectionId` - The ID of the next intersection
+    /// * `next_direction` : `u8` - The side of the next intersection the car will arrive at
+    pub fn get_next_intersection(&self, source : IntersectionId, source_dir : u8, direction : Direction) -> Option<(IntersectionId, u8)>
     {
-        let index : usize = match direction
-        {
-            Direction::Straight => usize::from(source_dir+2)%4,
-            Direction::Left => usize::from(source_dir+1)%4,
-            Direction::Right => usize::from(source_dir+3)%4,
-            _=> usize::from(source_dir)
-        };
-        let next = self.road.get(&source)?[index]?;
+        let next = self.get_next_node(source, source_dir, direction)?;
         Some((next.dest_int_id, next.direction))
 
     }
-}
 
+    ///Gets the road a car will take given a source intersection, inital direction, and intent
+    /// # Returns
+    /// `Option<Node>` : Returns `None` if there is no road leaving in that direction
+    pub fn get_next_node(&self, source : IntersectionId, source_dir : u8, direction : Direction) -> Option<Node>
+    {
+        let index = usize::from(Direction::get_next_direction(source_dir, direction));
+        self.road.get(&source)?[index]
+    }
+
+    ///Checks the road network for problems that would make the simulation panic, loop forever or behave unexpectedly
+    /// # Parameters
+    /// * `intersection_ids` : `&[IntersectionId]` - The IDs of every intersection that exists
+    /// # Returns
+    /// `ValidationReport` : Every problem found, in order of intersection ID
+    pub fn validate(&self, intersection_ids : &[IntersectionId]) -> ValidationReport
+    {
+        let mut issues = Vec::new();
+        let known: BTreeSet<IntersectionId> = intersection_ids.iter().copied().collect();
+        let mut sources: Vec<&IntersectionId> = self.road.keys().collect();
+        sources.sort();
+
+        let mut missing = BTreeSet::new();
+        //(intersection, arm) -> intersection the car arriving there came from
+        let mut incoming: HashMap<(IntersectionId, u8), IntersectionId> = HashMap::new();
+        for from in sources.iter().copied(){
+            if !known.contains(from){
+                missing.insert(*from);
+            }
+            for (arm, node) in self.road[from].iter().enumerate(){
+                let Some(node) = node else { continue };
+                let arm = arm as u8;
+                if !known.contains(&node.dest_int_id){
+                    missing.insert(node.dest_int_id);
+                }
+                if node.dist_from_source < 2{
+                    issues.push(Issue::TooShort{from: *from, to: node.dest_int_id, distance: node.dist_from_source});
+                }
+                incoming.insert((node.dest_int_id, node.direction), *from);
+
+                if let Some(other) = self.road.get(&node.dest_int_id).and_then(|arms| arms[usize::from(node.direction)]){
+                    if other.dest_int_id != *from{
+                        issues.push(Issue::ArmConflict{int_id: node.dest_int_id, arm: node.direction, arriving_from: *from, leaving_to: other.dest_int_id});
+                    }
+                }
+
+                //only report each pair of roads once
+                if node.dest_int_id < *from || (node.dest_int_id == *from && arm >= node.direction){
+                    continue;
+                }
+                let back = self.road.get(&node.dest_int_id).and_then(|arms| {
+                    arms.iter().enumerate().find_map(|(back_arm, back)| back.filter(|back| back.dest_int_id == *from).map(|back| (back_arm as u8, back)))
+                });
+                let Some((back_arm, back)) = back else { continue };
+                if back_arm != node.direction || back.direction != arm{
+                    issues.push(Issue::DirectionMismatch{from: *from, to: node.dest_int_id, there: (arm, node.direction), back: (back_arm, back.direction)});
+                }
+                if back.dist_from_source != node.dist_from_source{
+                    issues.push(Issue::DistanceMismatch{from: *from, to: node.dest_int_id, there: node.dist_from_source, back: back.dist_from_source});
+                }
+            }
+        }
+        issues.extend(missing.into_iter().map(Issue::MissingIntersection));
+
+        let mut groups: HashMap<IntersectionId, IntersectionId> = HashMap::new();
+        for id in known.iter(){
+            let exits: Vec<u8> = self.road.get(id).map(|arms| {
+                arms.iter().enumerate().filter(|(_, node)| node.is_some()).map(|(arm, _)| arm as u8).collect()
+            }).unwrap_or_default();
+            let arrivals: Vec<u8> = (0..4).filter(|side| incoming.contains_key(&(*id, *side))).collect();
+            if exits.is_empty() && arrivals.is_empty(){
+                issues.push(Issue::NoRoads(*id));
+                continue;
+            }
+            if exits.is_empty(){
+                issues.push(Issue::NoExit(*id));
+            }
+            else if exits.len() == 1{
+                for side in arrivals.into_iter().filter(|side| *side == exits[0]){
+                    issues.push(Issue::DeadEnd{int_id: *id, side});
+                }
+            }
+            groups.insert(*id, *id);
+        }
+
+        //join the groups of every pair of connected intersections
+        fn root(groups: &mut HashMap<IntersectionId, IntersectionId>, id: IntersectionId) -> IntersectionId
+        {
+            let parent = groups[&id];
+            if parent == id{
+                return id;
+            }
+            let top = root(groups, parent);
+            groups.insert(id, top);
+            top
+        }
+        for from in sources{
+            for node in self.road[from].iter().flatten(){
+                if groups.contains_key(from) && groups.contains_key(&node.dest_int_id){
+                    let (a, b) = (root(&mut groups, *from), root(&mut groups, node.dest_int_id));
+                    groups.insert(a.max(b), a.min(b));
+                }
+            }
+        }
+        let mut components: HashMap<IntersectionId, Vec<IntersectionId>> = HashMap::new();
+        let mut ids: Vec<IntersectionId> = groups.keys().copied().collect();
+        ids.sort();
+        for id in ids{
+            components.entry(root(&mut groups, id)).or_default().push(id);
+        }
+        if components.len() > 1{
+            let mut components: Vec<Vec<IntersectionId>> = components.into_values().collect();
+            components.sort();
+            issues.push(Issue::DisconnectedComponents(components));
+        }
+
+        ValidationReport{issues}
+    }
+}
@@ -0,0 +1,94 @@
+use crate::traffic_logic::{road::{Road, Node}, intersection::IntersectionId};
+use std::{cmp::Reverse, collections::{BinaryHeap, HashMap, HashSet, VecDeque}};
+
+///The roads a car takes to reach its destination
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub struct Route
+{
+    ///Every road still to drive, as the intersection and the arm it leaves from
+    pub links: VecDeque<(IntersectionId, u8)>,
+    ///The total cost of the route when it was found, in ticks for `Road::shortest_path`
+    pub cost: u64
+}
+
+///Where a car is going and how it gets there
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trip
+{
+    ///The number of the trip, trips are numbered in the order they set off
+    pub serial: u64,
+    pub origin: IntersectionId,
+    pub destination: IntersectionId,
+    ///The tick the car was placed at its origin
+    pub depart_tick: usize,
+    ///The cost of the route the car set off on
+    pub free_flow_time: u64,
+    pub route: Route,
+    ///The car finds a new route at every intersection from how busy the roads are
+    pub reroutes: bool,
+    ///The number of times the car has changed the road it was going to take
+    pub route_changes: usize,
+    ///Every road the car has driven onto, as the intersection and arm it left from and the tick it left
+    pub path: Vec<(IntersectionId, u8, usize)>
+}
+
+impl Road
+{
+    ///Finds the quickest way from one intersection to another with Dijkstra's algorithm, weighting
+    /// every road by the ticks it takes to drive
+    /// # Parameters
+    /// * `from` : `IntersectionId` - The intersection to start at
+    /// * `to` : `IntersectionId` - The intersection to end at
+    /// * `closed` : `&HashSet<(IntersectionId, u8)>` - Roads that cannot be used, by the intersection and arm they leave from
+    /// # Returns
+    /// `Option<Route>` : Returns `None` if `to` cannot be reached or is `from`
+    pub fn shortest_path(&self, from : IntersectionId, to : IntersectionId, closed : &HashSet<(IntersectionId, u8)>) -> Option<Route>
+    {
+        self.shortest_path_by(from, to, |int_id, arm, node| {
+            (!closed.contains(&(int_id, arm))).then_some(u64::from(node.dist_from_source))
+        })
+    }
+
+    ///Finds the cheapest way from one intersection to another with Dijkstra's algorithm
+    /// # Parameters
+    /// * `cost` : `FnMut(IntersectionId, u8, &Node) -> Option<u64>` - The cost of the road leaving an intersection on an arm, `None` if it cannot be used
+    /// # Returns
+    /// `Option<Route>` : Returns `None` if `to` cannot be reached or is `from`
+    pub fn shortest_path_by(&self, from : IntersectionId, to : IntersectionId, mut cost : impl FnMut(IntersectionId, u8, &Node) -> Option<u64>) -> Option<Route>
+    {
+        if from == to{
+            return None;
+        }
+        let mut best: HashMap<IntersectionId, u64> = HashMap::from([(from, 0)]);
+        //intersection -> (intersection before it, arm the road leaves that one from)
+        let mut previous: HashMap<IntersectionId, (IntersectionId, u8)> = HashMap::new();
+        let mut queue = BinaryHeap::from([Reverse((0, from))]);
+        while let Some(Reverse((total, current))) = queue.pop(){
+            if current == to{
+                let mut links = VecDeque::new();
+                let mut at = to;
+                while at != from{
+                    let link = previous[&at];
+                    links.push_front(link);
+                    at = link.0;
+                }
+                return Some(Route{links, cost: total});
+            }
+            if best.get(&current).is_some_and(|known| *known < total){
+                continue;
+            }
+            let Some(arms) = self.road.get(&current) else { continue };
+            for (arm, node) in arms.iter().enumerate(){
+                let Some(node) = node else { continue };
+                let Some(step) = cost(current, arm as u8, node) else { continue };
+                let next_total = total + step;
+                if best.get(&node.dest_int_id).is_none_or(|known| next_total < *known){
+                    best.insert(node.dest_int_id, next_total);
+                    previous.insert(node.dest_int_id, (current, arm as u8));
+                    queue.push(Reverse((next_total, node.dest_int_id)));
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,108 @@
+use crate::traffic_logic::intersection::IntersectionId;
+use std::fmt::Display;
+
+///How much a problem found by a validation pass matters
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Severity
+{
+    ///The network can be simulated but probably does not behave as intended
+    Warning,
+    ///Simulating the network will panic or loop forever once a car reaches the problem
+    Error
+}
+
+///A problem found by `Road::validate` or `Simulator::validate`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Issue
+{
+    ///The network is split into groups of intersections that cannot reach each other, listed by intersection ID
+    DisconnectedComponents(Vec<Vec<IntersectionId>>),
+    ///Cars arriving at `side` of the intersection can only leave by U-turning
+    DeadEnd{int_id: IntersectionId, side: u8},
+    ///The intersection has no roads at all
+    NoRoads(IntersectionId),
+    ///Cars can drive into the intersection but there is no road leaving it
+    NoExit(IntersectionId),
+    ///A road and the road back do not agree on their distance
+    DistanceMismatch{from: IntersectionId, to: IntersectionId, there: u32, back: u32},
+    ///A road and the road back do not use the same arms, each given as (arm it leaves from, arm it arrives at)
+    DirectionMismatch{from: IntersectionId, to: IntersectionId, there: (u8, u8), back: (u8, u8)},
+    ///The arm of the intersection is where a road from `arriving_from` arrives, but its road leaving goes to `leaving_to`
+    ArmConflict{int_id: IntersectionId, arm: u8, arriving_from: IntersectionId, leaving_to: IntersectionId},
+    ///An ID is used by the road network or a car but there is no intersection with that ID
+    MissingIntersection(IntersectionId),
+    ///The road takes less than 2 ticks to drive, which cars cannot do
+    TooShort{from: IntersectionId, to: IntersectionId, distance: u32}
+}
+
+impl Issue
+{
+    pub fn severity(&self) -> Severity
+    {
+        match self{
+            Self::NoRoads(_) | Self::NoExit(_) | Self::MissingIntersection(_) | Self::TooShort{..} => Severity::Error,
+            _ => Severity::Warning
+        }
+    }
+}
+
+impl Display for Issue{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self{
+            Self::DisconnectedComponents(groups) => write!(f, "The network is split into {} disconnected groups: {:?}", groups.len(), groups),
+            Self::DeadEnd{int_id, side} => write!(f, "Cars arriving at Intersection {} in direction {} can only U-turn", int_id, side),
+            Self::NoRoads(id) => write!(f, "Intersection {} has no roads", id),
+            Self::NoExit(id) => write!(f, "Intersection {} has no roads leaving it", id),
+            Self::DistanceMismatch{from, to, there, back} => write!(f,
+                "The road from Intersection {} to Intersection {} is {} ticks long but the road back is {}", from, to, there, back),
+            Self::DirectionMismatch{from, to, there, back} => write!(f,
+                "The road from Intersection {} to Intersection {} goes from direction {} to {} but the road back goes from direction {} to {}",
+                from, to, there.0, there.1, back.0, back.1),
+            Self::ArmConflict{int_id, arm, arriving_from, leaving_to} => write!(f,
+                "Cars from Intersection {} arrive at Intersection {} in direction {}, but that direction leads to Intersection {}", arriving_from, int_id, arm, leaving_to),
+            Self::MissingIntersection(id) => write!(f, "Intersection {} does not exist", id),
+            Self::TooShort{from, to, distance} => write!(f,
+                "The road from Intersection {} to Intersection {} is {} ticks long, roads must be at least 2 ticks long", from, to, distance)
+        }
+    }
+}
+
+///Every problem found by a validation pass
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ValidationReport
+{
+    pub issues: Vec<Issue>
+}
+
+impl ValidationReport
+{
+    ///Checks that nothing in the report would stop a simulation
+    pub fn is_ok(&self) -> bool
+    {
+        self.errors().next().is_none()
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = &Issue>
+    {
+        self.issues.iter().filter(|issue| issue.severity() == Severity::Error)
+    }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &Issue>
+    {
+        self.issues.iter().filter(|issue| issue.severity() == Severity::Warning)
+    }
+}
+
+impl Display for ValidationReport{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} error(s), {} warning(s)", self.errors().count(), self.warnings().count())?;
+        for issue in self.issues.iter(){
+            let label = match issue.severity(){
+                Severity::Error => "error",
+                Severity::Warning => "warning"
+            };
+            write!(f, "\n  * {}: {}", label, issue)?;
+        }
+        Ok(())
+    }
+}
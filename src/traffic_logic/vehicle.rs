@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+///The speed a road's distance in ticks is driven at, 50 km/h in metres a second
+pub const REFERENCE_SPEED : f64 = 13.89;
+
+///The length of road a passenger car takes up in a queue, its length and the gap to the car ahead
+pub const CAR_SPACE : f64 = 7.5;
+
+///The kind of vehicle a car is, which sets how fast it drives, how much room it is counted as and
+/// how long it takes to leave a queue
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug, Default, Serialize, Deserialize)]
+pub enum VehicleClass
+{
+    #[default]
+    PassengerCar,
+    Truck,
+    Bus,
+    Motorcycle,
+    Bicycle
+}
+
+///The physical parameters of a vehicle class
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VehicleParams
+{
+    ///In metres
+    pub length : f64,
+    ///In metres a second
+    pub max_speed : f64,
+    ///In metres a second squared
+    pub acceleration : f64,
+    ///The number of passenger cars the vehicle counts as when it leaves a queue
+    pub pce : f64
+}
+
+impl VehicleClass
+{
+    pub const ALL : [VehicleClass; 5] = [Self::PassengerCar, Self::Truck, Self::Bus, Self::Motorcycle, Self::Bicycle];
+
+    pub fn params(&self) -> VehicleParams
+    {
+        match self{
+            Self::PassengerCar => VehicleParams{length: 5.0, max_speed: 41.7, acceleration: 2.6, pce: 1.0},
+            Self::Truck => VehicleParams{length: 12.0, max_speed: 25.0, acceleration: 1.0, pce: 2.0},
+            Self::Bus => VehicleParams{length: 12.0, max_speed: 27.8, acceleration: 1.2, pce: 2.0},
+            Self::Motorcycle => VehicleParams{length: 2.5, max_speed: 50.0, acceleration: 4.0, pce: 0.5},
+            Self::Bicycle => VehicleParams{length: 1.8, max_speed: 5.5, acceleration: 1.0, pce: 0.3}
+        }
+    }
+
+    ///The room the vehicle takes up in a queue, in passenger car spaces. This is a measure of how full
+    /// roads and entries are, used by `Simulator::link_occupancy`, the entry queues of the demand and
+    /// the storage of mesoscopic links. Cars do not drive onto a road that is full, so long vehicles
+    /// make queues spill back sooner.
+    pub fn storage_spaces(&self) -> f64
+    {
+        (self.params().length + CAR_SPACE - VehicleClass::PassengerCar.params().length) / CAR_SPACE
+    }
+
+    ///The number of ticks it takes to drive a road. Vehicles that cannot reach the reference speed
+    /// take longer in proportion, and vehicles setting off from a queue lose the time they take to
+    /// speed up beyond what a passenger car loses. A tick is taken to be a second long.
+    /// # Parameters
+    /// * `distance` : `u32` - The distance of the road, the ticks a passenger car takes
+    /// * `from_stop` : `bool` - The vehicle was waiting in a queue before it drove onto the road
+    /// # Examples
+    ///
+    /// ```rust
+    /// use traffic_sim::traffic_logic::vehicle::VehicleClass;
+    /// assert_eq!(VehicleClass::PassengerCar.travel_ticks(10, true), 10);
+    /// assert_eq!(VehicleClass::Bicycle.travel_ticks(10, false), 26);
+    /// assert!(VehicleClass::Truck.travel_ticks(10, true) > VehicleClass::Truck.travel_ticks(10, false));
+    /// ```
+    pub fn travel_ticks(&self, distance : u32, from_stop : bool) -> u32
+    {
+        let params = self.params();
+        let cruising = (f64::from(distance) * (REFERENCE_SPEED / params.max_speed).max(1.0)).ceil();
+        let speed = params.max_speed.min(REFERENCE_SPEED);
+        let start_loss = if from_stop {
+            (speed / (2.0 * params.acceleration) - REFERENCE_SPEED / (2.0 * VehicleClass::PassengerCar.params().acceleration)).max(0.0).round()
+        } else {0.0};
+        ((cruising + start_loss) as u32).max(1)
+    }
+}
+
+impl Display for VehicleClass
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self{
+            Self::PassengerCar => "passenger car",
+            Self::Truck => "truck",
+            Self::Bus => "bus",
+            Self::Motorcycle => "motorcycle",
+            Self::Bicycle => "bicycle"
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -0,0 +1,70 @@
+use crate::simulator::Simulator;
+use crate::visualizer::layout;
+use crate::traffic_logic::intersection::IntersectionId;
+use std::path::Path;
+
+const DIRECTIONS: [&str; 4] = ["N", "E", "S", "W"];
+
+///Writes the road network of a simulation to a Graphviz DOT file, see `to_dot`
+pub fn export_dot(simulator: &Simulator, path: impl AsRef<Path>, congestion: bool) -> std::io::Result<()>
+{
+    std::fs::write(path, to_dot(simulator, congestion))
+}
+
+///Converts the road network of a simulation into a Graphviz DOT digraph.
+///
+/// Every intersection is a node labelled with its ID and signal type, every road an edge labelled
+/// with its distance and the arm it leaves from and arrives at. Nodes get a `pos` from `layout` so
+/// `neato -n` draws the network the way it is laid out, `dot` ignores it.
+/// # Parameters
+/// * `congestion` : `bool` - Colors every edge by how full it is on the current tick, the room the
+///   cars driving on the road and queued at its end take up divided by its distance times its lanes. Green is
+///   under a third full, orange under two thirds and red above that.
+pub fn to_dot(simulator: &Simulator, congestion: bool) -> String
+{
+    let positions = layout(simulator);
+    let occupancy = if congestion {simulator.link_occupancy()} else {Default::default()};
+    let mut dot = String::from("digraph road {\n    node [shape=circle];\n");
+
+    for intersection in simulator.intersections(){
+        let signal = match &intersection.signal_plan{
+            None => String::from("random"),
+            Some(plan) if plan.is_uncontrolled() => String::from("uncontrolled"),
+            Some(plan) => format!("fixed time, {} ticks", plan.cycle_length())
+        };
+        let pos = positions.get(&intersection.id)
+            .map(|pos| format!(", pos=\"{:.2},{:.2}!\"", pos.x * 72.0, pos.y * 72.0))
+            .unwrap_or_default();
+        dot.push_str(&format!("    {} [label=\"{}\\n{}\"{}];\n", intersection.id, intersection.id, signal, pos));
+    }
+
+    let mut sources: Vec<&IntersectionId> = simulator.road().road.keys().collect();
+    sources.sort();
+    for from in sources{
+        for (arm, node) in simulator.road().road[from].iter().enumerate(){
+            let Some(node) = node else {
+                continue;
+            };
+            let mut attributes = format!("label=\"{} ({}\u{2192}{})\"", node.dist_from_source, DIRECTIONS[arm], DIRECTIONS[usize::from(node.direction)]);
+            if node.lanes > 1{
+                attributes.push_str(&format!(", penwidth={}", node.lanes));
+            }
+            if simulator.is_closed(*from, arm as u8){
+                attributes.push_str(", style=dashed");
+            }
+            if congestion{
+                let cars = occupancy.get(&(*from, arm as u8)).copied().unwrap_or(0.0);
+                let storage = f64::from(node.dist_from_source) * f64::from(node.lanes.max(1));
+                let color = match cars / storage{
+                    load if load < 1.0 / 3.0 => "green",
+                    load if load < 2.0 / 3.0 => "orange",
+                    _ => "red"
+                };
+                attributes.push_str(&format!(", color={}, fontcolor={}", color, color));
+            }
+            dot.push_str(&format!("    {} -> {} [{}];\n", from, node.dest_int_id, attributes));
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
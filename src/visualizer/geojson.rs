@@ -0,0 +1,89 @@
+use crate::simulator::Simulator;
+use crate::visualizer::layout;
+use crate::traffic_logic::{geometry::Point, intersection::IntersectionId};
+use serde_json::{json, Value};
+use std::path::Path;
+
+///Writes the network and the current state of a simulation to a GeoJSON file, see `to_geojson`
+pub fn export_geojson(simulator: &Simulator, path: impl AsRef<Path>) -> std::io::Result<()>
+{
+    let text = serde_json::to_string_pretty(&to_geojson(simulator))?;
+    std::fs::write(path, text)
+}
+
+///Converts the network and the current state of a simulation into a GeoJSON `FeatureCollection`.
+///
+/// Every intersection becomes a `Point` and every road a `LineString` from the intersection it
+/// leaves to the one it goes to, so a two way road is two features. Coordinates are the positions
+/// of the intersections and the shapes of the roads, see `layout` for networks without positions.
+/// Call it before running for the static network, or after any tick for a snapshot of that tick.
+/// # Properties
+/// * Intersections : `kind`, `id`, `tick`, `light_queues` (cars waiting on the north, east, south and west sides) and `queued`
+/// * Roads : `kind`, `from`, `to`, `arm`, `arrival`, `distance`, `lanes`, `vehicles` (cars driving on the road), `closed` and `tick`
+pub fn to_geojson(simulator: &Simulator) -> Value
+{
+    let positions = layout(simulator);
+    let tick = simulator.timestep();
+    let counts = simulator.link_counts();
+    let mut features = Vec::new();
+
+    for intersection in simulator.intersections(){
+        let Some(pos) = positions.get(&intersection.id) else {
+            continue;
+        };
+        let queues: Vec<usize> = intersection.light_queues.iter().map(|queue| queue.len()).collect();
+        features.push(json!({
+            "type": "Feature",
+            "geometry": {"type": "Point", "coordinates": coordinates(pos)},
+            "properties": {
+                "kind": "intersection",
+                "id": intersection.id,
+                "tick": tick,
+                "queued": queues.iter().sum::<usize>(),
+                "light_queues": queues
+            }
+        }));
+    }
+
+    let mut sources: Vec<&IntersectionId> = simulator.road().road.keys().collect();
+    sources.sort();
+    for from in sources{
+        for (arm, node) in simulator.road().road[from].iter().enumerate(){
+            let Some(node) = node else {
+                continue;
+            };
+            let arm = arm as u8;
+            let (Some(start), Some(end)) = (positions.get(from), positions.get(&node.dest_int_id)) else {
+                continue;
+            };
+            let mut line = vec![*start];
+            if simulator.intersections().iter().all(|intersection| intersection.position.is_some()){
+                line.extend(simulator.road().get_shape(*from, arm).iter().copied());
+            }
+            line.push(*end);
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {"type": "LineString", "coordinates": line.iter().map(coordinates).collect::<Vec<Value>>()},
+                "properties": {
+                    "kind": "road",
+                    "from": from,
+                    "to": node.dest_int_id,
+                    "arm": arm,
+                    "arrival": node.direction,
+                    "distance": node.dist_from_source,
+                    "lanes": node.lanes,
+                    "vehicles": counts.get(&(*from, arm)).copied().unwrap_or(0),
+                    "closed": simulator.is_closed(*from, arm),
+                    "tick": tick
+                }
+            }));
+        }
+    }
+
+    json!({"type": "FeatureCollection", "features": features})
+}
+
+fn coordinates(point: &Point) -> Value
+{
+    json!([point.x, point.y])
+}
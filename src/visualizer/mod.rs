@@ -0,0 +1,24 @@
+pub mod geojson;
+pub mod dot;
+
+use crate::simulator::Simulator;
+use crate::traffic_logic::{geometry::{self, Point}, intersection::IntersectionId};
+use std::collections::HashMap;
+
+///Gets where every intersection is drawn.
+///
+/// Intersections are drawn at their positions if they all have one. Otherwise the network is laid
+/// out by walking the roads from the first intersection along their arms, each road as long as its
+/// distance, with every disconnected part placed to the right of the last.
+pub fn layout(simulator: &Simulator) -> HashMap<IntersectionId, Point>
+{
+    let nodes: Vec<(IntersectionId, Option<Point>)> = simulator.intersections().iter().map(|intersection| (intersection.id, intersection.position)).collect();
+    //every road as (from, arm it leaves from, to, arm it arrives at, distance)
+    let mut links: Vec<(IntersectionId, u8, IntersectionId, u8, f64)> = simulator.road().road.iter().flat_map(|(from, arms)| {
+        arms.iter().enumerate().filter_map(|(arm, node)| {
+            node.map(|node| (*from, arm as u8, node.dest_int_id, node.direction, f64::from(node.dist_from_source)))
+        }).collect::<Vec<_>>()
+    }).collect();
+    links.sort_by_key(|link| (link.0, link.1));
+    geometry::layout(&nodes, &links, 10.0)
+}
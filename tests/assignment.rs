@@ -0,0 +1,72 @@
+use std::cell::Cell;
+use traffic_sim::simulator::{Simulator, Current, assignment::{assign, AssignmentOptions, AssignmentError}};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan, Phase, LightConfig};
+
+///Two ways from 1 to 3, through 2 is a little shorter than through 4 but its light lets few cars
+/// through, and a trip from 1 to 3 every tick
+fn two_routes(trips : usize) -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(4);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    simulator.add_road(IntersectionId(2), 0, IntersectionId(3), 5);
+    simulator.add_road(IntersectionId(1), 0, IntersectionId(4), 6);
+    simulator.add_road(IntersectionId(4), 1, IntersectionId(3), 6);
+    for id in 1..=4{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    //cars from 1 wait at side 3 of 2 and turn left on the east west turns light
+    let phases = vec![Phase{config: LightConfig::EastWestTurns, duration: 2}, Phase{config: LightConfig::NorthSouth, duration: 4}];
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::new(phases, 0));
+    for trip in 0..trips{
+        simulator.schedule_trip(trip, Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(3));
+    }
+    simulator
+}
+
+#[test]
+fn congested_trips_spread_over_both_routes()
+{
+    let options = AssignmentOptions{max_iterations: 30, gap_tolerance: 0.05, ..Default::default()};
+    let assignment = assign(|| two_routes(60), &options).unwrap();
+    assert!(assignment.converged);
+    let gaps: Vec<f64> = assignment.iterations.iter().map(|stats| stats.relative_gap).collect();
+    assert!(gaps[0] > options.gap_tolerance);
+    assert!(*gaps.last().unwrap() <= options.gap_tolerance);
+    assert!(gaps.windows(2).all(|pair| pair[1] < pair[0]));
+    let first = assignment.iterations.first().unwrap();
+    let last = assignment.iterations.last().unwrap();
+    assert!(last.mean_travel_time < first.mean_travel_time);
+    assert_eq!(last.completed, 60);
+
+    assert_eq!(assignment.route_flows.len(), 2);
+    assert_eq!(assignment.route_flows.iter().map(|flow| flow.trips).sum::<usize>(), 60);
+    assert_eq!(assignment.link_volumes[&(IntersectionId(1), 0)] + assignment.link_volumes[&(IntersectionId(1), 1)], 60);
+}
+
+#[test]
+fn building_different_trips_each_time_is_an_error()
+{
+    let builds = Cell::new(0);
+    let result = assign(|| {
+        builds.set(builds.get() + 1);
+        //the second simulator built, for the first iteration, has one trip more
+        two_routes(10 + builds.get())
+    }, &AssignmentOptions::default());
+    let error = result.err().unwrap();
+    assert_eq!(error, AssignmentError::DemandChanged{iteration: 1, expected: 11, found: 12, first_difference: 11});
+    assert_eq!(error.to_string(), "The simulator built for iteration 1 schedules 12 trips where the first scheduled 11, trip 12 is the first that differs");
+}
+
+#[test]
+fn each_iteration_moves_a_shrinking_share_of_every_pair()
+{
+    let options = AssignmentOptions{max_iterations: 3, gap_tolerance: 0.0, ..Default::default()};
+    let assignment = assign(|| two_routes(60), &options).unwrap();
+    let switched: Vec<usize> = assignment.iterations.iter().map(|stats| stats.switched).collect();
+    //six pairs of ten trips, half of each can move after the first run and a third of the flow after the second
+    assert!(switched[0] > 0 && switched[0] <= 30, "{:?}", switched);
+    assert!(switched[1] <= 20, "{:?}", switched);
+    //the last run only measures the gap
+    assert_eq!(switched[2], 0);
+}
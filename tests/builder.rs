@@ -0,0 +1,103 @@
+use traffic_sim::simulator::{Position, Between, builder::{NetworkError, RoadNetworkBuilder}};
+use traffic_sim::traffic_logic::{geometry::Point, intersection::IntersectionId, road::CardinalDirection::{East, North, South}};
+
+#[test]
+fn roads_and_cars_can_refer_to_intersections_by_coordinates()
+{
+    let simulator = RoadNetworkBuilder::new()
+        .intersection_at("A", 0.0, 0.0)
+        .intersection_at("B", 10.0, 0.0)
+        .intersection_at("C", 0.0, -10.0)
+        .road((0.0, 0.0), East, (10.0, 0.0), 5)
+        .road("A", South, (0.0, -10.0), 4)
+        .car((10.0, 0.0), East)
+        .build()
+        .unwrap();
+    assert_eq!(simulator.road().get_distance(IntersectionId(1), IntersectionId(2)), Some(5));
+    assert_eq!(simulator.road().get_distance(IntersectionId(3), IntersectionId(1)), Some(4));
+    assert_eq!(simulator.cars().len(), 1);
+}
+
+#[test]
+fn unknown_coordinates_are_reported_as_missing()
+{
+    let errors = RoadNetworkBuilder::new()
+        .intersection_at("A", 0.0, 0.0)
+        .road((0.0, 0.0), North, (5.0, 5.0), 3)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(errors.errors, vec![NetworkError::MissingIntersection("(5, 5)".to_string())]);
+}
+
+#[test]
+fn roads_without_lanes_are_rejected()
+{
+    let errors = RoadNetworkBuilder::new()
+        .intersection("A")
+        .intersection("B")
+        .road("A", East, "B", 5)
+        .lanes(0)
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(errors.errors, vec![NetworkError::InvalidLanes{from: "A".to_string(), to: "B".to_string()}]);
+}
+
+#[test]
+fn connected_roads_follow_their_shape()
+{
+    //the road from A to B leaves A northwards and goes round three sides of a 30 by 40 rectangle
+    let mut simulator = RoadNetworkBuilder::new()
+        .units_per_tick(10.0)
+        .intersection_at("A", 0.0, 0.0)
+        .intersection_at("B", 30.0, 0.0)
+        .connect("A", "B")
+        .shape(vec![Point::new(0.0, 40.0), Point::new(30.0, 40.0)])
+        .build()
+        .unwrap();
+    let (a, b) = (IntersectionId(1), IntersectionId(2));
+    assert_eq!(simulator.road().get_distance(a, b), Some(11));
+    assert!(simulator.road().road[&a][0].is_some_and(|node| node.dest_int_id == b && node.direction == 0));
+    let line = vec![Point::new(0.0, 0.0), Point::new(0.0, 40.0), Point::new(30.0, 40.0), Point::new(30.0, 0.0)];
+    assert_eq!(simulator.road_line(a, 0), Some(line.clone()));
+    assert_eq!(simulator.road_line(b, 0), Some(line.into_iter().rev().collect()));
+    assert_eq!(simulator.road_line(a, 1), None);
+
+    //a car with 5 of the 11 ticks left has driven 60 of the 110 units, 20 along the top
+    let car = simulator.add_car(Position::new(None, Some(Between{int_1_id: a, int_2_id: b, distance_to_target: 5, from: 0})));
+    let location = simulator.car_location(car).unwrap();
+    assert!(location.distance(&Point::new(20.0, 40.0)) < 1e-9, "{:?}", location);
+    simulator.run(20);
+    assert!(simulator.cars().iter().all(|car| simulator.car_location(car.id).is_some()));
+}
+
+#[test]
+fn geographic_roads_are_measured_on_the_earth()
+{
+    //at 60 degrees north a degree of longitude is half as long as a degree of latitude
+    let simulator = RoadNetworkBuilder::new()
+        .geographic()
+        .units_per_tick(10.0)
+        .intersection_at("A", 10.0, 60.0)
+        .intersection_at("B", 10.01, 60.0)
+        .intersection_at("C", 10.006, 60.005)
+        .connect("A", "B")
+        .connect("A", "C")
+        .build()
+        .unwrap();
+    let (a, b, c) = (IntersectionId(1), IntersectionId(2), IntersectionId(3));
+    //556 metres
+    assert_eq!(simulator.road().get_distance(a, b), Some(56));
+    //333 metres east and 556 north is closer to north than east, though not on a planar map
+    assert!(simulator.road().road[&a][0].is_some_and(|node| node.dest_int_id == c));
+    assert!(simulator.road().road[&a][1].is_some_and(|node| node.dest_int_id == b));
+
+    let planar = RoadNetworkBuilder::new()
+        .intersection_at("A", 10.0, 60.0)
+        .intersection_at("C", 10.006, 60.005)
+        .connect("A", "C")
+        .build()
+        .unwrap();
+    assert!(planar.road().road[&a][1].is_some());
+}
@@ -0,0 +1,84 @@
+use traffic_sim::simulator::{Simulator, demand::{Demand, OdPair, TimeProfile}};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan, Phase, LightConfig};
+
+fn two_intersections() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(2);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    for id in 1..=2{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator
+}
+
+fn generated(simulator : &Simulator) -> usize
+{
+    simulator.entry_log().len() + simulator.entry_queue_length(IntersectionId(1))
+}
+
+#[test]
+fn trips_start_at_the_rate_of_their_pair()
+{
+    let pair = OdPair{origin: IntersectionId(1), destination: IntersectionId(2), trips_per_hour: 1800.0};
+    let demand = Demand::new(vec![pair]);
+    assert_eq!(demand.rate(&pair, 0), 0.5);
+
+    let mut simulator = two_intersections();
+    simulator.set_demand(demand);
+    simulator.run(4000);
+    //the mean is 2000 trips and the standard deviation about 45
+    let trips = generated(&simulator);
+    assert!((1800..=2200).contains(&trips), "{} trips were started", trips);
+}
+
+#[test]
+fn profiles_scale_the_rate_by_the_time_of_day()
+{
+    let pair = OdPair{origin: IntersectionId(1), destination: IntersectionId(2), trips_per_hour: 7200.0};
+    let demand = Demand::new(vec![pair]).with_profile(TimeProfile::new(100, vec![1.0, 0.0]));
+    assert_eq!((demand.rate(&pair, 50), demand.rate(&pair, 150), demand.rate(&pair, 250)), (2.0, 0.0, 2.0));
+
+    let mut simulator = two_intersections();
+    simulator.set_demand(demand);
+    simulator.run(400);
+    assert!(simulator.entry_log().iter().all(|entry| (entry.generated_tick / 100) % 2 == 0));
+    assert!(generated(&simulator) > 300);
+}
+
+#[test]
+fn entries_are_blocked_while_the_origin_is_full()
+{
+    let mut simulator = two_intersections();
+    //cars leaving 1 for 2 wait at side 3 and the north south light never lets them go
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::new(vec![Phase{config: LightConfig::NorthSouth, duration: 1000}], 0));
+    let mut demand = Demand::new(vec![OdPair{origin: IntersectionId(1), destination: IntersectionId(2), trips_per_hour: 36000.0}]);
+    demand.max_queue = 3;
+    simulator.set_demand(demand);
+    simulator.run(20);
+    assert_eq!(simulator.entry_log().len(), 3);
+    assert_eq!(simulator.cars().len(), 3);
+    assert!(simulator.entry_queue_length(IntersectionId(1)) > 100);
+    assert!(simulator.entry_log().iter().all(|entry| entry.car_id.is_some()));
+
+    simulator.clear_demand();
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::uncontrolled());
+    simulator.run(20);
+    assert!(simulator.entry_log().len() > 10);
+    assert!(simulator.entry_log()[..3].iter().all(|entry| !entry.was_blocked()));
+    let blocked = &simulator.entry_log()[3];
+    assert!(blocked.was_blocked());
+    assert!(blocked.entered_tick >= 20);
+}
+
+#[test]
+fn trips_waiting_behind_other_trips_are_not_blocked()
+{
+    let mut simulator = two_intersections();
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::uncontrolled());
+    //ten trips a tick and one car a tick can enter, so trips wait in the entry queue with room at the origin
+    simulator.set_demand(Demand::new(vec![OdPair{origin: IntersectionId(1), destination: IntersectionId(2), trips_per_hour: 36000.0}]));
+    simulator.run(20);
+    assert!(simulator.entry_log().iter().any(|entry| entry.entered_tick > entry.generated_tick));
+    assert!(simulator.entry_log().iter().all(|entry| !entry.was_blocked()));
+}
@@ -0,0 +1,41 @@
+use traffic_sim::simulator::{Simulator, Current, Position};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan};
+use traffic_sim::visualizer::dot::to_dot;
+
+fn two_intersections() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(2);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 4);
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::four_phase(20, 5));
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::uncontrolled());
+    simulator
+}
+
+#[test]
+fn every_intersection_is_a_node_and_every_road_an_edge()
+{
+    let dot = to_dot(&two_intersections(), false);
+    assert_eq!(dot, "digraph road {
+    node [shape=circle];
+    1 [label=\"1\\nfixed time, 50 ticks\", pos=\"0.00,0.00!\"];
+    2 [label=\"2\\nuncontrolled\", pos=\"288.00,0.00!\"];
+    1 -> 2 [label=\"4 (E\u{2192}W)\"];
+    2 -> 1 [label=\"4 (W\u{2192}E)\"];
+}
+");
+}
+
+#[test]
+fn closed_and_congested_roads_are_marked()
+{
+    let mut simulator = two_intersections();
+    simulator.close_arm(IntersectionId(2), 3, 0, 100);
+    for _ in 0..3{
+        simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(2), direction: 3}), None));
+    }
+    let dot = to_dot(&simulator, true);
+    assert!(dot.contains("2 -> 1 [label=\"4 (W\u{2192}E)\", style=dashed, color=green, fontcolor=green];"));
+    //three cars queued at the end of a road four ticks long
+    assert!(dot.contains("1 -> 2 [label=\"4 (E\u{2192}W)\", style=dashed, color=red, fontcolor=red];"));
+}
@@ -0,0 +1,136 @@
+use traffic_sim::simulator::{Simulator, Current, Position, Between, microscopic::Idm, saturation::SaturationFlow};
+use traffic_sim::traffic_logic::{car::{Car, CarId}, driver::DriverProfile, intersection::{IntersectionId, LightStatus, SignalPlan, Phase, LightConfig, TrafficLight}};
+
+fn lights(status : LightStatus) -> [TrafficLight; 4]
+{
+    [TrafficLight{main_status: status, left_turn_status: status}; 4]
+}
+
+#[test]
+fn drivers_decide_once_when_the_light_turns_yellow()
+{
+    let mut going = 0;
+    for id in 0..1000{
+        let mut car = Car::new(CarId(id));
+        car.driver = Some(DriverProfile::Normal);
+        car.see_light(&lights(LightStatus::Yellow), 0);
+        let decision = car.can_go(&lights(LightStatus::Yellow), 0);
+        for _ in 0..10{
+            car.see_light(&lights(LightStatus::Yellow), 0);
+            assert_eq!(car.can_go(&lights(LightStatus::Yellow), 0), decision);
+        }
+        going += usize::from(decision);
+        car.see_light(&lights(LightStatus::Red), 0);
+        assert_eq!(car.yellow_decision, None);
+    }
+    //a normal driver goes on 40% of yellows
+    assert!((300..=500).contains(&going), "{} drivers went", going);
+
+    let mut car = Car::new(CarId(1));
+    car.see_light(&lights(LightStatus::Yellow), 0);
+    assert_eq!(car.yellow_decision, Some(false));
+    assert!(!car.can_go(&lights(LightStatus::Yellow), 0));
+}
+
+///Places a driver at the front of the queue at 1 on a long yellow, and checks if it leaves before the red
+fn goes_on_a_long_yellow(driver : DriverProfile) -> bool
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(2);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    //cars waiting at side 3 go on the east west light, yellow from the second tick to the twentieth
+    let phases = vec![Phase{config: LightConfig::EastWest, duration: 20}, Phase{config: LightConfig::NorthSouth, duration: 100}];
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::new(phases, 0).with_yellow(19));
+    simulator.run(2);
+    let car = simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 3}), None));
+    simulator.set_driver_profile(car, Some(driver));
+    for _ in 0..20{
+        simulator.run(1);
+        if simulator.get_position(car).is_none_or(|pos| pos.current_intersection.is_none()){
+            return true;
+        }
+    }
+    false
+}
+
+#[test]
+fn waiting_drivers_keep_to_their_yellow_decision()
+{
+    let went = |driver| (0..300).filter(|_| goes_on_a_long_yellow(driver)).count() as f64 / 300.0;
+    //deciding again every tick of the yellow a cautious driver would go two times in three
+    assert!(went(DriverProfile::Cautious) < 0.15);
+    assert!(went(DriverProfile::Aggressive) > 0.75);
+}
+
+///Drives a car towards 2 in the microscopic mode while its light shows a long yellow, and checks if
+/// it gets past 2 before the red
+fn drives_through_a_long_yellow(driver : Option<DriverProfile>) -> bool
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 20);
+    simulator.add_road(IntersectionId(2), 1, IntersectionId(3), 20);
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::uncontrolled());
+    simulator.set_signal_plan(IntersectionId(3), SignalPlan::uncontrolled());
+    //cars from 1 arrive at side 3 of 2 and go on the east west light, yellow from the second tick to the thirtieth
+    let phases = vec![Phase{config: LightConfig::EastWest, duration: 30}, Phase{config: LightConfig::NorthSouth, duration: 100}];
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::new(phases, 0).with_yellow(29));
+    simulator.set_microscopic(Some(Idm::default()));
+    let car = simulator.add_car(Position::new(None, Some(Between{int_1_id: IntersectionId(1), int_2_id: IntersectionId(2), distance_to_target: 10, from: 3})));
+    simulator.set_driver_profile(car, driver);
+    for _ in 0..30{
+        simulator.run(1);
+        if simulator.get_position(car).is_none_or(|pos| pos.in_between.is_none_or(|between| between.int_1_id != IntersectionId(1))){
+            return true;
+        }
+    }
+    false
+}
+
+#[test]
+fn approaching_drivers_go_on_yellow_by_their_profile()
+{
+    //a driver without a profile stops for a yellow it can stop for comfortably
+    assert!(!drives_through_a_long_yellow(None));
+    let went = (0..100).filter(|_| drives_through_a_long_yellow(Some(DriverProfile::Aggressive))).count();
+    assert!(went > 75, "{} drivers went", went);
+    let went = (0..100).filter(|_| drives_through_a_long_yellow(Some(DriverProfile::Cautious))).count();
+    assert!(went < 20, "{} drivers went", went);
+}
+
+///Counts the cars queued at the north side of an intersection with a saturation flow that leave on yellow over ten cycles
+fn left_on_yellow(driver : Option<DriverProfile>) -> usize
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(2);
+    simulator.add_road(IntersectionId(1), 2, IntersectionId(2), 50);
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::new(vec![Phase{config: LightConfig::NorthSouth, duration: 10}, Phase{config: LightConfig::EastWest, duration: 10}], 0).with_yellow(4));
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::uncontrolled());
+    simulator.set_saturation_flow(Some(SaturationFlow::default()));
+    if let Some(driver) = driver{
+        simulator.set_driver_mix(vec![(driver, 1.0)]);
+    }
+    for _ in 0..200{
+        simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 0}), None));
+    }
+    let mut left = 0;
+    for _ in 0..200{
+        let before = simulator.intersections()[0].light_queues[0].len();
+        simulator.run(1);
+        if simulator.intersections()[0].lights[2].main_status == LightStatus::Yellow{
+            left += before - simulator.intersections()[0].light_queues[0].len();
+        }
+    }
+    left
+}
+
+#[test]
+fn a_drivers_yellow_decision_wins_over_the_saturation_flow()
+{
+    //drivers without a profile use the effective green of every yellow
+    let without_profile = left_on_yellow(None);
+    assert!(without_profile >= 10, "{} cars left on yellow", without_profile);
+    //cautious drivers go through one yellow in twenty
+    let cautious = left_on_yellow(Some(DriverProfile::Cautious));
+    assert!(cautious * 2 < without_profile, "{} cautious drivers left on yellow against {} without a profile", cautious, without_profile);
+}
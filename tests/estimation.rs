@@ -0,0 +1,85 @@
+use traffic_sim::scenario::{ScenarioError, csv::parse_counts_csv};
+use traffic_sim::simulator::{Simulator, demand::{Demand, OdPair}, estimation::{estimate_od, geh, CountLocation, EstimationOptions, ObservedCount}};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan};
+
+///Three uncontrolled intersections in a row from west to east
+fn corridor() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    simulator.add_road(IntersectionId(2), 1, IntersectionId(3), 5);
+    for id in 1..=3{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator
+}
+
+fn pair(origin : u32, destination : u32, trips_per_hour : f64) -> OdPair
+{
+    OdPair{origin: IntersectionId(origin), destination: IntersectionId(destination), trips_per_hour}
+}
+
+#[test]
+fn estimates_match_the_counts_better_than_the_seed()
+{
+    let seed = Demand::new(vec![pair(1, 2, 100.0), pair(1, 3, 100.0), pair(2, 3, 100.0)]);
+    //counted from 300 trips an hour from 1 to 2, 600 from 1 to 3 and 200 from 2 to 3
+    let counts = vec![
+        ObservedCount{location: CountLocation::Link{int_id: IntersectionId(1), arm: 1}, vehicles_per_hour: 900.0},
+        ObservedCount{location: CountLocation::Link{int_id: IntersectionId(2), arm: 1}, vehicles_per_hour: 800.0},
+        ObservedCount{location: CountLocation::Turn{int_id: IntersectionId(2), approach: 3, exit: 1}, vehicles_per_hour: 600.0}
+    ];
+    let options = EstimationOptions{max_iterations: 5, ..Default::default()};
+    let estimate = estimate_od(corridor, &seed, &counts, &options);
+
+    let first = estimate.iterations.first().unwrap();
+    let best = estimate.iterations.iter().map(|stats| stats.rmse).fold(f64::MAX, f64::min);
+    assert!(first.rmse > 500.0);
+    assert!(best < first.rmse / 5.0, "the RMSE only fell from {} to {}", first.rmse, best);
+    assert!(estimate.iterations.last().unwrap().geh_below_5 > first.geh_below_5);
+    for (count, simulated) in counts.iter().zip(estimate.simulated_counts.iter()){
+        assert!(geh(*simulated, count.vehicles_per_hour) < 5.0, "{:?} was {} in the best run", count, simulated);
+    }
+    //pairs keep the place they had in the seed
+    let rates: Vec<f64> = estimate.demand.pairs.iter().map(|pair| pair.trips_per_hour).collect();
+    assert!((rates[1] - 600.0).abs() < 100.0, "{:?}", rates);
+    assert!(rates[0] + rates[1] > 800.0 && rates[0] + rates[1] < 1000.0, "{:?}", rates);
+}
+
+#[test]
+fn counts_read_from_csv_mix_links_and_turns()
+{
+    let seed = Demand::new(vec![pair(1, 2, 100.0), pair(1, 3, 100.0), pair(2, 3, 100.0)]);
+    //the same counts as above, the turning movement row also says no car turns any other way at 2
+    let counts = parse_counts_csv("intersection,arm,vehicles_per_hour\n1,E,900\n2,east,800\n2,W,0,600,0\n").unwrap();
+    assert_eq!(counts.len(), 6);
+    assert_eq!(counts[1].location, CountLocation::Link{int_id: IntersectionId(2), arm: 1});
+    assert!(counts.contains(&ObservedCount{location: CountLocation::Turn{int_id: IntersectionId(2), approach: 3, exit: 1}, vehicles_per_hour: 600.0}));
+
+    let options = EstimationOptions{max_iterations: 5, ..Default::default()};
+    let estimate = estimate_od(corridor, &seed, &counts, &options);
+    assert_eq!(estimate.simulated_counts.len(), counts.len());
+    for (count, simulated) in counts.iter().zip(estimate.simulated_counts.iter()){
+        assert!(geh(*simulated, count.vehicles_per_hour) < 5.0, "{:?} was {} in the best run", count, simulated);
+    }
+}
+
+#[test]
+fn counts_with_an_unknown_arm_are_rejected_with_their_line()
+{
+    let message = |text: &str| match parse_counts_csv(text){
+        Err(ScenarioError::Csv{line, message}) => (line, message),
+        other => panic!("expected a CSV error, found {:?}", other)
+    };
+    assert_eq!(message("intersection,arm,vehicles_per_hour\n1,E,900\n1,X,900\n"), (3, "'X' is not a direction".to_string()));
+    assert_eq!(message("1,E,900\n2,Q,0,600,0\n"), (2, "'Q' is not a direction".to_string()));
+    assert_eq!(message("1,E\n"), (1, "expected 3, 5 or 6 fields, found 2".to_string()));
+}
+
+#[test]
+fn geh_is_zero_for_equal_counts()
+{
+    assert_eq!(geh(400.0, 400.0), 0.0);
+    assert!((geh(500.0, 400.0) - 4.714).abs() < 0.001);
+}
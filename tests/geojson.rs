@@ -0,0 +1,65 @@
+use traffic_sim::simulator::{Simulator, Current, Position};
+use traffic_sim::traffic_logic::{geometry::Point, intersection::{IntersectionId, SignalPlan}};
+use traffic_sim::visualizer::geojson::to_geojson;
+use serde_json::json;
+
+fn two_intersections() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(2);
+    simulator.add_link(IntersectionId(1), 1, IntersectionId(2), 3, 10, 2);
+    simulator
+}
+
+#[test]
+fn intersections_are_points_and_roads_are_lines()
+{
+    let mut simulator = two_intersections();
+    simulator.set_position(IntersectionId(1), Point::new(-111.9, 33.4));
+    simulator.set_position(IntersectionId(2), Point::new(-111.8, 33.4));
+    simulator.set_shape(IntersectionId(1), 1, vec![Point::new(-111.85, 33.5)]);
+    simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 3}), None));
+
+    let geojson = to_geojson(&simulator);
+    assert_eq!(geojson["type"], "FeatureCollection");
+    let features = geojson["features"].as_array().unwrap();
+    assert_eq!(features.len(), 3);
+
+    let first = &features[0];
+    assert_eq!(first["type"], "Feature");
+    assert_eq!(first["geometry"], json!({"type": "Point", "coordinates": [-111.9, 33.4]}));
+    assert_eq!(first["properties"]["kind"], "intersection");
+    assert_eq!(first["properties"]["id"], 1);
+    assert_eq!(first["properties"]["light_queues"], json!([0, 0, 0, 1]));
+    assert_eq!(first["properties"]["queued"], 1);
+
+    let road = &features[2];
+    assert_eq!(road["geometry"], json!({"type": "LineString", "coordinates": [[-111.9, 33.4], [-111.85, 33.5], [-111.8, 33.4]]}));
+    let properties = &road["properties"];
+    assert_eq!((&properties["kind"], &properties["from"], &properties["to"]), (&json!("road"), &json!(1), &json!(2)));
+    assert_eq!((&properties["arm"], &properties["arrival"], &properties["distance"], &properties["lanes"]), (&json!(1), &json!(3), &json!(10), &json!(2)));
+    assert_eq!((&properties["vehicles"], &properties["closed"], &properties["tick"]), (&json!(0), &json!(false), &json!(0)));
+}
+
+#[test]
+fn snapshots_show_cars_on_roads_and_closures()
+{
+    let mut simulator = two_intersections();
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::uncontrolled());
+    simulator.add_trip(Current{int_id: IntersectionId(1), direction: 3}, IntersectionId(2)).unwrap();
+    simulator.close_arm(IntersectionId(2), 3, 3, 100);
+    simulator.run(3);
+    let geojson = to_geojson(&simulator);
+    let road = &geojson["features"][2]["properties"];
+    assert_eq!(road["vehicles"], 1);
+    assert_eq!(road["closed"], true);
+    assert_eq!(road["tick"], 3);
+}
+
+#[test]
+fn networks_without_positions_are_laid_out_along_their_arms()
+{
+    let geojson = to_geojson(&two_intersections());
+    assert_eq!(geojson["features"][0]["geometry"]["coordinates"], json!([0.0, 0.0]));
+    assert_eq!(geojson["features"][1]["geometry"]["coordinates"], json!([10.0, 0.0]));
+}
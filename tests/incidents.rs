@@ -0,0 +1,112 @@
+use traffic_sim::simulator::{Simulator, Current, Position};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan};
+
+///A square of uncontrolled intersections, the way from 1 to 3 through 2 is shorter than through 4
+fn square() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(4);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    simulator.add_road(IntersectionId(2), 0, IntersectionId(3), 5);
+    simulator.add_road(IntersectionId(1), 0, IntersectionId(4), 8);
+    simulator.add_road(IntersectionId(4), 1, IntersectionId(3), 8);
+    for id in 1..=4{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator
+}
+
+fn first_arm(simulator : &Simulator) -> u8
+{
+    simulator.completed_trips().last().unwrap().path[0].1
+}
+
+#[test]
+fn trips_go_around_a_closed_road_until_it_reopens()
+{
+    let mut simulator = square();
+    simulator.close_road(IntersectionId(1), IntersectionId(2), 0, 100);
+    assert!(simulator.is_closed(IntersectionId(1), 1));
+    assert!(simulator.is_closed(IntersectionId(2), 3));
+
+    simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(3)).unwrap();
+    simulator.run(40);
+    assert_eq!(first_arm(&simulator), 0);
+
+    simulator.reopen_road(IntersectionId(1), IntersectionId(2));
+    assert!(!simulator.is_closed(IntersectionId(1), 1));
+    simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(3)).unwrap();
+    simulator.run(40);
+    assert_eq!(simulator.completed_trips().len(), 2);
+    assert_eq!(first_arm(&simulator), 1);
+}
+
+#[test]
+fn closures_end_on_their_own()
+{
+    let mut simulator = square();
+    simulator.close_arm(IntersectionId(1), 1, 0, 10);
+    simulator.run(10);
+    assert!(!simulator.is_closed(IntersectionId(1), 1));
+    simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(3)).unwrap();
+    simulator.run(40);
+    assert_eq!(first_arm(&simulator), 1);
+}
+
+#[test]
+fn cars_without_a_trip_never_turn_onto_a_closed_road()
+{
+    let mut simulator = square();
+    simulator.close_arm(IntersectionId(1), 1, 0, 1000);
+    for _ in 0..20{
+        simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 3}), None));
+    }
+    for _ in 0..60{
+        simulator.run(1);
+        assert_eq!(simulator.link_counts().get(&(IntersectionId(1), 1)), None);
+    }
+}
+
+#[test]
+fn cars_already_on_a_road_drive_to_the_end_when_it_closes()
+{
+    let mut simulator = square();
+    let car = simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(2)).unwrap();
+    simulator.run(2);
+    assert!(simulator.get_position(car).unwrap().in_between.is_some());
+    simulator.close_road(IntersectionId(1), IntersectionId(2), 2, 100);
+    simulator.run(10);
+    assert_eq!(simulator.completed_trips()[0].destination, IntersectionId(2));
+}
+
+#[test]
+fn incidents_let_fewer_cars_off_a_road()
+{
+    let travel = |capacity : Option<f64>| {
+        let mut simulator = square();
+        if let Some(capacity) = capacity{
+            simulator.add_incident(IntersectionId(1), IntersectionId(2), 0, 1000, capacity);
+        }
+        for _ in 0..10{
+            simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(2)).unwrap();
+        }
+        simulator.run(100);
+        simulator.completed_trips().iter().map(|trip| trip.arrive_tick).max().unwrap()
+    };
+    assert!(travel(Some(0.25)) > travel(None) + 20);
+}
+
+#[test]
+fn closing_an_arm_closes_every_road_arriving_at_it()
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_link(IntersectionId(2), 3, IntersectionId(1), 1, 5, 1);
+    simulator.add_link(IntersectionId(3), 0, IntersectionId(1), 1, 5, 1);
+    simulator.add_link(IntersectionId(1), 1, IntersectionId(2), 3, 5, 1);
+    simulator.close_arm(IntersectionId(1), 1, 0, 100);
+    assert!([(1, 1), (2, 3), (3, 0)].iter().all(|(int_id, arm)| simulator.is_closed(IntersectionId(*int_id), *arm)));
+    simulator.run(1);
+    simulator.reopen_arm(IntersectionId(1), 1);
+    assert!([(1, 1), (2, 3), (3, 0)].iter().all(|(int_id, arm)| !simulator.is_closed(IntersectionId(*int_id), *arm)));
+}
@@ -0,0 +1,132 @@
+use traffic_sim::simulator::{Simulator, Position, Between, microscopic::Idm, lanes::Mobil};
+use traffic_sim::traffic_logic::{car::{CarId, TurningProportions}, intersection::{IntersectionId, SignalPlan}, vehicle::VehicleClass};
+
+///A three lane road from 1 east to 2, where every car turns left onto the road north to 3
+fn left_turn_ahead() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_link(IntersectionId(1), 1, IntersectionId(2), 3, 30, 3);
+    simulator.add_road(IntersectionId(2), 0, IntersectionId(3), 10);
+    for id in 1..=3{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator.set_turning_proportions(IntersectionId(2), 3, Some(TurningProportions::new(1.0, 0.0, 0.0, 0.0)));
+    simulator.set_microscopic(Some(Idm::default()));
+    simulator
+}
+
+#[test]
+fn cars_move_over_to_the_lane_they_turn_from()
+{
+    let mut simulator = left_turn_ahead();
+    let car = simulator.add_car(Position::new(None, Some(Between{int_1_id: IntersectionId(1), int_2_id: IntersectionId(2), distance_to_target: 25, from: 3})));
+    for _ in 0..60{
+        simulator.run(1);
+        if simulator.get_position(car).unwrap().in_between.is_some_and(|between| between.int_2_id == IntersectionId(3)){
+            break;
+        }
+    }
+    assert_eq!(simulator.get_position(car).unwrap().in_between.unwrap().int_2_id, IntersectionId(3));
+
+    let changes: Vec<_> = simulator.lane_changes().iter().filter(|change| change.car_id == car).collect();
+    let lanes: Vec<(u8, u8)> = changes.iter().map(|change| (change.from_lane, change.to_lane)).collect();
+    assert_eq!(lanes, vec![(0, 1), (1, 2)]);
+    for change in changes{
+        assert!(change.mandatory);
+        assert_eq!(change.link, (IntersectionId(1), IntersectionId(2)));
+        assert!(change.remaining < simulator.lane_changing().turn_distance);
+    }
+}
+
+#[test]
+fn cars_far_from_their_turn_stay_in_their_lane()
+{
+    let mut simulator = left_turn_ahead();
+    simulator.add_car(Position::new(None, Some(Between{int_1_id: IntersectionId(1), int_2_id: IntersectionId(2), distance_to_target: 25, from: 3})));
+    simulator.run(5);
+    assert!(simulator.lane_changes().is_empty());
+}
+
+const TWO_LANES : (IntersectionId, IntersectionId) = (IntersectionId(1), IntersectionId(2));
+
+///A two lane road of 30 ticks from 1 east to 2, which cars join by turning right from the road from 4
+/// in the south, into the kerb lane, or left from the road from 5 in the north, into the other lane.
+/// From 2 they drive on to 3, on its arm `exit`.
+fn two_lanes(exit : u8) -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(5);
+    simulator.add_link(IntersectionId(1), 1, IntersectionId(2), 3, 30, 2);
+    simulator.add_road(IntersectionId(2), exit, IntersectionId(3), 10);
+    simulator.add_road(IntersectionId(1), 2, IntersectionId(4), 20);
+    simulator.add_road(IntersectionId(1), 0, IntersectionId(5), 10);
+    for id in 1..=5{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator.set_turning_proportions(IntersectionId(1), 2, Some(TurningProportions::new(0.0, 0.0, 1.0, 0.0)));
+    simulator.set_turning_proportions(IntersectionId(1), 0, Some(TurningProportions::new(1.0, 0.0, 0.0, 0.0)));
+    simulator.set_microscopic(Some(Idm::default()));
+    simulator
+}
+
+fn coming_from(simulator : &mut Simulator, from : u32, side : u8, distance_to_target : u32) -> CarId
+{
+    simulator.add_car(Position::new(None, Some(Between{int_1_id: IntersectionId(from), int_2_id: IntersectionId(1), distance_to_target, from: side})))
+}
+
+#[test]
+fn cars_overtake_a_bicycle_in_the_next_lane()
+{
+    let mut simulator = two_lanes(1);
+    simulator.set_turning_proportions(IntersectionId(2), 3, Some(TurningProportions::new(0.0, 1.0, 0.0, 0.0)));
+    //a polite cyclist would move over for the car instead
+    simulator.set_lane_changing(Mobil{politeness: 0.0, ..Mobil::default()});
+    let bicycle = coming_from(&mut simulator, 4, 2, 2);
+    simulator.set_vehicle_class(bicycle, VehicleClass::Bicycle);
+    let car = coming_from(&mut simulator, 4, 2, 12);
+    simulator.run(40);
+
+    let changes: Vec<_> = simulator.lane_changes().iter().map(|change| (change.car_id, change.link, change.from_lane, change.to_lane, change.mandatory)).collect();
+    assert_eq!(changes, vec![(car, TWO_LANES, 0, 1, false)]);
+    let (bicycle, car) = (simulator.kinematics(bicycle).unwrap(), simulator.kinematics(car).unwrap());
+    assert_eq!((bicycle.link, bicycle.lane, car.link, car.lane), (TWO_LANES, 0, TWO_LANES, 1));
+    assert!(car.position > bicycle.position + 100.0);
+}
+
+#[test]
+fn cars_wait_for_a_safe_gap_to_get_into_the_lane_they_turn_from()
+{
+    let mut simulator = two_lanes(0);
+    //every car turns left at 2, from the lane further from the kerb
+    simulator.set_turning_proportions(IntersectionId(2), 3, Some(TurningProportions::new(1.0, 0.0, 0.0, 0.0)));
+    let idm = Idm::default();
+    let mobil = *simulator.lane_changing();
+    let turning = coming_from(&mut simulator, 4, 2, 2);
+    //a tick behind, in the lane the first car needs
+    let behind = coming_from(&mut simulator, 5, 0, 3);
+
+    let mut refused = Vec::new();
+    for _ in 0..40{
+        let tick = simulator.timestep();
+        let (before, other) = (simulator.kinematics(turning).copied(), simulator.kinematics(behind).copied());
+        simulator.run(1);
+        let (Some(before), Some(other)) = (before, other) else { continue };
+        if before.link != TWO_LANES || other.link != TWO_LANES || before.lane != 0 || before.remaining() >= mobil.turn_distance || other.position > before.position{
+            continue;
+        }
+        //the car behind would have to brake harder than is safe for the car to move in front of it
+        let gap = before.position - VehicleClass::PassengerCar.params().length - other.position;
+        if idm.acceleration(VehicleClass::PassengerCar, other.speed, gap, other.speed - before.speed) < -mobil.safe_deceleration{
+            refused.push(tick);
+            assert_eq!(simulator.kinematics(turning).unwrap().lane, 0);
+        }
+    }
+    assert!(!refused.is_empty());
+
+    //it moved over once the other car had gone past, and made its turn
+    let change = simulator.lane_changes().iter().find(|change| change.car_id == turning).unwrap();
+    assert!(change.mandatory);
+    assert!(refused.iter().all(|tick| *tick < change.tick));
+    assert!(simulator.get_position(turning).unwrap().in_between.is_some_and(|between| between.int_1_id == IntersectionId(2)));
+}
@@ -0,0 +1,142 @@
+use traffic_sim::simulator::{Simulator, demand::{Demand, OdPair}, macroscopic::Ctm};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan, Phase, LightConfig};
+
+#[test]
+fn pairs_without_trips_leave_the_model_empty_rather_than_undefined()
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 20);
+    simulator.add_road(IntersectionId(2), 1, IntersectionId(3), 20);
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::uncontrolled());
+    //only the empty pair drives the roads from 3 back to 1
+    simulator.set_demand(Demand::new(vec![
+        OdPair{origin: IntersectionId(1), destination: IntersectionId(3), trips_per_hour: 600.0},
+        OdPair{origin: IntersectionId(3), destination: IntersectionId(1), trips_per_hour: 0.0}
+    ]));
+    let mut ctm = simulator.cell_transmission(Ctm::default());
+    ctm.run(600);
+    assert!(ctm.vehicles().is_finite());
+    assert!(ctm.completed() > 0.0);
+    assert_eq!(ctm.density(IntersectionId(3), 3), Some(0.0));
+    assert!(ctm.series().iter().all(|sample| sample.density.is_finite() && sample.flow.is_finite()));
+}
+
+///Roads of `ticks` from 1 east to 2 and on to 3 with `lanes` lanes, and `trips_per_hour` from 1 to 3
+fn corridor(ticks : u32, lanes : u8, trips_per_hour : f64, plan : SignalPlan) -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_link(IntersectionId(1), 1, IntersectionId(2), 3, ticks, lanes);
+    simulator.add_link(IntersectionId(2), 1, IntersectionId(3), 3, ticks, lanes);
+    simulator.set_signal_plan(IntersectionId(2), plan);
+    simulator.set_demand(Demand::new(vec![OdPair{origin: IntersectionId(1), destination: IntersectionId(3), trips_per_hour}]));
+    simulator
+}
+
+#[test]
+fn every_vehicle_generated_is_on_a_road_waiting_or_arrived()
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(4);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 10);
+    simulator.add_road(IntersectionId(2), 1, IntersectionId(3), 10);
+    simulator.add_road(IntersectionId(2), 2, IntersectionId(4), 10);
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::four_phase(20, 5));
+    simulator.set_demand(Demand::new(vec![
+        OdPair{origin: IntersectionId(1), destination: IntersectionId(3), trips_per_hour: 1500.0},
+        OdPair{origin: IntersectionId(1), destination: IntersectionId(4), trips_per_hour: 900.0},
+        OdPair{origin: IntersectionId(4), destination: IntersectionId(3), trips_per_hour: 600.0}
+    ]));
+    let mut ctm = simulator.cell_transmission(Ctm::default());
+    for ticks in [1, 59, 300, 540]{
+        ctm.run(ticks);
+        let generated = 3000.0 * ctm.timestep() as f64 / 3600.0;
+        let total = ctm.vehicles() + ctm.waiting() + ctm.completed();
+        assert!((total - generated).abs() < 1e-6, "{} vehicles of {} generated", total, generated);
+    }
+    assert!(ctm.completed() > 0.0 && ctm.vehicles() > 0.0);
+}
+
+#[test]
+fn a_red_light_holds_traffic_and_the_queue_spills_back_one_cell_after_another()
+{
+    let red = SignalPlan::new(vec![Phase{config: LightConfig::NorthSouth, duration: 1000}], 0);
+    let ctm_settings = Ctm::default();
+    let mut ctm = corridor(10, 1, 1500.0, red).cell_transmission(ctm_settings);
+    let diagram = ctm_settings.diagram;
+    let jam = diagram.jam_density * diagram.free_speed * ctm_settings.tick_seconds;
+    //the tick every cell of the road to the light first holds most of a standing queue
+    let mut jammed = vec![None; 10];
+    for tick in 1..=600{
+        ctm.run(1);
+        let cells = ctm.cells(IntersectionId(1), 1).unwrap();
+        assert_eq!(cells.len(), 10);
+        for (cell, jammed) in cells.iter().zip(jammed.iter_mut()){
+            if jammed.is_none() && *cell >= 0.9 * jam{
+                *jammed = Some(tick);
+            }
+        }
+        assert!(cells.iter().all(|vehicles| *vehicles <= jam + 1e-9));
+    }
+    assert_eq!((ctm.completed(), ctm.density(IntersectionId(2), 1)), (0.0, Some(0.0)));
+    let jammed: Vec<usize> = jammed.into_iter().map(|tick| tick.expect("A cell never filled up")).collect();
+    assert!(jammed.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", jammed);
+    assert!(ctm.waiting() > 0.0);
+}
+
+#[test]
+fn no_road_carries_more_than_its_capacity()
+{
+    let settings = Ctm::default();
+    let mut ctm = corridor(10, 2, 5000.0, SignalPlan::uncontrolled()).cell_transmission(settings);
+    ctm.run(1200);
+    let capacity = settings.diagram.capacity() * 2.0 * 3600.0;
+    assert!(capacity < 5000.0);
+    assert!(ctm.series().iter().all(|sample| sample.flow <= capacity + 1e-6));
+    //the demand above capacity waits at its origin and the rest goes at capacity
+    let last = ctm.series().iter().rfind(|sample| sample.link == (IntersectionId(2), 1)).unwrap();
+    assert!((last.flow - capacity).abs() < 1e-6, "{} of {}", last.flow, capacity);
+    assert!(ctm.waiting() > 0.0);
+}
+
+#[test]
+fn an_uncongested_road_has_the_density_of_its_flow_at_the_free_speed()
+{
+    let settings = Ctm::default();
+    let mut ctm = corridor(20, 1, 600.0, SignalPlan::uncontrolled()).cell_transmission(settings);
+    ctm.run(300);
+    //600 vehicles an hour at the free speed, a vehicle every 6 seconds, one every 83.3 metres
+    let density = 600.0 / (settings.diagram.free_speed * 3.6);
+    let samples: Vec<_> = ctm.series().iter().filter(|sample| sample.tick > 60).collect();
+    assert_eq!(samples.len(), 4 * 2);
+    for sample in samples{
+        assert!((sample.flow - 600.0).abs() < 1e-6, "{:?}", sample);
+        assert!((sample.density - density).abs() < 1e-6, "{:?}", sample);
+        assert!((sample.vehicles - density * 20.0 * settings.diagram.free_speed / 1000.0).abs() < 1e-6, "{:?}", sample);
+    }
+    assert_eq!(ctm.waiting(), 0.0);
+}
+
+#[test]
+fn traffic_waiting_to_turn_on_red_holds_up_the_traffic_behind_it()
+{
+    let held = |pairs : Vec<OdPair>| {
+        let mut simulator = Simulator::new();
+        simulator.add_intersections(4);
+        simulator.add_link(IntersectionId(1), 1, IntersectionId(2), 3, 10, 1);
+        simulator.add_link(IntersectionId(2), 1, IntersectionId(3), 3, 10, 1);
+        simulator.add_link(IntersectionId(2), 0, IntersectionId(4), 2, 10, 1);
+        //traffic from 1 may go straight on to 3 but never turn left to 4
+        simulator.set_signal_plan(IntersectionId(2), SignalPlan::new(vec![Phase{config: LightConfig::EastWest, duration: 1000}], 0));
+        simulator.set_demand(Demand::new(pairs));
+        let mut ctm = simulator.cell_transmission(Ctm::default());
+        ctm.run(300);
+        ctm.completed()
+    };
+    let straight = OdPair{origin: IntersectionId(1), destination: IntersectionId(3), trips_per_hour: 600.0};
+    assert!(held(vec![straight]) > 0.0);
+    //the road sends its traffic in the order it came, so the first vehicle turning left stops every other
+    let left = OdPair{origin: IntersectionId(1), destination: IntersectionId(4), trips_per_hour: 300.0};
+    assert_eq!(held(vec![straight, left]), 0.0);
+}
@@ -0,0 +1,134 @@
+use traffic_sim::simulator::{Simulator, Current, Position, demand::{Demand, OdPair}, mesoscopic::{Meso, Unmodelled}};
+use traffic_sim::traffic_logic::{car::{CarId, TurningProportions}, driver::DriverProfile, intersection::{IntersectionId, SignalPlan}, vehicle::VehicleClass};
+
+///Three uncontrolled intersections in a row, with roads of 20 ticks from 1 to 2 and from 2 to 3
+fn corridor() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 20);
+    simulator.add_road(IntersectionId(2), 1, IntersectionId(3), 20);
+    for id in 1..=3{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator
+}
+
+const START : Current = Current{int_id: IntersectionId(1), direction: 3};
+
+#[test]
+fn scheduled_trips_take_about_as_long_as_in_the_simulator()
+{
+    let mut simulator = corridor();
+    for trip in 0..10{
+        simulator.schedule_trip(trip * 10, START, IntersectionId(3));
+    }
+    let mut meso = simulator.mesoscopic(Meso::default());
+    simulator.run(200);
+    meso.run(200);
+    //the simulator gives the IDs of cars that arrived to later cars, so trips are matched by their serial
+    let mut micro: Vec<(u64, usize)> = simulator.completed_trips().iter().map(|trip| (trip.serial, trip.travel_time())).collect();
+    let mut macro_: Vec<(u64, usize)> = meso.completed_trips().iter().map(|trip| (trip.serial, trip.travel_time())).collect();
+    micro.sort();
+    macro_.sort();
+    assert_eq!(micro.len(), 10);
+    assert_eq!(micro.iter().map(|trip| trip.0).collect::<Vec<_>>(), macro_.iter().map(|trip| trip.0).collect::<Vec<_>>());
+    //vehicles in the mesoscopic model wait for the capacity of every intersection and slow down for themselves,
+    // which adds a few ticks to a trip of 40
+    for ((_, micro), (_, meso)) in micro.iter().zip(macro_.iter()){
+        assert!(micro.abs_diff(*meso) <= micro / 4, "{} ticks in the simulator against {} in the mesoscopic model", micro, meso);
+    }
+}
+
+#[test]
+fn cars_already_on_a_trip_carry_on_with_their_id()
+{
+    let mut simulator = corridor();
+    let car_id = simulator.add_trip(START, IntersectionId(3)).unwrap();
+    simulator.run(5);
+    assert!(simulator.get_position(car_id).unwrap().in_between.is_some());
+    simulator.schedule_trip(10, START, IntersectionId(3));
+    let mut meso = simulator.mesoscopic(Meso::default());
+    meso.run(100);
+    let trips = meso.completed_trips();
+    assert_eq!(trips.len(), 2);
+    assert_eq!((trips[0].car_id, trips[0].depart_tick), (car_id, 0));
+    assert_eq!(trips[0].path[0].0, IntersectionId(1));
+    assert_eq!(trips[1].car_id, CarId(car_id.0 + 1));
+}
+
+#[test]
+fn vehicles_leave_a_road_in_the_order_they_joined_it()
+{
+    let mut simulator = corridor();
+    let bicycle = simulator.add_trip(START, IntersectionId(3)).unwrap();
+    simulator.set_vehicle_class(bicycle, VehicleClass::Bicycle);
+    let car = simulator.add_trip(START, IntersectionId(3)).unwrap();
+    let mut meso = simulator.mesoscopic(Meso::default());
+    meso.run(300);
+    let trips = meso.completed_trips();
+    assert_eq!(trips.iter().map(|trip| trip.car_id).collect::<Vec<_>>(), vec![bicycle, car]);
+    assert!(trips[1].path[1].2 >= trips[0].path[1].2);
+}
+
+#[test]
+fn what_the_model_leaves_out_is_reported()
+{
+    let mut simulator = corridor();
+    assert_eq!(simulator.mesoscopic(Meso::default()).unmodelled(), Unmodelled::default());
+    simulator.add_car(Position::new(Some(START), None));
+    simulator.schedule_car(5, START);
+    simulator.set_turning_proportions(IntersectionId(2), 3, Some(TurningProportions::new(0.0, 1.0, 0.0, 0.0)));
+    let mut meso = simulator.mesoscopic(Meso::default());
+    assert_eq!(meso.unmodelled(), Unmodelled{random_cars: 2, transit_lines: 0, turning_proportions: 1, rerouting: 0});
+    meso.run(100);
+    assert_eq!(meso.vehicles(), 0);
+    assert!(meso.completed_trips().is_empty());
+}
+
+#[test]
+fn trips_waiting_to_enter_keep_waiting_and_new_trips_get_a_driver_from_the_mix()
+{
+    let mut simulator = corridor();
+    simulator.set_driver_mix(vec![(DriverProfile::Cautious, 1.0)]);
+    simulator.set_reroute_share(1.0);
+    simulator.set_demand(Demand::new(vec![OdPair{origin: IntersectionId(1), destination: IntersectionId(3), trips_per_hour: 36000.0}]));
+    simulator.run(3);
+    let waiting = simulator.entry_queue_length(IntersectionId(1));
+    assert!(waiting > 0);
+    simulator.set_demand(Demand::new(Vec::new()));
+    simulator.schedule_trip(5, START, IntersectionId(3));
+    let mut meso = simulator.mesoscopic(Meso::default());
+    assert_eq!(meso.waiting(), waiting);
+    //every car on the network reroutes, and so would the trips started from now on
+    let rerouting = simulator.cars().len() + 1;
+    assert_eq!(meso.unmodelled(), Unmodelled{rerouting, ..Unmodelled::default()});
+    meso.run(600);
+    assert_eq!(meso.waiting() + meso.vehicles(), 0);
+    let trips = meso.completed_trips();
+    assert_eq!(trips.len(), rerouting - 1 + waiting + 1);
+    assert!(trips.iter().all(|trip| trip.driver == Some(DriverProfile::Cautious)));
+}
+
+#[test]
+fn a_road_with_an_incident_lets_fewer_vehicles_out()
+{
+    let left = |incident : bool| {
+        let mut simulator = corridor();
+        for _ in 0..6{
+            simulator.schedule_trip(0, START, IntersectionId(3));
+        }
+        if incident{
+            simulator.add_incident(IntersectionId(1), IntersectionId(2), 0, 1000, 0.25);
+        }
+        let mut meso = simulator.mesoscopic(Meso::default());
+        meso.run(200);
+        let mut ticks: Vec<usize> = meso.completed_trips().iter().map(|trip| trip.path[1].2).collect();
+        ticks.sort();
+        assert_eq!(ticks.len(), 6);
+        ticks.last().unwrap() - ticks.first().unwrap()
+    };
+    //the cars join the road every other tick and slow each other down a little, the incident only lets a car out every fourth tick
+    assert!(left(false) < 15);
+    assert_eq!(left(true), 20);
+}
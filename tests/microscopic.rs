@@ -0,0 +1,98 @@
+use traffic_sim::simulator::{Simulator, Position, Between, microscopic::{Idm, TrajectoryPoint}};
+use traffic_sim::traffic_logic::{car::CarId, intersection::{IntersectionId, SignalPlan, Phase, LightConfig}, vehicle::REFERENCE_SPEED};
+
+const LINK : (IntersectionId, IntersectionId) = (IntersectionId(1), IntersectionId(2));
+
+///A one way road of 20 ticks from 1 east to 2 and on to 3, where cars from 1 wait at the west side of
+/// 2 for `red` ticks before the light lets them go
+fn red_light(red : usize) -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_link(IntersectionId(1), 1, IntersectionId(2), 3, 20, 1);
+    simulator.add_link(IntersectionId(2), 1, IntersectionId(3), 3, 20, 1);
+    for id in [1, 3]{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    let phases = vec![Phase{config: LightConfig::NorthSouth, duration: red}, Phase{config: LightConfig::EastWest, duration: 1000}];
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::new(phases, 0));
+    simulator.set_microscopic(Some(Idm::default().with_trajectories()));
+    simulator
+}
+
+fn on_road(simulator : &mut Simulator, distance_to_target : u32) -> CarId
+{
+    simulator.add_car(Position::new(None, Some(Between{int_1_id: IntersectionId(1), int_2_id: IntersectionId(2), distance_to_target, from: 3})))
+}
+
+///The points of a car on the road from 1 to 2, in the order of their ticks
+fn approach(simulator : &Simulator, car : CarId) -> Vec<TrajectoryPoint>
+{
+    simulator.trajectories().iter().filter(|point| point.car_id == car && point.link == LINK).copied().collect()
+}
+
+#[test]
+fn cars_brake_to_a_stand_at_the_stop_bar_on_red()
+{
+    let mut simulator = red_light(1000);
+    let car = on_road(&mut simulator, 15);
+    simulator.run(60);
+    let points = approach(&simulator, car);
+    let length = 20.0 * REFERENCE_SPEED;
+
+    let braking = points.iter().position(|point| point.acceleration < 0.0).expect("The car never braked");
+    let arrived = points.iter().position(|point| point.position >= length - 1.0).expect("The car never reached the stop bar");
+    assert!(points[braking..=arrived].windows(2).all(|pair| pair[1].speed <= pair[0].speed));
+    //once at the stop bar the car stays there and comes to a stand
+    assert!(points[arrived..].iter().all(|point| point.position >= length - 1.0 && point.position <= length));
+    let last = points.last().unwrap();
+    assert_eq!((last.tick, last.speed), (59, 0.0));
+    assert!(simulator.kinematics(car).is_some_and(|state| state.stopping && state.waiting > 0));
+}
+
+#[test]
+fn a_queue_sets_off_one_car_after_another()
+{
+    let mut simulator = red_light(40);
+    let cars: Vec<CarId> = [9, 11, 13, 15].into_iter().map(|distance| on_road(&mut simulator, distance)).collect();
+    simulator.run(40);
+    assert!(cars.iter().skip(1).all(|car| simulator.kinematics(*car).is_some_and(|state| state.speed < 0.1)));
+
+    //the tick each car, from the front of the queue back, first moves after the light turns green
+    let mut started = vec![None; cars.len()];
+    for tick in 40..80{
+        simulator.run(1);
+        for (car, started) in cars.iter().zip(started.iter_mut()){
+            let moving = simulator.kinematics(*car).is_none_or(|state| state.link != LINK || state.speed >= 0.1);
+            if started.is_none() && moving{
+                *started = Some(tick);
+            }
+        }
+    }
+    let started: Vec<usize> = started.into_iter().map(|tick| tick.expect("A car never set off")).collect();
+    assert!(started.windows(2).all(|pair| pair[1] > pair[0]), "{:?}", started);
+}
+
+#[test]
+fn trajectories_only_go_forward()
+{
+    let mut simulator = red_light(40);
+    for distance in [15, 13, 11, 9]{
+        on_road(&mut simulator, distance);
+    }
+    simulator.run(100);
+    let points = simulator.trajectories();
+    assert!(!points.is_empty());
+    let mut cars: Vec<(CarId, (IntersectionId, IntersectionId))> = points.iter().map(|point| (point.car_id, point.link)).collect();
+    cars.sort();
+    cars.dedup();
+    for (car, link) in cars{
+        let path: Vec<&TrajectoryPoint> = points.iter().filter(|point| point.car_id == car && point.link == link).collect();
+        assert!(path.windows(2).all(|pair| pair[1].tick == pair[0].tick + 1), "car {:?} skipped a tick on {:?}", car, link);
+        assert!(path.windows(2).all(|pair| pair[1].position >= pair[0].position), "car {:?} went back on {:?}", car, link);
+        assert!(path.iter().all(|point| point.speed >= 0.0 && point.position >= 0.0));
+    }
+    //no road leads out of 3 so the cars stop at its stop bar rather than drive over it
+    let dead_end: Vec<&TrajectoryPoint> = points.iter().filter(|point| point.link == (IntersectionId(2), IntersectionId(3))).collect();
+    assert!(!dead_end.is_empty() && dead_end.iter().all(|point| point.position <= 20.0 * REFERENCE_SPEED));
+}
@@ -0,0 +1,105 @@
+use traffic_sim::scenario::{RoadSpec, osm::{parse_osm, OsmImport, OsmOptions}};
+use traffic_sim::traffic_logic::{intersection::SignalPlan, road::CardinalDirection::{self, North, East, South, West}};
+
+///A way as its node IDs and tags
+type WaySpec<'a> = (&'a [i64], &'a [(&'a str, &'a str)]);
+
+///A junction at node 1 with nodes 2 to 5 about 111 metres to its north, east, south and west, and the ways
+/// given. Node 1 has signals if `signals` is set.
+fn import(signals : bool, ways : &[WaySpec]) -> OsmImport
+{
+    let mut xml = String::from("<osm>\n");
+    let signal_tag = if signals {r#"<tag k="highway" v="traffic_signals"/>"#} else {""};
+    xml += &format!(r#"<node id="1" lat="0.0" lon="0.0">{}</node>"#, signal_tag);
+    for (id, lat, lon) in [(2, 0.001, 0.0), (3, 0.0, 0.001), (4, -0.001, 0.0), (5, 0.0, -0.001)]{
+        xml += &format!(r#"<node id="{}" lat="{}" lon="{}"/>"#, id, lat, lon);
+    }
+    for (i, (nodes, tags)) in ways.iter().enumerate(){
+        xml += &format!(r#"<way id="{}">"#, 100 + i);
+        for node in nodes.iter(){
+            xml += &format!(r#"<nd ref="{}"/>"#, node);
+        }
+        for (k, v) in tags.iter(){
+            xml += &format!(r#"<tag k="{}" v="{}"/>"#, k, v);
+        }
+        xml += "</way>\n";
+    }
+    xml += "</osm>";
+    parse_osm(&xml, &OsmOptions::default()).unwrap()
+}
+
+fn road<'a>(import : &'a OsmImport, from : &str, to : &str) -> &'a RoadSpec
+{
+    import.scenario.roads.iter().find(|road| road.from == from && road.to == to).unwrap()
+}
+
+#[test]
+fn one_way_roads_are_driven_the_way_they_are_tagged()
+{
+    let import = import(false, &[(&[1, 2], &[("highway", "primary"), ("oneway", "yes")]), (&[1, 3], &[("highway", "primary"), ("oneway", "-1")]), (&[1, 4], &[("highway", "primary")])]);
+    assert!(road(&import, "1", "2").one_way);
+    let reversed = road(&import, "3", "1");
+    assert!(reversed.one_way);
+    assert_eq!((reversed.direction, reversed.to_direction), (West, None));
+    assert!(!road(&import, "1", "4").one_way);
+}
+
+#[test]
+fn lanes_are_split_between_the_directions_of_two_way_roads()
+{
+    let import = import(false, &[(&[1, 2], &[("highway", "primary"), ("lanes", "4")]), (&[1, 3], &[("highway", "primary"), ("oneway", "yes"), ("lanes", "3")]),
+        (&[1, 4], &[("highway", "primary")])]);
+    assert_eq!(road(&import, "1", "2").lanes, 2);
+    assert_eq!(road(&import, "1", "3").lanes, 3);
+    assert_eq!(road(&import, "1", "4").lanes, 1);
+}
+
+#[test]
+fn signal_nodes_get_a_signal_plan_and_other_junctions_none()
+{
+    let ways: &[WaySpec] = &[(&[2, 1, 4], &[("highway", "primary")]), (&[3, 1, 5], &[("highway", "primary")])];
+    let plan = |import : &OsmImport, name : &str| import.scenario.intersections.iter().find(|intersection| intersection.name == name).unwrap().signal_plan.clone().unwrap();
+    let signalized = import(true, ways);
+    assert_eq!(plan(&signalized, "1"), SignalPlan::four_phase(20, 5));
+    assert!(plan(&signalized, "2").is_uncontrolled());
+    assert!(plan(&import(false, ways), "1").is_uncontrolled());
+}
+
+#[test]
+fn roads_leave_on_the_arm_closest_to_their_bearing()
+{
+    let import = import(false, &[(&[1, 2], &[("highway", "primary")]), (&[1, 3], &[("highway", "primary")]), (&[1, 4], &[("highway", "primary")]), (&[1, 5], &[("highway", "primary")])]);
+    let arms: Vec<(CardinalDirection, Option<CardinalDirection>)> = ["2", "3", "4", "5"].iter().map(|to| {
+        let road = road(&import, "1", to);
+        (road.direction, road.to_direction)
+    }).collect();
+    assert_eq!(arms, vec![(North, None), (East, None), (South, None), (West, None)]);
+}
+
+#[test]
+fn the_speed_limit_sets_how_many_ticks_a_road_takes()
+{
+    //every road is about 111.2 metres long
+    let import = import(false, &[(&[1, 2], &[("highway", "primary"), ("maxspeed", "40")]), (&[1, 3], &[("highway", "primary"), ("maxspeed", "25 mph")]),
+        (&[1, 4], &[("highway", "residential")]), (&[1, 5], &[("highway", "road")])]);
+    assert_eq!(road(&import, "1", "2").distance, 10);
+    assert_eq!(road(&import, "1", "3").distance, 10);
+    assert_eq!(road(&import, "1", "4").distance, 13);
+    assert_eq!(road(&import, "1", "5").distance, 8);
+}
+
+#[test]
+fn both_carriageways_of_a_divided_road_are_kept()
+{
+    let import = import(false, &[(&[1, 2], &[("highway", "primary"), ("oneway", "yes"), ("lanes", "2")]), (&[2, 1], &[("highway", "primary"), ("oneway", "yes"), ("lanes", "2")])]);
+    assert_eq!(import.scenario.roads.len(), 1);
+    assert!(!road(&import, "1", "2").one_way);
+    assert!(import.warnings.is_empty());
+    let simulator = import.scenario.to_simulator().unwrap();
+    assert_eq!(simulator.road().road.values().flatten().flatten().count(), 2);
+
+    let import = self::import(false, &[(&[1, 2], &[("highway", "primary"), ("oneway", "yes")]), (&[2, 1], &[("highway", "primary"), ("oneway", "yes"), ("lanes", "2")]),
+        (&[1, 2], &[("highway", "primary")])]);
+    assert_eq!(import.scenario.roads.len(), 1);
+    assert_eq!(import.warnings.len(), 2);
+}
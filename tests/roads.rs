@@ -0,0 +1,47 @@
+use traffic_sim::simulator::{Simulator, Current};
+use traffic_sim::traffic_logic::{car::Direction, intersection::{IntersectionId, LightConfig, Phase, SignalPlan}};
+
+///Intersection 1 has a road east to the north side of intersection 2, which has a road south to intersection 3
+fn bent_road(plan : SignalPlan) -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_link(IntersectionId(1), 1, IntersectionId(2), 0, 4, 1);
+    simulator.add_link(IntersectionId(2), 2, IntersectionId(3), 0, 4, 1);
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::uncontrolled());
+    simulator.set_signal_plan(IntersectionId(2), plan);
+    simulator
+}
+
+#[test]
+fn cars_arrive_at_the_side_the_road_joins()
+{
+    let mut simulator = bent_road(SignalPlan::uncontrolled());
+    let car = simulator.add_trip(Current{int_id: IntersectionId(1), direction: 3}, IntersectionId(3)).unwrap();
+    simulator.run(1);
+    let between = simulator.get_position(car).unwrap().in_between.unwrap();
+    assert_eq!((between.int_2_id, between.from), (IntersectionId(2), 0));
+    assert_eq!(simulator.road().get_next_intersection(IntersectionId(1), 3, Direction::Straight), Some((IntersectionId(2), 0)));
+}
+
+#[test]
+fn cars_obey_the_light_facing_the_side_they_wait_at()
+{
+    //the light at index 0 is green first, which a car waiting at the north side must not take
+    let plan = SignalPlan::new(vec![Phase{config: LightConfig::NorthLeft, duration: 30}, Phase{config: LightConfig::SouthLeft, duration: 30}], 0);
+    let mut simulator = bent_road(plan);
+    let car = simulator.add_trip(Current{int_id: IntersectionId(1), direction: 3}, IntersectionId(3)).unwrap();
+    simulator.run(10);
+    let current = simulator.get_position(car).unwrap().current_intersection.unwrap();
+    assert_eq!((current.int_id, current.direction), (IntersectionId(2), 0));
+    assert_eq!(simulator.intersections()[1].light_queues[0].front(), Some(&car));
+
+    simulator.run(19);
+    assert!(simulator.get_position(car).unwrap().current_intersection.is_some());
+
+    //the light at index (0+2)%4 turns green on tick 30
+    simulator.run(20);
+    let trip = &simulator.completed_trips()[0];
+    assert_eq!(trip.path.len(), 2);
+    assert_eq!((trip.path[1].0, trip.path[1].1, trip.path[1].2), (IntersectionId(2), 2, 30));
+}
@@ -0,0 +1,81 @@
+use traffic_sim::simulator::{Simulator, Current, saturation::SaturationFlow};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan, Phase, LightConfig, LightStatus};
+
+const NORTH_OF_1 : Current = Current{int_id: IntersectionId(1), direction: 0};
+
+///A queue of 200 cars at the end of a road of `lanes` lanes from 3 to the north side of 1, going on south to 2,
+/// with 27 ticks of green and 3 of yellow in a cycle of 60
+fn queue(lanes : u8, saturation : SaturationFlow) -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_link(IntersectionId(3), 2, IntersectionId(1), 0, 10, lanes);
+    simulator.add_link(IntersectionId(1), 2, IntersectionId(2), 0, 200, lanes);
+    let phases = vec![Phase{config: LightConfig::NorthSouth, duration: 30}, Phase{config: LightConfig::EastWest, duration: 30}];
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::new(phases, 0).with_yellow(3));
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::uncontrolled());
+    simulator.set_saturation_flow(Some(saturation));
+    for _ in 0..200{
+        simulator.add_trip(NORTH_OF_1, IntersectionId(2)).unwrap();
+    }
+    simulator
+}
+
+///Runs a tick at a time and gives the cars that left the queue on each tick
+fn discharged(simulator : &mut Simulator, ticks : usize) -> Vec<usize>
+{
+    (0..ticks).map(|_| {
+        let before = simulator.intersections()[0].light_queues[0].len();
+        simulator.run(1);
+        before - simulator.intersections()[0].light_queues[0].len()
+    }).collect()
+}
+
+#[test]
+fn a_queue_discharges_the_capacity_of_its_effective_green()
+{
+    let saturation = SaturationFlow::default();
+    for lanes in [1, 2]{
+        let mut simulator = queue(lanes, saturation);
+        let left = discharged(&mut simulator, 360);
+        let per_cycle = saturation.capacity(27.0, 3.0, 60.0, lanes) * 60.0 / 3600.0;
+        //what is left of the last car at the end of the effective green is lost, as it is with whole vehicles. The
+        // first cycle is left out as no one knows how long its light had been green before the first tick
+        for cycle in left[60..].chunks(60){
+            let cars = cycle.iter().sum::<usize>() as f64;
+            assert!(cars <= per_cycle && cars > per_cycle - 1.0, "{} cars a cycle from {} lanes against a capacity of {}", cars, lanes, per_cycle);
+        }
+        //a lane lets out a car every 1.9 seconds, two lanes more than one a tick
+        assert_eq!(left.iter().max(), Some(&usize::from(lanes)));
+    }
+}
+
+#[test]
+fn nothing_leaves_in_the_start_up_lost_time()
+{
+    let saturation = SaturationFlow{start_up_lost_time: 5.0, ..SaturationFlow::default()};
+    let mut simulator = queue(2, saturation);
+    let left = discharged(&mut simulator, 180);
+    //the north light turns green on ticks 60 and 120
+    for start in [60, 120]{
+        assert_eq!(simulator.intersections()[0].signal_plan.as_ref().unwrap().lights_at(start).unwrap()[0].main_status, LightStatus::Green);
+        assert!(left[start..start + 5].iter().all(|cars| *cars == 0), "{:?}", &left[start..start + 10]);
+        assert!(left[start + 5] > 0);
+    }
+}
+
+#[test]
+fn switching_the_saturation_flow_starts_the_light_timers_again()
+{
+    let mut simulator = queue(1, SaturationFlow::default());
+    simulator.run(20);
+    assert!(simulator.intersections()[0].shown_for[0][0] > 0.0);
+    simulator.set_saturation_flow(None);
+    simulator.run(50);
+    //the light turned green again while the timers were not kept
+    simulator.set_saturation_flow(Some(SaturationFlow::default()));
+    assert!(simulator.intersections().iter().all(|intersection| intersection.shown_for == [[0.0; 2]; 4] && intersection.discharge == [0.0; 4]));
+    let left = discharged(&mut simulator, 10);
+    assert_eq!(left[..2], [0, 0]);
+    assert!(left[2..].iter().sum::<usize>() > 0);
+}
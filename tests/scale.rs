@@ -0,0 +1,50 @@
+use std::collections::HashSet;
+use traffic_sim::simulator::{Simulator, Current};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan};
+
+const SIDE : u32 = 10;
+
+///A grid of uncontrolled intersections, numbered row by row from the north west corner
+fn grid() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(SIDE * SIDE);
+    for row in 0..SIDE{
+        for column in 0..SIDE{
+            let id = row * SIDE + column + 1;
+            if column + 1 < SIDE{
+                simulator.add_road(IntersectionId(id), 1, IntersectionId(id + 1), 4);
+            }
+            if row + 1 < SIDE{
+                simulator.add_road(IntersectionId(id), 2, IntersectionId(id + SIDE), 4);
+            }
+            simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+        }
+    }
+    simulator
+}
+
+#[test]
+fn tens_of_thousands_of_vehicles_reuse_the_ids_of_cars_that_arrived()
+{
+    let mut simulator = grid();
+    let trips = 20_000;
+    for trip in 0..trips{
+        //every intersection starts and ends trips, never at the intersection they start from
+        let origin = trip % (SIDE * SIDE) + 1;
+        let offset = SIDE + 1 + trip / (SIDE * SIDE) % (SIDE * SIDE - SIDE - 1);
+        let destination = (origin - 1 + offset) % (SIDE * SIDE) + 1;
+        simulator.schedule_trip(trip as usize / 10, Current{int_id: IntersectionId(origin), direction: 0}, IntersectionId(destination));
+    }
+    simulator.run(4000);
+
+    let completed = simulator.completed_trips();
+    assert_eq!(completed.len(), trips as usize);
+    let serials: HashSet<_> = completed.iter().map(|trip| trip.serial).collect();
+    assert_eq!(serials.len(), completed.len());
+    assert_eq!((*serials.iter().min().unwrap(), *serials.iter().max().unwrap()), (1, trips as u64));
+    //no more cars are on the roads at once than a few ticks of departures, so few IDs are needed
+    let ids: HashSet<_> = completed.iter().map(|trip| trip.car_id).collect();
+    assert_eq!(ids.iter().min().unwrap().0, 1);
+    assert!(ids.len() < trips as usize / 10, "{} IDs for {} trips", ids.len(), trips);
+}
@@ -0,0 +1,133 @@
+use traffic_sim::scenario::{Scenario, ScenarioError, CarSpec};
+use traffic_sim::simulator::{Simulator, Current, transit::{Schedule, TransitLine}, demand::{Demand, OdPair, TimeProfile}, microscopic::Idm, saturation::SaturationFlow, violations::NonCompliance};
+use traffic_sim::traffic_logic::{car::TurningProportions, driver::DriverProfile, intersection::{IntersectionId, SignalPlan}, road::CardinalDirection::{East, West}, vehicle::VehicleClass};
+
+///A row of three intersections joined from west to east
+fn busy_simulator() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 6);
+    simulator.add_road(IntersectionId(2), 1, IntersectionId(3), 6);
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::uncontrolled());
+    for id in 2..=3{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::four_phase(10, 5));
+    }
+    simulator.set_turning_proportions(IntersectionId(2), 3, Some(TurningProportions::new(0.0, 3.0, 1.0, 0.0)));
+    simulator.close_arm(IntersectionId(3), 0, 0, 500);
+    simulator.add_transit_line(TransitLine::new("1", vec![IntersectionId(1), IntersectionId(2), IntersectionId(3)], Schedule::Headway{first: 0, headway: 100, count: 3}).stop(1, 2, 60.0));
+    let truck = simulator.add_trip(Current{int_id: IntersectionId(1), direction: 3}, IntersectionId(3)).unwrap();
+    simulator.set_vehicle_class(truck, VehicleClass::Truck);
+    simulator.set_driver_profile(truck, Some(DriverProfile::Cautious));
+    simulator.schedule_trip(200, Current{int_id: IntersectionId(3), direction: 1}, IntersectionId(1));
+    //the truck waits out its driver's reaction and its PCE before it sets off
+    while simulator.get_position(truck).unwrap().in_between.is_none(){
+        simulator.run(1);
+    }
+    simulator
+}
+
+#[test]
+fn a_saved_simulator_builds_the_same_scenario_again()
+{
+    let simulator = busy_simulator();
+    let scenario = Scenario::from_simulator(&simulator);
+    assert_eq!(scenario.tick, simulator.timestep());
+    assert_eq!(scenario.turning.len(), 1);
+    assert_eq!(scenario.closures.len(), 1);
+    assert_eq!(scenario.transit_lines[0].dispatched, 1);
+    assert_eq!(scenario.demand[0].destination.as_deref(), Some("1"));
+
+    let truck = scenario.cars.iter().find(|car| car.class == VehicleClass::Truck).unwrap();
+    assert_eq!(truck.driver, Some(DriverProfile::Cautious));
+    assert_eq!(truck.on_road.as_ref().unwrap().from, "1");
+    assert_eq!(truck.trip.as_ref().unwrap().destination, "3");
+    assert_eq!(truck.trip.as_ref().unwrap().route, vec!["2".to_string(), "3".to_string()]);
+    let bus = scenario.cars.iter().find(|car| car.bus.is_some()).unwrap();
+    assert_eq!(bus.class, VehicleClass::Bus);
+
+    let toml = Scenario::from_toml_str(&scenario.to_toml_string().unwrap()).unwrap();
+    let json = Scenario::from_json_str(&scenario.to_json_string().unwrap()).unwrap();
+    assert_eq!(toml, scenario);
+    assert_eq!(json, scenario);
+
+    let rebuilt = scenario.to_simulator().unwrap();
+    assert_eq!(rebuilt.timestep(), simulator.timestep());
+    assert_eq!(Scenario::from_simulator(&rebuilt), scenario);
+}
+
+#[test]
+fn a_rebuilt_simulator_finishes_the_trips_it_was_saved_with()
+{
+    let mut rebuilt = Scenario::from_simulator(&busy_simulator()).to_simulator().unwrap();
+    rebuilt.run(400);
+    let truck = rebuilt.completed_trips().iter().find(|trip| trip.class == VehicleClass::Truck).unwrap();
+    assert_eq!(truck.depart_tick, 0);
+    assert_eq!(truck.driver, Some(DriverProfile::Cautious));
+    assert_eq!(truck.path.iter().map(|step| step.0).collect::<Vec<_>>(), vec![IntersectionId(1), IntersectionId(2)]);
+    assert!(rebuilt.completed_trips().iter().any(|trip| trip.depart_tick == 200 && trip.destination == IntersectionId(1)));
+    assert_eq!(rebuilt.line_metrics()[0].dispatched, 3);
+}
+
+#[test]
+fn cars_at_unknown_intersections_are_reported()
+{
+    let mut scenario = Scenario::from_simulator(&busy_simulator());
+    scenario.cars.push(CarSpec::waiting("Nowhere", East));
+    assert!(matches!(scenario.to_simulator(), Err(ScenarioError::UnknownIntersection(name)) if name == "Nowhere"));
+}
+
+#[test]
+fn routes_between_unconnected_intersections_are_reported()
+{
+    let mut scenario = Scenario::from_simulator(&busy_simulator());
+    let truck = scenario.cars.iter_mut().find(|car| car.trip.is_some()).unwrap();
+    truck.trip.as_mut().unwrap().route = vec!["2".to_string(), "2".to_string()];
+    assert!(matches!(scenario.to_simulator(), Err(ScenarioError::NoRoad{..})));
+    scenario.cars.clear();
+    scenario.cars.push(CarSpec::waiting("1", West));
+    assert!(scenario.to_simulator().is_ok());
+}
+
+#[test]
+fn the_settings_of_the_run_are_saved_with_the_traffic()
+{
+    let mut simulator = busy_simulator();
+    simulator.set_demand(Demand::new(vec![OdPair{origin: IntersectionId(1), destination: IntersectionId(3), trips_per_hour: 300.0}])
+        .with_profile(TimeProfile::new(100, vec![1.0, 0.5])).with_classes(vec![(VehicleClass::PassengerCar, 0.9), (VehicleClass::Truck, 0.1)]));
+    simulator.add_incident(IntersectionId(1), IntersectionId(2), 0, 300, 0.5);
+    simulator.set_driver_mix(vec![(DriverProfile::Normal, 0.7), (DriverProfile::Aggressive, 0.3)]);
+    simulator.set_reroute_share(0.25);
+    simulator.set_queue_delay(3.0);
+    simulator.set_microscopic(Some(Idm::default()));
+    simulator.set_saturation_flow(Some(SaturationFlow::default()));
+    let mut non_compliance = NonCompliance::default();
+    non_compliance.red.insert(None, 0.01);
+    non_compliance.red.insert(Some(DriverProfile::Aggressive), 0.05);
+    non_compliance.approach_factors.insert((IntersectionId(2), 3), 2.0);
+    simulator.set_non_compliance(non_compliance.clone());
+
+    let scenario = Scenario::from_simulator(&simulator);
+    assert_eq!(Scenario::from_toml_str(&scenario.to_toml_string().unwrap()).unwrap(), scenario);
+    assert_eq!(Scenario::from_json_str(&scenario.to_json_string().unwrap()).unwrap(), scenario);
+    let rebuilt = scenario.to_simulator().unwrap();
+    assert_eq!(rebuilt.demand(), simulator.demand());
+    assert_eq!(rebuilt.incidents(), simulator.incidents());
+    assert_eq!(rebuilt.driver_mix(), simulator.driver_mix());
+    assert_eq!(rebuilt.reroute_share(), 0.25);
+    assert_eq!(rebuilt.queue_delay(), 3.0);
+    assert_eq!(rebuilt.microscopic(), Some(&Idm::default()));
+    assert_eq!(rebuilt.saturation_flow(), Some(&SaturationFlow::default()));
+    assert_eq!(rebuilt.non_compliance(), Some(&non_compliance));
+    assert_eq!(Scenario::from_simulator(&rebuilt), scenario);
+}
+
+#[test]
+fn buses_of_unknown_lines_name_the_line()
+{
+    let mut scenario = Scenario::from_simulator(&busy_simulator());
+    let bus = scenario.cars.iter_mut().find_map(|car| car.bus.as_mut()).unwrap();
+    assert_eq!(bus.line, "1");
+    bus.line = "Night".to_string();
+    assert!(matches!(scenario.to_simulator(), Err(ScenarioError::InvalidLine(name)) if name == "Night"));
+}
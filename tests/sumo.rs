@@ -0,0 +1,107 @@
+use traffic_sim::scenario::{Scenario, ScenarioError, IntersectionSpec, RoadSpec, sumo::{SumoOptions, parse_sumo_net, parse_sumo_plain, parse_sumo_routes, sumo_plain_strings}};
+use traffic_sim::traffic_logic::{intersection::{IntersectionId, SignalPlan}, road::CardinalDirection::{East, West}};
+
+const NODES : &str = r#"<nodes>
+    <node id="A" x="0" y="0" type="priority"/>
+    <node id="B" x="0" y="100" type="priority"/>
+    <node id="C" x="100" y="100" type="priority"/>
+    <node id="D" x="100" y="0" type="priority"/>
+</nodes>"#;
+
+//the road through D is much longer, so the shortest route from A to C goes through B
+const EDGES : &str = r#"<edges>
+    <edge id="AB" from="A" to="B"/>
+    <edge id="BC" from="B" to="C"/>
+    <edge id="AD" from="A" to="D" length="600"/>
+    <edge id="DC" from="D" to="C"/>
+</edges>"#;
+
+const ROUTES : &str = r#"<routes>
+    <vehicle id="slow" depart="0">
+        <route edges="AD DC"/>
+    </vehicle>
+</routes>"#;
+
+#[test]
+fn vehicles_follow_their_sumo_route()
+{
+    let options = SumoOptions::default();
+    let import = parse_sumo_plain(NODES, EDGES, &options).unwrap();
+    let routes = parse_sumo_routes(ROUTES, &import.edges, &options).unwrap();
+    let mut scenario = import.scenario;
+    let departure = routes.vehicles[0].departure(&scenario).unwrap();
+    assert_eq!(departure.route, vec!["A".to_string(), "D".to_string(), "C".to_string()]);
+    scenario.demand.push(departure);
+
+    let mut simulator = scenario.to_simulator().unwrap();
+    simulator.run(200);
+    let trip = &simulator.completed_trips()[0];
+    let names: Vec<&str> = scenario.intersections.iter().map(|intersection| intersection.name.as_str()).collect();
+    let path: Vec<&str> = trip.path.iter().map(|step| names[step.0.0 as usize - 1]).collect();
+    assert_eq!(path, vec!["A", "D"]);
+    assert_eq!(trip.destination, IntersectionId(3));
+}
+
+#[test]
+fn exporting_roads_to_unknown_intersections_is_an_error()
+{
+    let mut scenario = Scenario::default();
+    scenario.intersections.push(IntersectionSpec{name: "A".to_string(), signal_plan: None, position: None});
+    scenario.roads.push(RoadSpec{from: "A".to_string(), direction: East, to: "Z".to_string(), to_direction: None, distance: 5, lanes: 1, one_way: false, shape: Vec::new()});
+    assert!(matches!(sumo_plain_strings(&scenario, &SumoOptions::default()), Err(ScenarioError::UnknownIntersection(name)) if name == "Z"));
+}
+
+#[test]
+fn intersections_without_positions_are_laid_out_along_their_roads()
+{
+    let mut scenario = Scenario::default();
+    for name in ["A", "B"]{
+        scenario.intersections.push(IntersectionSpec{name: name.to_string(), signal_plan: None, position: None});
+    }
+    scenario.roads.push(RoadSpec{from: "A".to_string(), direction: East, to: "B".to_string(), to_direction: None, distance: 10, lanes: 1, one_way: false, shape: Vec::new()});
+    let options = SumoOptions{default_speed: 10.0, ..Default::default()};
+    let (nodes, edges) = sumo_plain_strings(&scenario, &options).unwrap();
+    assert!(nodes.contains(r#"<node id="A" x="0.00" y="0.00""#));
+    assert!(nodes.contains(r#"<node id="B" x="100.00" y="0.00""#));
+    assert!(edges.contains(r#"<edge id="A_B" from="A" to="B""#));
+    assert!(edges.contains(r#"<edge id="B_A" from="B" to="A""#));
+}
+
+//a compiled network of a signalized junction J with two lanes towards K and one lane back, and an internal junction and edge
+const NET : &str = r#"<net version="1.9">
+    <location netOffset="0.00,0.00" projParameter="!"/>
+    <edge id=":J_0" function="internal">
+        <lane id=":J_0_0" index="0" speed="13.89" length="5.00" shape="0.00,0.00 0.00,5.00"/>
+    </edge>
+    <edge id="JK" from="J" to="K" priority="1">
+        <lane id="JK_0" index="0" speed="10.00" length="200.00" shape="0.00,0.00 200.00,0.00"/>
+        <lane id="JK_1" index="1" speed="10.00" length="200.00" shape="0.00,3.20 200.00,3.20"/>
+    </edge>
+    <edge id="KJ" from="K" to="J" priority="1">
+        <lane id="KJ_0" index="0" speed="10.00" length="200.00" shape="200.00,-3.20 0.00,-3.20"/>
+    </edge>
+    <junction id="J" type="traffic_light" x="0.00" y="0.00" incLanes="KJ_0" intLanes=":J_0_0" shape=""/>
+    <junction id="K" type="priority" x="200.00" y="0.00" incLanes="JK_0 JK_1" intLanes="" shape=""/>
+    <junction id=":J_0_0" type="internal" x="0.00" y="2.00" incLanes="" intLanes=""/>
+</net>"#;
+
+#[test]
+fn compiled_networks_are_read_from_their_lanes()
+{
+    let import = parse_sumo_net(NET, &SumoOptions::default()).unwrap();
+    let scenario = &import.scenario;
+    assert_eq!(scenario.intersections.iter().map(|intersection| intersection.name.as_str()).collect::<Vec<_>>(), vec!["J", "K"]);
+    assert_eq!(scenario.intersections[0].signal_plan, Some(SignalPlan::four_phase(20, 5)));
+    assert!(scenario.intersections[1].signal_plan.as_ref().unwrap().is_uncontrolled());
+    assert_eq!(scenario.roads.len(), 1);
+    let road = &scenario.roads[0];
+    assert_eq!((road.from.as_str(), road.direction, road.to.as_str(), road.to_direction), ("J", East, "K", None));
+    //200 metres at the 10 m/s of the lanes
+    assert_eq!((road.distance, road.lanes, road.one_way), (20, 2, false));
+    assert_eq!(import.edges.len(), 2);
+    assert_eq!(import.edges["KJ"], ("K".to_string(), "J".to_string()));
+    //the way back has one lane fewer
+    assert_eq!(import.warnings.len(), 1);
+    assert!(import.warnings[0].contains("KJ"));
+    assert_eq!(scenario.arm_towards("K", "J"), Some(West));
+}
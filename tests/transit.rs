@@ -0,0 +1,73 @@
+use traffic_sim::simulator::{Simulator, transit::{Schedule, TransitLine}};
+use traffic_sim::traffic_logic::intersection::{IntersectionId, SignalPlan, Phase, LightConfig};
+
+///Three uncontrolled intersections in a row from west to east
+fn corridor() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 10);
+    simulator.add_road(IntersectionId(2), 1, IntersectionId(3), 10);
+    for id in 1..=3{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator
+}
+
+fn line(passengers_per_hour : f64) -> TransitLine
+{
+    TransitLine::new("1", vec![IntersectionId(1), IntersectionId(2), IntersectionId(3)], Schedule::Headway{first: 0, headway: 40, count: 3})
+        .stop(1, 5, passengers_per_hour)
+}
+
+#[test]
+fn buses_stand_at_stops_for_the_dead_time_and_their_boarding()
+{
+    let mut simulator = corridor();
+    simulator.add_transit_line(line(0.0)).unwrap();
+    simulator.run(200);
+    let events = simulator.stop_events();
+    assert_eq!(events.len(), 3);
+    for (run, event) in events.iter().enumerate(){
+        assert_eq!((event.run, event.stop, event.boarded), (run, 0, 0));
+        assert_eq!(event.departure_tick - event.arrival_tick, 5);
+    }
+
+    let mut simulator = corridor();
+    simulator.add_transit_line(line(3600.0)).unwrap();
+    simulator.run(400);
+    assert!(simulator.stop_events()[0].boarded > 0);
+    for event in simulator.stop_events(){
+        assert_eq!(event.departure_tick - event.arrival_tick, 5 + event.boarded * 2);
+    }
+}
+
+#[test]
+fn buses_on_time_keep_their_headway()
+{
+    let mut simulator = corridor();
+    simulator.add_transit_line(line(0.0)).unwrap();
+    simulator.run(200);
+    let metrics = &simulator.line_metrics()[0];
+    assert_eq!((metrics.dispatched, metrics.stop_visits), (3, 3));
+    assert!(metrics.mean_delay.abs() <= 1.0, "{:?}", metrics);
+    assert_eq!(metrics.on_time_share, 1.0);
+    assert_eq!(metrics.mean_headway, 40.0);
+    assert_eq!((metrics.headway_cv, metrics.bunching_share), (0.0, 0.0));
+}
+
+#[test]
+fn buses_held_at_a_light_arrive_bunched()
+{
+    let mut simulator = corridor();
+    //buses from 1 wait at side 3 of 2 and go on the east west light, which only turns green after 100 ticks
+    let phases = vec![Phase{config: LightConfig::NorthSouth, duration: 100}, Phase{config: LightConfig::EastWest, duration: 1000}];
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::new(phases, 0));
+    simulator.add_transit_line(line(0.0)).unwrap();
+    simulator.run(300);
+    let metrics = &simulator.line_metrics()[0];
+    assert_eq!(metrics.stop_visits, 3);
+    assert!(metrics.mean_delay > 20.0, "{:?}", metrics);
+    assert!(metrics.mean_headway < 20.0, "{:?}", metrics);
+    assert_eq!(metrics.bunching_share, 1.0);
+}
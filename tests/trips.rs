@@ -0,0 +1,129 @@
+use std::collections::{HashSet, VecDeque};
+use traffic_sim::simulator::{Simulator, Current, Position};
+use traffic_sim::traffic_logic::{intersection::{IntersectionId, SignalPlan, Phase, LightConfig}, route::Route};
+
+///A square of uncontrolled intersections, the way from 1 to 3 through 2 is shorter than through 4
+fn square() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(4);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    simulator.add_road(IntersectionId(2), 0, IntersectionId(3), 5);
+    simulator.add_road(IntersectionId(1), 0, IntersectionId(4), 8);
+    simulator.add_road(IntersectionId(4), 1, IntersectionId(3), 8);
+    for id in 1..=4{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator
+}
+
+#[test]
+fn trips_take_the_shortest_route_and_leave_when_they_arrive()
+{
+    let mut simulator = square();
+    let car = simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(3)).unwrap();
+    simulator.run(40);
+    assert!(simulator.get_position(car).is_none());
+    assert!(simulator.cars().is_empty());
+
+    let trip = &simulator.completed_trips()[0];
+    assert_eq!((trip.car_id, trip.origin, trip.destination, trip.free_flow_time), (car, IntersectionId(1), IntersectionId(3), 10));
+    let roads: Vec<_> = trip.path.iter().map(|step| (step.0, step.1)).collect();
+    assert_eq!(roads, vec![(IntersectionId(1), 1), (IntersectionId(2), 0)]);
+    assert_eq!(trip.travel_time(), 8);
+    assert_eq!(trip.route_changes, 0);
+}
+
+#[test]
+fn the_id_of_a_car_that_arrived_goes_to_the_next_car_and_trips_keep_their_own_serial()
+{
+    let mut simulator = square();
+    let first = simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(3)).unwrap();
+    simulator.run(40);
+    let second = simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(3)).unwrap();
+    simulator.run(40);
+    assert_eq!(first, second);
+
+    let serials: Vec<_> = simulator.completed_trips().iter().map(|trip| (trip.car_id, trip.serial)).collect();
+    assert_eq!(serials, vec![(first, 1), (first, 2)]);
+}
+
+#[test]
+fn trips_on_a_given_route_follow_it()
+{
+    let mut simulator = square();
+    let route = Route{links: VecDeque::from([(IntersectionId(1), 0), (IntersectionId(4), 1)]), cost: 16};
+    simulator.add_trip_on_route(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(3), route).unwrap();
+    simulator.run(60);
+    let roads: Vec<_> = simulator.completed_trips()[0].path.iter().map(|step| (step.0, step.1)).collect();
+    assert_eq!(roads, vec![(IntersectionId(1), 0), (IntersectionId(4), 1)]);
+}
+
+#[test]
+fn shortest_paths_skip_closed_roads_and_unreachable_trips_are_not_started()
+{
+    let simulator = square();
+    let closed = HashSet::from([(IntersectionId(1), 1)]);
+    let route = simulator.road().shortest_path(IntersectionId(1), IntersectionId(3), &closed).unwrap();
+    assert_eq!(route, Route{links: VecDeque::from([(IntersectionId(1), 0), (IntersectionId(4), 1)]), cost: 16});
+
+    let mut simulator = square();
+    simulator.add_intersections(1);
+    assert_eq!(simulator.add_trip(Current{int_id: IntersectionId(1), direction: 2}, IntersectionId(5)), None);
+    assert!(simulator.cars().is_empty());
+}
+
+///The square with a road into 1 from the south and cars held at 2 by a light that never lets them go
+fn queue_at_2(queued : usize) -> Simulator
+{
+    let mut simulator = square();
+    simulator.add_intersections(1);
+    simulator.add_road(IntersectionId(5), 0, IntersectionId(1), 2);
+    simulator.set_signal_plan(IntersectionId(5), SignalPlan::uncontrolled());
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::new(vec![Phase{config: LightConfig::NorthSouth, duration: 1000}], 0));
+    for _ in 0..queued{
+        simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(2), direction: 3}), None));
+    }
+    simulator
+}
+
+#[test]
+fn queued_cars_make_roads_cost_more()
+{
+    let mut simulator = queue_at_2(5);
+    assert_eq!(simulator.queue_delay(), 2.0);
+    assert_eq!(simulator.live_link_costs()[&(IntersectionId(1), 1)], 15);
+    assert_eq!(simulator.live_link_costs()[&(IntersectionId(1), 0)], 8);
+    simulator.set_queue_delay(1.0);
+    assert_eq!(simulator.live_link_costs()[&(IntersectionId(1), 1)], 10);
+}
+
+#[test]
+fn rerouting_cars_drive_around_a_queue()
+{
+    let next_after_1 = |queue_delay : f64| {
+        let mut simulator = queue_at_2(8);
+        simulator.set_reroute_share(1.0);
+        simulator.set_queue_delay(queue_delay);
+        let car = simulator.add_trip(Current{int_id: IntersectionId(5), direction: 2}, IntersectionId(3)).unwrap();
+        loop{
+            simulator.run(1);
+            if let Some(between) = simulator.get_position(car).unwrap().in_between.filter(|between| between.int_1_id == IntersectionId(1)){
+                return between.int_2_id;
+            }
+        }
+    };
+    assert_eq!(next_after_1(2.0), IntersectionId(4));
+    //without a delay for the queue the live cost is the distance
+    assert_eq!(next_after_1(0.0), IntersectionId(2));
+
+    let mut simulator = queue_at_2(8);
+    simulator.set_reroute_share(1.0);
+    simulator.add_trip(Current{int_id: IntersectionId(5), direction: 2}, IntersectionId(3)).unwrap();
+    simulator.run(60);
+    let trip = &simulator.completed_trips()[0];
+    assert!(trip.reroutes);
+    assert_eq!(trip.route_changes, 1);
+    assert_eq!(trip.path[1].0, IntersectionId(1));
+    assert_eq!(trip.path[1].1, 0);
+}
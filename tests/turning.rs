@@ -0,0 +1,70 @@
+use traffic_sim::simulator::{Simulator, Current, Position};
+use traffic_sim::traffic_logic::{car::{Direction, TurningProportions}, intersection::{IntersectionId, SignalPlan}};
+
+///An uncontrolled intersection 1 with a road to another intersection on every arm, roads have to be
+/// long enough to hold the cars that stay at their end
+fn crossroads(distance : u32) -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(5);
+    for arm in 0..4u8{
+        simulator.add_road(IntersectionId(1), arm, IntersectionId(u32::from(arm) + 2), distance);
+    }
+    for id in 1..=5{
+        simulator.set_signal_plan(IntersectionId(id), SignalPlan::uncontrolled());
+    }
+    simulator
+}
+
+///Places cars at an intersection and counts the cars that leave 1 on each arm
+fn turns(mut simulator : Simulator, start : Current, cars : usize) -> [usize; 4]
+{
+    let ids: Vec<_> = (0..cars).map(|_| simulator.add_car(Position::new(Some(start), None))).collect();
+    let mut turns = [0; 4];
+    let mut left = ids.clone();
+    while !left.is_empty(){
+        simulator.run(1);
+        left.retain(|car| {
+            let Some(between) = simulator.get_position(*car).and_then(|pos| pos.in_between).filter(|between| between.int_1_id == IntersectionId(1)) else { return true };
+            let arm = simulator.road().road[&IntersectionId(1)].iter().position(|node| node.is_some_and(|node| node.dest_int_id == between.int_2_id)).unwrap();
+            turns[arm] += 1;
+            false
+        });
+        assert!(simulator.timestep() < cars * 4, "cars did not leave intersection 1");
+    }
+    turns
+}
+
+#[test]
+fn cars_turn_in_proportion_to_the_shares()
+{
+    let mut simulator = crossroads(400);
+    simulator.set_turning_proportions(IntersectionId(1), 2, Some(TurningProportions::new(0.2, 0.5, 0.3, 0.0)));
+    let turns = turns(simulator, Current{int_id: IntersectionId(1), direction: 2}, 1000);
+    let share = |turn| turns[usize::from(Direction::get_next_direction(2, turn))] as f64 / 1000.0;
+    //each share is within about 4 standard deviations
+    assert!((share(Direction::Left) - 0.2).abs() < 0.05);
+    assert!((share(Direction::Straight) - 0.5).abs() < 0.06);
+    assert!((share(Direction::Right) - 0.3).abs() < 0.06);
+    assert_eq!(share(Direction::UTurn), 0.0);
+}
+
+#[test]
+fn closed_roads_share_their_turns_among_the_open_ones()
+{
+    let mut simulator = crossroads(400);
+    simulator.set_turning_proportions(IntersectionId(1), 2, Some(TurningProportions::new(0.0, 0.5, 0.5, 0.0)));
+    simulator.close_arm(IntersectionId(1), Direction::get_next_direction(2, Direction::Straight), 0, 10000);
+    let turns = turns(simulator, Current{int_id: IntersectionId(1), direction: 2}, 100);
+    assert_eq!(turns[usize::from(Direction::get_next_direction(2, Direction::Right))], 100);
+}
+
+#[test]
+fn cars_driving_through_pick_their_turn_from_the_side_they_arrive_at()
+{
+    let mut simulator = crossroads(5);
+    simulator.set_turning_proportions(IntersectionId(1), 2, Some(TurningProportions::new(1.0, 0.0, 0.0, 0.0)));
+    //intersection 4 only has the road north to 1, so every car from it arrives at the south side of 1
+    let turns = turns(simulator, Current{int_id: IntersectionId(4), direction: 2}, 50);
+    assert_eq!(turns[usize::from(Direction::get_next_direction(2, Direction::Left))], 50);
+}
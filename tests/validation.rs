@@ -0,0 +1,79 @@
+use traffic_sim::simulator::{Simulator, Current};
+use traffic_sim::traffic_logic::{intersection::IntersectionId, road::{Node, Road}, validation::{Issue, Severity}};
+
+#[test]
+fn a_ring_of_roads_has_no_issues()
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(4);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    simulator.add_road(IntersectionId(2), 2, IntersectionId(3), 5);
+    simulator.add_road(IntersectionId(3), 3, IntersectionId(4), 5);
+    simulator.add_road(IntersectionId(4), 0, IntersectionId(1), 5);
+    let report = simulator.validate();
+    assert_eq!(report.issues, vec![]);
+    assert!(report.is_ok());
+}
+
+#[test]
+fn dead_ends_and_lonely_intersections_are_reported()
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(3);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    let report = simulator.validate();
+    assert_eq!(report.issues, vec![
+        Issue::DeadEnd{int_id: IntersectionId(1), side: 1},
+        Issue::DeadEnd{int_id: IntersectionId(2), side: 3},
+        Issue::NoRoads(IntersectionId(3))
+    ]);
+    assert_eq!(report.errors().collect::<Vec<_>>(), vec![&Issue::NoRoads(IntersectionId(3))]);
+    assert!(!report.is_ok());
+}
+
+#[test]
+fn disconnected_groups_are_listed_by_id()
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(4);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(3), 5);
+    simulator.add_road(IntersectionId(2), 1, IntersectionId(4), 5);
+    let report = simulator.validate();
+    assert!(report.issues.contains(&Issue::DisconnectedComponents(vec![
+        vec![IntersectionId(1), IntersectionId(3)],
+        vec![IntersectionId(2), IntersectionId(4)]
+    ])));
+    assert!(report.is_ok());
+}
+
+#[test]
+fn roads_that_disagree_with_the_road_back_are_reported()
+{
+    let mut road = Road::new();
+    road.road.insert(IntersectionId(1), [None, Some(Node::new(IntersectionId(2), 5, 3, 1)), Some(Node::new(IntersectionId(3), 1, 0, 1)), None]);
+    road.road.insert(IntersectionId(2), [None, None, None, Some(Node::new(IntersectionId(1), 7, 2, 1))]);
+    road.road.insert(IntersectionId(3), [Some(Node::new(IntersectionId(1), 1, 2, 1)), None, None, None]);
+    let report = road.validate(&[IntersectionId(1), IntersectionId(2), IntersectionId(3)]);
+    assert!(report.issues.contains(&Issue::DirectionMismatch{from: IntersectionId(1), to: IntersectionId(2), there: (1, 3), back: (3, 2)}));
+    assert!(report.issues.contains(&Issue::DistanceMismatch{from: IntersectionId(1), to: IntersectionId(2), there: 5, back: 7}));
+    assert!(report.issues.contains(&Issue::TooShort{from: IntersectionId(1), to: IntersectionId(3), distance: 1}));
+    //cars from 2 arrive at the arm of 1 whose road leads to 3
+    assert!(report.issues.contains(&Issue::ArmConflict{int_id: IntersectionId(1), arm: 2, arriving_from: IntersectionId(2), leaving_to: IntersectionId(3)}));
+    assert_eq!(Issue::TooShort{from: IntersectionId(1), to: IntersectionId(3), distance: 1}.severity(), Severity::Error);
+}
+
+#[test]
+fn cars_and_roads_to_missing_intersections_are_errors()
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(2);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), 5);
+    simulator.schedule_trip(5, Current{int_id: IntersectionId(1), direction: 3}, IntersectionId(9));
+    assert!(simulator.validate().errors().any(|issue| *issue == Issue::MissingIntersection(IntersectionId(9))));
+
+    let mut road = Road::new();
+    road.road.insert(IntersectionId(1), [Some(Node::new(IntersectionId(5), 4, 2, 1)), None, None, None]);
+    let report = road.validate(&[IntersectionId(1)]);
+    assert_eq!(report.issues, vec![Issue::MissingIntersection(IntersectionId(5))]);
+    assert!(!report.is_ok());
+}
@@ -0,0 +1,108 @@
+use traffic_sim::simulator::{Simulator, Current, Position};
+use traffic_sim::traffic_logic::{intersection::{IntersectionId, SignalPlan, Phase, LightConfig}, vehicle::VehicleClass};
+
+const WEST_OF_1 : Current = Current{int_id: IntersectionId(1), direction: 3};
+
+///Two intersections joined by a road from west to east, 1 always lets cars from the west go east
+fn corridor(distance : u32) -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(2);
+    simulator.add_road(IntersectionId(1), 1, IntersectionId(2), distance);
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::new(vec![Phase{config: LightConfig::EastWest, duration: 1000}], 0));
+    simulator.set_signal_plan(IntersectionId(2), SignalPlan::uncontrolled());
+    simulator
+}
+
+///Drives one trip of a class from 1 to 2 and gives its travel time
+fn travel_time(class : VehicleClass) -> usize
+{
+    let mut simulator = corridor(10);
+    let car = simulator.add_trip(WEST_OF_1, IntersectionId(2)).unwrap();
+    simulator.set_vehicle_class(car, class);
+    simulator.run(60);
+    simulator.completed_trips()[0].travel_time()
+}
+
+#[test]
+fn slow_classes_take_longer_to_drive_a_road()
+{
+    let car = travel_time(VehicleClass::PassengerCar);
+    //the time on the road differs, and a truck counts as two cars so it waits a tick longer to leave 1
+    for (class, held) in [(VehicleClass::Truck, 1), (VehicleClass::Bicycle, 0)]{
+        let extra = class.travel_ticks(10, true) - VehicleClass::PassengerCar.travel_ticks(10, true);
+        assert!(extra > 0);
+        assert_eq!(travel_time(class), car + extra as usize + held, "{:?}", class);
+    }
+}
+
+#[test]
+fn vehicles_counting_as_two_cars_hold_the_queue_a_tick_longer()
+{
+    let mut simulator = corridor(10);
+    let classes = [VehicleClass::PassengerCar, VehicleClass::Truck, VehicleClass::PassengerCar, VehicleClass::PassengerCar];
+    let cars: Vec<_> = classes.iter().map(|class| {
+        let car = simulator.add_car(Position::new(Some(WEST_OF_1), None));
+        simulator.set_vehicle_class(car, *class);
+        car
+    }).collect();
+    let mut left = vec![None; cars.len()];
+    for tick in 1..=10{
+        simulator.run(1);
+        for (car, left) in cars.iter().zip(left.iter_mut()){
+            if left.is_none() && simulator.get_position(*car).is_some_and(|pos| pos.in_between.is_some()){
+                *left = Some(tick);
+            }
+        }
+    }
+    let left: Vec<usize> = left.into_iter().map(Option::unwrap).collect();
+    assert_eq!(left.windows(2).map(|pair| pair[1] - pair[0]).collect::<Vec<_>>(), vec![2, 1, 1]);
+}
+
+#[test]
+fn cars_do_not_drive_onto_a_full_road()
+{
+    let fill = |class : VehicleClass| {
+        let mut simulator = corridor(2);
+        //cars from 1 wait at the west side of 2 and its light never lets them go
+        simulator.set_signal_plan(IntersectionId(2), SignalPlan::new(vec![Phase{config: LightConfig::NorthSouth, duration: 1000}], 0));
+        for _ in 0..10{
+            let car = simulator.add_car(Position::new(Some(WEST_OF_1), None));
+            simulator.set_vehicle_class(car, class);
+        }
+        simulator.run(40);
+        let storage = simulator.link_storage(IntersectionId(1), 1).unwrap();
+        let occupancy = simulator.link_occupancy()[&(IntersectionId(1), 1)];
+        //the last vehicle let on found the road with room left
+        assert!(occupancy >= storage && occupancy - class.storage_spaces() < storage, "{} spaces on a road of {}", occupancy, storage);
+        simulator.intersections()[0].light_queues[3].len()
+    };
+    assert!((VehicleClass::PassengerCar.storage_spaces() - 1.0).abs() < 1e-9);
+    //a road of 2 ticks holds 3.7 cars
+    assert_eq!(fill(VehicleClass::PassengerCar), 6);
+    assert_eq!(fill(VehicleClass::Truck), 8);
+}
+
+#[test]
+fn metrics_are_broken_down_by_class()
+{
+    let mut simulator = corridor(10);
+    for class in [VehicleClass::PassengerCar, VehicleClass::Truck, VehicleClass::Truck]{
+        let car = simulator.add_trip(WEST_OF_1, IntersectionId(2)).unwrap();
+        simulator.set_vehicle_class(car, class);
+    }
+    simulator.run(60);
+    let bicycle = simulator.add_car(Position::new(Some(WEST_OF_1), None));
+    simulator.set_vehicle_class(bicycle, VehicleClass::Bicycle);
+
+    let metrics = simulator.class_metrics();
+    assert_eq!(metrics.keys().copied().collect::<Vec<_>>(), vec![VehicleClass::PassengerCar, VehicleClass::Truck, VehicleClass::Bicycle]);
+    let trucks: Vec<_> = simulator.completed_trips().iter().filter(|trip| trip.class == VehicleClass::Truck).collect();
+    let truck = metrics[&VehicleClass::Truck];
+    assert_eq!((truck.vehicles, truck.completed_trips), (0, 2));
+    assert_eq!(truck.mean_travel_time, trucks.iter().map(|trip| trip.travel_time()).sum::<usize>() as f64 / 2.0);
+    assert_eq!(truck.mean_wait_time, trucks.iter().map(|trip| trip.wait_time).sum::<usize>() as f64 / 2.0);
+    assert!(truck.mean_travel_time > metrics[&VehicleClass::PassengerCar].mean_travel_time);
+    let bicycles = metrics[&VehicleClass::Bicycle];
+    assert_eq!((bicycles.vehicles, bicycles.completed_trips, bicycles.mean_travel_time), (1, 0, 0.0));
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+use traffic_sim::simulator::{Simulator, Current, Position, violations::{movements_conflict, NonCompliance, ViolationKind}};
+use traffic_sim::traffic_logic::{car::TurningProportions, intersection::{IntersectionId, SignalPlan, Phase, LightConfig}};
+
+///Intersection 1 with a road to another intersection on every arm, its north south light always green
+fn crossroads() -> Simulator
+{
+    let mut simulator = Simulator::new();
+    simulator.add_intersections(5);
+    for arm in 0..4u8{
+        simulator.add_road(IntersectionId(1), arm, IntersectionId(u32::from(arm) + 2), 5);
+    }
+    simulator.set_signal_plan(IntersectionId(1), SignalPlan::new(vec![Phase{config: LightConfig::NorthSouth, duration: 1000}], 0));
+    simulator
+}
+
+fn runs_every_red() -> NonCompliance
+{
+    NonCompliance{red: HashMap::from([(None, 1.0)]), ..Default::default()}
+}
+
+#[test]
+fn conflicts_do_not_depend_on_the_order_of_the_movements()
+{
+    for a in (0..4).flat_map(|side| (0..4).map(move |exit| (side, exit))){
+        for b in (0..4).flat_map(|side| (0..4).map(move |exit| (side, exit))){
+            assert_eq!(movements_conflict(a, b), movements_conflict(b, a), "{:?} {:?}", a, b);
+        }
+    }
+}
+
+#[test]
+fn cars_running_a_red_are_logged_with_the_cars_they_cut_across()
+{
+    let mut simulator = crossroads();
+    simulator.set_non_compliance(runs_every_red());
+    //from the west through to the east on red, and from the north through to the south on green
+    let runner = simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 3}), None));
+    let legal = simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 0}), None));
+    simulator.run(1);
+
+    let violations = simulator.violations();
+    assert_eq!(violations.len(), 1);
+    let violation = &violations[0];
+    assert_eq!((violation.tick, violation.int_id, violation.side, violation.exit), (0, IntersectionId(1), 3, 1));
+    assert_eq!((violation.car_id, violation.driver, violation.kind), (runner, None, ViolationKind::Red));
+    assert_eq!(violation.conflicts, vec![legal]);
+}
+
+#[test]
+fn cars_on_paths_that_keep_clear_are_not_conflicts()
+{
+    let mut simulator = crossroads();
+    simulator.set_non_compliance(runs_every_red());
+    //from the west through to the east on red, and from the north turning right to the west on green
+    simulator.set_turning_proportions(IntersectionId(1), 0, Some(TurningProportions::new(0.0, 0.0, 1.0, 0.0)));
+    simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 3}), None));
+    let legal = simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 0}), None));
+    simulator.run(1);
+    assert!(simulator.get_position(legal).unwrap().in_between.is_some_and(|between| between.int_2_id == IntersectionId(5)));
+    assert_eq!(simulator.violations().len(), 1);
+    assert_eq!(simulator.violations()[0].conflicts, vec![]);
+}
+
+#[test]
+fn cars_wait_at_red_without_non_compliance()
+{
+    let mut simulator = crossroads();
+    let car = simulator.add_car(Position::new(Some(Current{int_id: IntersectionId(1), direction: 3}), None));
+    simulator.run(20);
+    assert!(simulator.violations().is_empty());
+    assert!(simulator.get_position(car).unwrap().current_intersection.is_some_and(|current| current.int_id == IntersectionId(1)));
+}